@@ -11,9 +11,12 @@ use audio::AudioBackendInfo;
 use std::sync::mpsc::Sender;
 use std::sync::mpsc;
 use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
 use std::thread;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
 
 use self::conversions::*;
 
@@ -23,26 +26,82 @@ use std::i16::MAX as i16_max;
 /// Structure for storing count of audio remaining
 struct RemainingAudio {
     frames: i64,
+    /// Called when more audio is needed, for cores which provide audio through a callback.
+    request_callback: Option<Box<Fn() + Send>>,
+    /// When more audio was last requested, if none has been submitted since.
+    requested: Option<Instant>,
+}
+
+impl RemainingAudio {
+    /// Asks for more audio before we run dry, rather than waiting on the next frame. Requests
+    /// which haven't been answered within `timeout` are made again, as the core may not have
+    /// had any audio ready.
+    fn request_if_low(&mut self, target: i64, timeout: Duration, now: Instant) {
+        if self.frames >= target {
+            return;
+        }
+
+        match self.requested {
+            Some(requested) if now.duration_since(requested) < timeout => return,
+            _ => {}
+        }
+
+        if let Some(ref callback) = self.request_callback {
+            self.requested = Some(now);
+            callback();
+        }
+    }
 }
 
 /// Counts audio passing through
-struct AudioCounter<I>
-where
-    I: Iterator,
-{
+struct AudioCounter {
     counter: Arc<Mutex<RemainingAudio>>,
-    incoming: I,
+    incoming: Receiver<i16>,
+    target: i64,
+    /// How long to wait for requested audio before asking again.
+    timeout: Duration,
 }
 
-impl<I> Iterator for AudioCounter<I>
-where
-    I: Iterator,
-{
-    type Item = I::Item;
+impl Iterator for AudioCounter {
+    type Item = i16;
 
-    fn next(&mut self) -> Option<<Self as Iterator>::Item> {
+    fn next(&mut self) -> Option<i16> {
         self.counter.lock().unwrap().frames -= 1;
-        self.incoming.next()
+
+        loop {
+            self.counter
+                .lock()
+                .unwrap()
+                .request_if_low(self.target, self.timeout, Instant::now());
+
+            match self.incoming.recv_timeout(self.timeout) {
+                Ok(v) => return Some(v),
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+    }
+}
+
+/// Returns how long a frame lasts, which requests for audio are given to be answered.
+fn get_request_timeout(fps: f64) -> Duration {
+    Duration::from_millis((1000.0 / fps).ceil() as u64)
+}
+
+/// Returns the amount of samples which should be buffered before another frame is run.
+fn get_buffer_target(sample_rate: u32, fps: f64) -> i64 {
+    (sample_rate as f64 / fps) as i64 * 2
+}
+
+/// Pulls the next sample out of a converter, outputting silence (and flagging `finished`)
+/// once the incoming stream has been closed.
+fn next_sample<I: Iterator<Item = f32>>(converter: &mut I, finished: &mut bool) -> f32 {
+    match converter.next() {
+        Some(v) => v,
+        None => {
+            *finished = true;
+            0.0
+        }
     }
 }
 
@@ -50,6 +109,7 @@ pub struct RodioBackend {
     sink: Sender<i16>,
     samples_remaining: Arc<Mutex<RemainingAudio>>,
     sample_rate: u32,
+    fps: f64,
 }
 
 impl AudioBackend for RodioBackend {
@@ -67,24 +127,34 @@ impl AudioBackend for RodioBackend {
         }
 
         sample_size.frames += frames.len() as i64;
+        sample_size.requested = None;
     }
 
     fn is_done(&self) -> bool {
-        // TODO: Don't hardcode framerate
-        self.samples_remaining.lock().unwrap().frames < self.sample_rate as i64 / 60 * 2
+        self.samples_remaining.lock().unwrap().frames < get_buffer_target(self.sample_rate, self.fps)
     }
 
     fn get_done_callback(&self) -> Box<Fn() -> bool + Send> {
         let inner_samples = self.samples_remaining.clone();
-        let sample_rate = self.sample_rate;
-        Box::new(move || inner_samples.lock().unwrap().frames < sample_rate as i64 / 60 * 2)
+        let target = get_buffer_target(self.sample_rate, self.fps);
+        Box::new(move || inner_samples.lock().unwrap().frames < target)
+    }
+
+    fn set_request_callback(&mut self, callback: Box<Fn() + Send>) {
+        let mut remaining = self.samples_remaining.lock().unwrap();
+        remaining.request_callback = Some(callback);
+        remaining.requested = None;
     }
 }
 
-pub fn build(sample_rate: u32) -> Box<AudioBackend> {
+pub fn build(sample_rate: u32, fps: f64) -> Box<AudioBackend> {
     let (frame_tx, frame_rx): (Sender<i16>, Receiver<i16>) = mpsc::channel();
 
-    let sample_mutex = Arc::new(Mutex::new(RemainingAudio { frames: 0 }));
+    let sample_mutex = Arc::new(Mutex::new(RemainingAudio {
+        frames: 0,
+        request_callback: None,
+        requested: None,
+    }));
 
     let thread_mutex = sample_mutex.clone();
 
@@ -101,7 +171,9 @@ pub fn build(sample_rate: u32) -> Box<AudioBackend> {
 
             let counter = AudioCounter {
                 counter: thread_mutex,
-                incoming: frame_rx.into_iter(),
+                incoming: frame_rx,
+                target: get_buffer_target(sample_rate, fps),
+                timeout: get_request_timeout(fps),
             };
 
             let mut converter = DataConverter::new(ChannelsCountConverter::new(
@@ -119,32 +191,43 @@ pub fn build(sample_rate: u32) -> Box<AudioBackend> {
             let stream_id = event_loop.build_output_stream(&device, &format).unwrap();
             event_loop.play_stream(stream_id.clone());
 
-            event_loop.run(move |_, data| match data {
-                cpal::StreamData::Output {
-                    buffer: cpal::UnknownTypeOutputBuffer::U16(mut buffer),
-                } => for sample in buffer.chunks_mut(format.channels as usize) {
-                    for out in sample.iter_mut() {
-                        let sample: f32 = converter.next().unwrap();
-                        *out = ((sample * 0.5 + 0.5) * u16_max as f32) as u16;
-                    }
-                },
-                cpal::StreamData::Output {
-                    buffer: cpal::UnknownTypeOutputBuffer::I16(mut buffer),
-                } => for sample in buffer.chunks_mut(format.channels as usize) {
-                    for out in sample.iter_mut() {
-                        let sample: f32 = converter.next().unwrap();
-                        *out = (sample * i16_max as f32) as i16;
-                    }
-                },
-                cpal::StreamData::Output {
-                    buffer: cpal::UnknownTypeOutputBuffer::F32(mut buffer),
-                } => for sample in buffer.chunks_mut(format.channels as usize) {
-                    for out in sample.iter_mut() {
-                        let sample: f32 = converter.next().unwrap();
-                        *out = sample;
-                    }
-                },
-                _ => (),
+            // Once the backend has been dropped (e.g. the core changed its sample rate and
+            // a new backend was built), the sample channel closes and we tear down our stream.
+            let event_loop_ref = &event_loop;
+            event_loop.run(move |id, data| {
+                let mut finished = false;
+
+                match data {
+                    cpal::StreamData::Output {
+                        buffer: cpal::UnknownTypeOutputBuffer::U16(mut buffer),
+                    } => for sample in buffer.chunks_mut(format.channels as usize) {
+                        for out in sample.iter_mut() {
+                            let sample: f32 = next_sample(&mut converter, &mut finished);
+                            *out = ((sample * 0.5 + 0.5) * u16_max as f32) as u16;
+                        }
+                    },
+                    cpal::StreamData::Output {
+                        buffer: cpal::UnknownTypeOutputBuffer::I16(mut buffer),
+                    } => for sample in buffer.chunks_mut(format.channels as usize) {
+                        for out in sample.iter_mut() {
+                            let sample: f32 = next_sample(&mut converter, &mut finished);
+                            *out = (sample * i16_max as f32) as i16;
+                        }
+                    },
+                    cpal::StreamData::Output {
+                        buffer: cpal::UnknownTypeOutputBuffer::F32(mut buffer),
+                    } => for sample in buffer.chunks_mut(format.channels as usize) {
+                        for out in sample.iter_mut() {
+                            let sample: f32 = next_sample(&mut converter, &mut finished);
+                            *out = sample;
+                        }
+                    },
+                    _ => (),
+                }
+
+                if finished {
+                    event_loop_ref.destroy_stream(id);
+                }
             });
 
             /*let incoming = Vec::new();
@@ -168,7 +251,49 @@ pub fn build(sample_rate: u32) -> Box<AudioBackend> {
         sink: frame_tx,
         samples_remaining: sample_mutex,
         sample_rate,
+        fps,
     })
 }
 
 pub static INFO: AudioBackendInfo = AudioBackendInfo { name: "Rodio" };
+
+#[cfg(test)]
+mod test {
+    use super::RemainingAudio;
+
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    #[test]
+    fn unanswered_requests() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let thread_requests = requests.clone();
+
+        let mut remaining = RemainingAudio {
+            frames: 10,
+            request_callback: Some(Box::new(move || {
+                thread_requests.fetch_add(1, Ordering::SeqCst);
+            })),
+            requested: None,
+        };
+
+        let timeout = Duration::from_millis(16);
+        let start = Instant::now();
+
+        remaining.request_if_low(100, timeout, start);
+        remaining.request_if_low(100, timeout, start + Duration::from_millis(5));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // The core had nothing ready, so no audio was submitted - we ask again
+        remaining.request_if_low(100, timeout, start + timeout);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        // Nothing is asked for while there is enough audio
+        remaining.frames = 100;
+        remaining.request_if_low(100, timeout, start + timeout * 3);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+}
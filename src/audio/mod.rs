@@ -16,21 +16,28 @@ pub trait AudioBackend {
     fn is_done(&self) -> bool;
 
     fn get_done_callback(&self) -> Box<Fn() -> bool + Send>;
+
+    /// Registers a callback which is called from the audio thread when more audio is needed.
+    /// Used for cores which provide audio through a callback, rather than while running.
+    fn set_request_callback(&mut self, callback: Box<Fn() + Send>);
 }
 
 static AVAILABLE_AUDIO_BACKENDS: &'static [(
     &'static AudioBackendInfo,
-    fn(u32) -> Box<AudioBackend>,
+    fn(u32, f64) -> Box<AudioBackend>,
 )] = &[
     #[cfg(feature = "audio_cpal")]
     (&cpal::INFO, cpal::build),
 ];
 
 /// Builds a new renderer with the specified properties.
-pub fn build(sample_rate: u32) -> Option<Box<AudioBackend>> {
+///
+/// `fps` is the framerate of the core, used to decide how much audio should be buffered
+/// before another frame is requested.
+pub fn build(sample_rate: u32, fps: f64) -> Option<Box<AudioBackend>> {
     for &(ref info, ref function) in AVAILABLE_AUDIO_BACKENDS {
         println!("Attempting to load audio core: {:?}", info);
-        return Some(function(sample_rate));
+        return Some(function(sample_rate, fps));
     }
 
     return None;
@@ -5,9 +5,34 @@
 use retro_types::RetroPixelFormat;
 use retro_types::RetroEnvironment;
 use retro_types::RawRetroVariable;
+use retro_types::RawRetroControllerInfo;
+use retro_types::RetroGameGeometry;
+use retro_types::RetroAvInfo;
+use retro_types::RETRO_HW_FRAME_BUFFER_VALID;
+use retro_types::RetroFrameTimeCallback;
+use retro_types::RetroAudioCallback;
+use retro_types::RetroKeyboardCallback;
+use retro_types::RetroRumbleInterface;
+use retro_types::RetroRumbleEffect;
+use retro_types::RetroSensorInterface;
+use retro_types::RetroSensorAction;
+use retro_types::RetroSensor;
+use retro_types::RetroCameraCallback;
+use retro_types::RETRO_CAMERA_BUFFER_RAW_FRAMEBUFFER;
+use retro_types::RetroLocationCallback;
+use retro_types::RetroPerfCallback;
+use retro_types::RetroPerfCounter;
+use retro_types::RetroVfsInterfaceInfo;
+use retro_types::RetroDiskControlCallback;
+use retro_types::RetroDiskControlExtCallback;
+use retro_types::RETRO_DISK_CONTROL_INTERFACE_VERSION;
+use retro_types::RETRO_VFS_INTERFACE_VERSION;
 
 use backend::state::get_current_backend;
 use backend::protocol::send_message;
+use backend::vfs::VFS_INTERFACE;
+
+use input::ports::MAX_PORTS;
 
 use std::os::raw::*;
 use std::mem::transmute;
@@ -76,6 +101,24 @@ pub unsafe extern "C" fn environment_callback(cmd: c_uint, data: *const c_void)
             send_message(ProtocolMessageType::SetVariables(strings));
             true
         }
+        RetroEnvironment::SetControllerInfo => {
+            // One entry per port, until an empty one
+            let mut ports = Vec::new();
+
+            let mut add = 0;
+            loop {
+                let info = &*(data as *const RawRetroControllerInfo).offset(add);
+                if info.is_eof() {
+                    break;
+                }
+
+                ports.push(info.to_owned().unwrap());
+                add += 1;
+            }
+
+            send_message(ProtocolMessageType::SetControllerInfo(ports));
+            true
+        }
         RetroEnvironment::GetVariable => {
             let variable = &mut *(data as *mut RawRetroVariable);
             let key = variable.get_key().unwrap();
@@ -129,6 +172,131 @@ pub unsafe extern "C" fn environment_callback(cmd: c_uint, data: *const c_void)
             *(data as *mut *const c_char) = frontend.system_path.as_ptr() as *const _;
             true
         }
+        RetroEnvironment::Shutdown => {
+            send_message(ProtocolMessageType::Shutdown);
+            true
+        }
+        RetroEnvironment::SetSupportNoGame => {
+            // This call remains local
+            get_current_backend().support_no_game = *(data as *const bool);
+            true
+        }
+        RetroEnvironment::SetFrameTimeCallback => {
+            // This call remains local
+            get_current_backend().frame_time_callback =
+                Some(*(data as *const RetroFrameTimeCallback));
+            true
+        }
+        RetroEnvironment::SetAudioCallback => {
+            get_current_backend().audio_callback = Some(*(data as *const RetroAudioCallback));
+            send_message(ProtocolMessageType::EnableAudioCallback);
+            true
+        }
+        RetroEnvironment::SetKeyboardCallback => {
+            get_current_backend().keyboard_callback = Some(*(data as *const RetroKeyboardCallback));
+            send_message(ProtocolMessageType::EnableKeyboardCallback);
+            true
+        }
+        RetroEnvironment::GetRumbleInterface => {
+            *(data as *mut RetroRumbleInterface) = RetroRumbleInterface {
+                set_rumble_state: set_rumble_state_callback,
+            };
+            true
+        }
+        RetroEnvironment::GetSensorInterface => {
+            *(data as *mut RetroSensorInterface) = RetroSensorInterface {
+                set_sensor_state: set_sensor_state_callback,
+                get_sensor_input: get_sensor_input_callback,
+            };
+            true
+        }
+        RetroEnvironment::GetCameraInterface => {
+            let camera = &mut *(data as *mut RetroCameraCallback);
+
+            // Frames are only ever provided as raw framebuffers
+            if camera.caps & RETRO_CAMERA_BUFFER_RAW_FRAMEBUFFER == 0 {
+                println!("Core only supports OpenGL camera textures!");
+                return false;
+            }
+
+            camera.start = Some(camera_start_callback);
+            camera.stop = Some(camera_stop_callback);
+            get_current_backend().camera_callback = Some(*camera);
+            true
+        }
+        RetroEnvironment::GetLocationInterface => {
+            let location = &mut *(data as *mut RetroLocationCallback);
+            location.start = Some(location_start_callback);
+            location.stop = Some(location_stop_callback);
+            location.get_position = Some(location_get_position_callback);
+            location.set_interval = Some(location_set_interval_callback);
+            get_current_backend().location_callback = Some(*location);
+            true
+        }
+        RetroEnvironment::GetPerfInterface => {
+            *(data as *mut RetroPerfCallback) = RetroPerfCallback {
+                get_time_usec: perf_get_time_usec_callback,
+                get_cpu_features: get_cpu_features_callback,
+                get_perf_counter: perf_get_counter_callback,
+                perf_register: perf_register_callback,
+                perf_start: perf_start_callback,
+                perf_stop: perf_stop_callback,
+                perf_log: perf_log_callback,
+            };
+            true
+        }
+        RetroEnvironment::GetVFSInterface => {
+            let info = &mut *(data as *mut RetroVfsInterfaceInfo);
+            if info.required_interface_version > RETRO_VFS_INTERFACE_VERSION {
+                println!(
+                    "Core requires VFS v{}, but only v{} is supported",
+                    info.required_interface_version, RETRO_VFS_INTERFACE_VERSION
+                );
+                return false;
+            }
+
+            info.iface = &VFS_INTERFACE;
+            true
+        }
+        RetroEnvironment::SetDiskControlInterface => {
+            let callback = &*(data as *const RetroDiskControlCallback);
+            get_current_backend().disk_control =
+                Some(RetroDiskControlExtCallback::from_basic(callback));
+            true
+        }
+        RetroEnvironment::GetDiskControlInterfaceVersion => {
+            *(data as *mut c_uint) = RETRO_DISK_CONTROL_INTERFACE_VERSION;
+            true
+        }
+        RetroEnvironment::SetDiskControlExtInterface => {
+            get_current_backend().disk_control =
+                Some(*(data as *const RetroDiskControlExtCallback));
+            true
+        }
+        RetroEnvironment::GetInputMaxUsers => {
+            *(data as *mut c_uint) = MAX_PORTS as c_uint;
+            true
+        }
+        RetroEnvironment::GetInputBitmasks => true,
+        RetroEnvironment::GetCanDup => {
+            *(data as *mut bool) = true;
+            true
+        }
+        RetroEnvironment::SetRotation => {
+            let rotation = *(data as *const c_uint);
+            send_message(ProtocolMessageType::SetRotation(rotation % 4));
+            true
+        }
+        RetroEnvironment::SetGeometry => {
+            let geometry = *(data as *const RetroGameGeometry);
+            send_message(ProtocolMessageType::SetGeometry(geometry));
+            true
+        }
+        RetroEnvironment::SetSystemAVInfo => {
+            let av_info = *(data as *const RetroAvInfo);
+            send_message(ProtocolMessageType::SetSystemAVInfo(av_info));
+            true
+        }
         _ => {
             println!("Unsupported environmental command: {:?}", safe_command);
             false
@@ -145,41 +313,78 @@ pub unsafe extern "C" fn video_refresh_callback(
     let width = width as usize;
     let height = height as usize;
 
-    if data != 0 as *const _ {
+    if data == 0 as *const _ {
+        // The core wants the previous frame to be shown again
+        send_message(ProtocolMessageType::VideoRefresh(VideoRefreshType::Dupe));
+    } else if data == RETRO_HW_FRAME_BUFFER_VALID {
+        // Hardware callback
+        send_message(ProtocolMessageType::VideoRefresh(
+            VideoRefreshType::Hardware,
+        ));
+    } else {
         // Software refresh
-        let format = get_current_backend().format;
+        let backend = get_current_backend();
+        let format = backend.format;
         let pixel_size = format.get_pixel_size();
-        let mut padless_data: Vec<u8> = Vec::with_capacity(width * height * pixel_size);
-
-        // Copy the data (which can have a pitch of > 0) into our own safe array
-        if width > 0 && height > 0 && pitch > 0 {
-            assert!(padless_data.len() <= pitch * height);
+        let row_size = width * pixel_size;
+        let rows = if width > 0 { height } else { 0 };
+
+        // Rows would overlap, so the frame can't be read
+        if rows > 0 && pitch < row_size {
+            println!(
+                "Core gave a pitch of {} for rows of {} bytes, dropping frame",
+                pitch, row_size
+            );
+            send_message(ProtocolMessageType::VideoRefresh(VideoRefreshType::Dupe));
+            return;
+        }
 
+        // Rows can have padding after them, so they are read where the core left them
+        let raw_data: &[u8] = if rows > 0 {
             // c_void isn't a particularly useful type - we have to transmute
-            let raw_data: &[u8] = transmute(from_raw_parts(data, pitch * height));
-
-            for y in 0..height {
-                padless_data
-                    .extend_from_slice(&raw_data[y * pitch..(y * pitch + width * pixel_size)]);
-            }
+            transmute(from_raw_parts(data, pitch * (rows - 1) + row_size))
+        } else {
+            &[]
+        };
+        let row = |y: usize| &raw_data[y * pitch..(y * pitch + row_size)];
+
+        // Cores which don't dupe frames themselves may still submit the same frame repeatedly.
+        // This is checked before copying, so that duplicates are never copied.
+        let is_dupe = {
+            let last_frame = &backend.last_frame;
+            backend.last_frame_format == Some(format) && width == backend.last_frame_width
+                && height == backend.last_frame_height
+                && last_frame.len() == rows * row_size
+                && (0..rows).all(|y| row(y) == &last_frame[y * row_size..(y + 1) * row_size])
+        };
+        if is_dupe {
+            send_message(ProtocolMessageType::VideoRefresh(VideoRefreshType::Dupe));
+            return;
         }
 
-        assert_eq!(padless_data.len(), width * height * pixel_size);
+        // Copy the data into our own safe array
+        let mut padless_data: Vec<u8> = Vec::with_capacity(rows * row_size);
+        for y in 0..rows {
+            padless_data.extend_from_slice(row(y));
+        }
 
-        let formatted_data = format.convert(&padless_data, width, height);
+        // The last frame's buffer is reused, rather than allocating a new one for every frame
+        backend.last_frame.clear();
+        backend.last_frame.extend_from_slice(&padless_data);
+        backend.last_frame_format = Some(format);
+        backend.last_frame_width = width;
+        backend.last_frame_height = height;
 
+        // Conversion is left to the frontend, so we only send as many bytes as the core gave us
         send_message(ProtocolMessageType::VideoRefresh(
             VideoRefreshType::Software {
-                framebuffer: formatted_data,
+                framebuffer: padless_data,
                 width: width as u64,
                 height: height as u64,
+                pitch: (width * pixel_size) as u64,
+                format,
             },
         ));
-    } else {
-        // Hardware callback
-        send_message(ProtocolMessageType::VideoRefresh(
-            VideoRefreshType::Hardware,
-        ));
     }
 }
 
@@ -196,7 +401,16 @@ pub unsafe extern "C" fn audio_sample_batch_callback(data: *const i16, frames: u
 }
 
 pub unsafe extern "C" fn input_poll_callback() {
-    send_message(ProtocolMessageType::PollInput);
+    // The frontend answers with every device at once, so that queries stay local
+    let snapshot = match send_message(ProtocolMessageType::PollInput)
+        .unwrap()
+        .unwrap()
+    {
+        ProtocolMessageType::InputSnapshot(v) => v,
+        _ => panic!("Unexpected input poll response!"),
+    };
+
+    get_current_backend().input = snapshot;
 }
 
 pub unsafe extern "C" fn input_state_callback(
@@ -205,37 +419,208 @@ pub unsafe extern "C" fn input_state_callback(
     index: c_uint,
     id: c_uint,
 ) -> i16 {
-    // TODO: Make backends abstract
-    /*let frontend = get_current_frontend();
-    let key = match id {
-        0 => InputKey::B,
-        1 => InputKey::Y,
-        2 => InputKey::Select,
-        3 => InputKey::Start,
-        4 => InputKey::Up,
-        5 => InputKey::Down,
-        6 => InputKey::Left,
-        7 => InputKey::Right,
-        8 => InputKey::A,
-        9 => InputKey::X,
-        10 => InputKey::L,
-        11 => InputKey::R,
-        12 => InputKey::L2,
-        13 => InputKey::R2,
-        14 => InputKey::L3,
-        15 => InputKey::R3,
-        _ => return 0
-    };*/
-
-    match send_message(ProtocolMessageType::InputState {
+    get_current_backend()
+        .input
+        .get_state(port, device, index, id)
+}
+
+pub unsafe extern "C" fn set_rumble_state_callback(
+    port: c_uint,
+    effect: c_uint,
+    strength: u16,
+) -> bool {
+    let effect = match RetroRumbleEffect::from(effect) {
+        Some(v) => v,
+        None => return false,
+    };
+
+    send_message(ProtocolMessageType::SetRumbleState {
         port,
-        device,
-        index,
-        id,
-    }).unwrap()
+        effect,
+        strength,
+    });
+
+    true
+}
+
+pub unsafe extern "C" fn set_sensor_state_callback(
+    port: c_uint,
+    action: c_uint,
+    rate: c_uint,
+) -> bool {
+    let action = match RetroSensorAction::from(action) {
+        Some(v) => v,
+        None => return false,
+    };
+
+    match send_message(ProtocolMessageType::SetSensorState { port, action, rate })
+        .unwrap()
+        .unwrap()
+    {
+        ProtocolMessageType::SensorStateResponse(v) => v,
+        _ => panic!("Unexpected sensor state response!"),
+    }
+}
+
+pub unsafe extern "C" fn get_sensor_input_callback(port: c_uint, id: c_uint) -> f32 {
+    let sensor = match RetroSensor::from(id) {
+        Some(v) => v,
+        None => return 0.0,
+    };
+
+    match send_message(ProtocolMessageType::SensorInput { port, sensor })
+        .unwrap()
+        .unwrap()
+    {
+        ProtocolMessageType::SensorInputResponse(v) => v,
+        _ => panic!("Unexpected sensor input response!"),
+    }
+}
+
+pub unsafe extern "C" fn camera_start_callback() -> bool {
+    let (width, height) = match get_current_backend().camera_callback {
+        Some(ref v) => (v.width, v.height),
+        None => return false,
+    };
+
+    match send_message(ProtocolMessageType::CameraStart { width, height })
+        .unwrap()
+        .unwrap()
+    {
+        ProtocolMessageType::CameraStartResponse(v) => v,
+        _ => panic!("Unexpected camera start response!"),
+    }
+}
+
+pub unsafe extern "C" fn camera_stop_callback() {
+    send_message(ProtocolMessageType::CameraStop);
+}
+
+pub unsafe extern "C" fn location_start_callback() -> bool {
+    match send_message(ProtocolMessageType::LocationStart)
+        .unwrap()
         .unwrap()
     {
-        ProtocolMessageType::InputResponse(v) => v,
-        _ => panic!("Unexpected input response!"),
+        ProtocolMessageType::LocationStartResponse(v) => v,
+        _ => panic!("Unexpected location start response!"),
     }
 }
+
+pub unsafe extern "C" fn location_stop_callback() {
+    send_message(ProtocolMessageType::LocationStop);
+}
+
+pub unsafe extern "C" fn location_get_position_callback(
+    latitude: *mut f64,
+    longitude: *mut f64,
+    horizontal_accuracy: *mut f64,
+    vertical_accuracy: *mut f64,
+) -> bool {
+    let position = match send_message(ProtocolMessageType::GetPosition)
+        .unwrap()
+        .unwrap()
+    {
+        ProtocolMessageType::PositionResponse(v) => v,
+        _ => panic!("Unexpected position response!"),
+    };
+
+    match position {
+        Some(position) => {
+            *latitude = position.latitude;
+            *longitude = position.longitude;
+            *horizontal_accuracy = position.horizontal_accuracy;
+            *vertical_accuracy = position.vertical_accuracy;
+            true
+        }
+        None => false,
+    }
+}
+
+pub unsafe extern "C" fn location_set_interval_callback(
+    interval_ms: c_uint,
+    interval_distance: c_uint,
+) {
+    send_message(ProtocolMessageType::LocationSetInterval {
+        interval_ms,
+        interval_distance,
+    });
+}
+
+pub unsafe extern "C" fn perf_get_time_usec_callback() -> i64 {
+    let elapsed = get_current_backend().started.elapsed();
+    (elapsed.as_secs() * 1_000_000 + elapsed.subsec_micros() as u64) as i64
+}
+
+/// Ticks are nanoseconds since the backend was started.
+pub unsafe extern "C" fn perf_get_counter_callback() -> u64 {
+    let elapsed = get_current_backend().started.elapsed();
+    elapsed.as_secs() * 1_000_000_000 + elapsed.subsec_nanos() as u64
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub unsafe extern "C" fn get_cpu_features_callback() -> u64 {
+    use retro_types::{RETRO_SIMD_AES, RETRO_SIMD_AVX, RETRO_SIMD_AVX2, RETRO_SIMD_CMOV,
+                      RETRO_SIMD_MMX, RETRO_SIMD_POPCNT, RETRO_SIMD_SSE, RETRO_SIMD_SSE2,
+                      RETRO_SIMD_SSE3, RETRO_SIMD_SSE4, RETRO_SIMD_SSE42, RETRO_SIMD_SSSE3};
+
+    let features = [
+        (is_x86_feature_detected!("mmx"), RETRO_SIMD_MMX),
+        (is_x86_feature_detected!("sse"), RETRO_SIMD_SSE),
+        (is_x86_feature_detected!("sse2"), RETRO_SIMD_SSE2),
+        (is_x86_feature_detected!("sse3"), RETRO_SIMD_SSE3),
+        (is_x86_feature_detected!("ssse3"), RETRO_SIMD_SSSE3),
+        (is_x86_feature_detected!("sse4.1"), RETRO_SIMD_SSE4),
+        (is_x86_feature_detected!("sse4.2"), RETRO_SIMD_SSE42),
+        (is_x86_feature_detected!("avx"), RETRO_SIMD_AVX),
+        (is_x86_feature_detected!("avx2"), RETRO_SIMD_AVX2),
+        (is_x86_feature_detected!("aes"), RETRO_SIMD_AES),
+        (is_x86_feature_detected!("popcnt"), RETRO_SIMD_POPCNT),
+    ];
+
+    // Every x86 processor that can run this has CMOV
+    features
+        .iter()
+        .filter(|x| x.0)
+        .fold(RETRO_SIMD_CMOV, |result, x| result | x.1)
+}
+
+#[cfg(target_arch = "aarch64")]
+pub unsafe extern "C" fn get_cpu_features_callback() -> u64 {
+    // NEON is mandatory on AArch64
+    ::retro_types::RETRO_SIMD_NEON
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+pub unsafe extern "C" fn get_cpu_features_callback() -> u64 {
+    0
+}
+
+pub unsafe extern "C" fn perf_register_callback(counter: *mut RetroPerfCounter) {
+    if counter.is_null() || (*counter).registered {
+        return;
+    }
+
+    (*counter).registered = true;
+    get_current_backend().perf_counters.push(counter);
+}
+
+pub unsafe extern "C" fn perf_start_callback(counter: *mut RetroPerfCounter) {
+    if counter.is_null() || !(*counter).registered {
+        return;
+    }
+
+    (*counter).call_count += 1;
+    (*counter).start = perf_get_counter_callback();
+}
+
+pub unsafe extern "C" fn perf_stop_callback(counter: *mut RetroPerfCounter) {
+    if counter.is_null() || !(*counter).registered {
+        return;
+    }
+
+    (*counter).total += perf_get_counter_callback() - (*counter).start;
+}
+
+pub unsafe extern "C" fn perf_log_callback() {
+    print!("{}", get_current_backend().get_perf_report());
+}
@@ -7,6 +7,9 @@ use std::fs::File;
 use std::str::Utf8Error;
 use std::path::Path;
 use std::io::Read;
+use std::os::raw::c_uint;
+use std::slice::{from_raw_parts, from_raw_parts_mut};
+use std::cmp::min;
 
 use retro_types::*;
 use backend::callbacks::*;
@@ -76,28 +79,53 @@ impl LibRetroCore {
         Ok(())
     }
 
-    pub fn load_game(&self, path: Option<&Path>) -> Result<bool, CoreError> {
+    /// Describes content at the specified path to the core, as it expects. If the contents
+    /// are provided, they are used instead of reading the path.
+    pub fn build_game_info(
+        &self,
+        path: &Path,
+        contents: Option<Vec<u8>>,
+    ) -> Result<RetroGameInfo, CoreError> {
         let info = self.get_system_info()?;
 
-        let meta = match path {
-            Some(v) => Some(if info.need_fullpath {
-                RetroGameInfo::new(
-                    v.to_str(),
-                    None,
-                    translate_lib_result(v.metadata())?.len() as _,
-                    Some(""),
-                )
-            } else {
-                let length: usize;
-                let data = {
-                    let mut file = translate_lib_result(File::open(v))?;
+        Ok(if info.need_fullpath {
+            RetroGameInfo::new(
+                path.to_str(),
+                None,
+                match contents {
+                    Some(ref contents) => contents.len(),
+                    None => translate_lib_result(path.metadata())?.len() as _,
+                },
+                Some(""),
+            )
+        } else {
+            let length: usize;
+            let data = match contents {
+                Some(contents) => {
+                    length = contents.len();
+                    contents
+                }
+                None => {
+                    let mut file = translate_lib_result(File::open(path))?;
                     let mut buf = Vec::new();
                     length = translate_lib_result(file.read_to_end(&mut buf))?;
                     buf
-                };
+                }
+            };
+
+            RetroGameInfo::new(path.to_str(), Some(data), length, Some(""))
+        })
+    }
 
-                RetroGameInfo::new(v.to_str(), Some(data), length, Some(""))
-            }),
+    /// Loads content from the specified path. If the contents are provided, they are used
+    /// instead of reading the path.
+    pub fn load_game(
+        &self,
+        path: Option<&Path>,
+        contents: Option<Vec<u8>>,
+    ) -> Result<bool, CoreError> {
+        let meta = match path {
+            Some(v) => Some(self.build_game_info(v, contents)?),
             _ => None,
         };
 
@@ -182,6 +210,60 @@ impl LibRetroCore {
         Ok(())
     }
 
+    /// Returns a copy of a region of the core's memory, if the core exposes it.
+    pub fn read_memory(&self, id: c_uint) -> Result<Option<Vec<u8>>, CoreError> {
+        unsafe {
+            let data_func: lib::Symbol<RetroGetMemoryDataFn> =
+                translate_lib_result(self.library.get(b"retro_get_memory_data"))?;
+            let size_func: lib::Symbol<RetroGetMemorySizeFn> =
+                translate_lib_result(self.library.get(b"retro_get_memory_size"))?;
+
+            let data = data_func(id);
+            let size = size_func(id);
+
+            if data == 0 as *mut _ || size == 0 {
+                return Ok(None);
+            }
+
+            Ok(Some(from_raw_parts(data as *const u8, size).to_owned()))
+        }
+    }
+
+    /// Overwrites a region of the core's memory, returning false if the core doesn't expose it.
+    pub fn write_memory(&self, id: c_uint, contents: &[u8]) -> Result<bool, CoreError> {
+        unsafe {
+            let data_func: lib::Symbol<RetroGetMemoryDataFn> =
+                translate_lib_result(self.library.get(b"retro_get_memory_data"))?;
+            let size_func: lib::Symbol<RetroGetMemorySizeFn> =
+                translate_lib_result(self.library.get(b"retro_get_memory_size"))?;
+
+            let data = data_func(id);
+            let size = size_func(id);
+
+            if data == 0 as *mut _ || size == 0 {
+                return Ok(false);
+            }
+
+            let length = min(size, contents.len());
+            from_raw_parts_mut(data as *mut u8, size)[..length]
+                .copy_from_slice(&contents[..length]);
+
+            Ok(true)
+        }
+    }
+
+    /// Tells the core which controller (a `RETRO_DEVICE_*` value) is plugged into a port.
+    pub fn set_controller_port_device(&self, port: u32, device: u32) -> Result<(), CoreError> {
+        unsafe {
+            let func: lib::Symbol<RetroSetControllerPortDeviceFn> =
+                translate_lib_result(self.library.get(b"retro_set_controller_port_device"))?;
+
+            func(port, device);
+        }
+
+        Ok(())
+    }
+
     pub fn get_api_version(&self) -> Result<u32, CoreError> {
         unsafe {
             let func: lib::Symbol<RetroApiVersionFn> =
@@ -0,0 +1,385 @@
+//! Swaps the disks of cores with multiple (e.g. multi-disc CD games), and loads M3U playlists
+//! of disks for cores which don't understand them.
+
+use backend::core::LibRetroCore;
+use backend::state::get_current_backend;
+
+use core_protocol::DiskAction;
+use core_protocol::DiskStatus;
+
+use retro_types::RawRetroGameInfo;
+use retro_types::RetroDiskControlExtCallback;
+use retro_types::RetroGetImagePathFn;
+
+use std::ffi::CStr;
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Cursor;
+use std::os::raw::c_char;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// The longest path or label that is read from a core.
+const MAX_NAME_LENGTH: usize = 4096;
+
+/// Returns if a path is an M3U playlist.
+pub fn is_playlist(path: &Path) -> bool {
+    match path.extension().and_then(|x| x.to_str()) {
+        Some(extension) => extension.eq_ignore_ascii_case("m3u"),
+        None => false,
+    }
+}
+
+/// Reads the disks listed in a M3U playlist, which may have been sent by the frontend as a
+/// virtual file.
+pub fn read_playlist(path: &Path) -> io::Result<Vec<PathBuf>> {
+    match get_current_backend().vfs.get_virtual_file(path) {
+        Some(data) => parse_playlist(path, Cursor::new(data)),
+        None => parse_playlist(path, BufReader::new(File::open(path)?)),
+    }
+}
+
+/// Parses the disks listed in a M3U playlist. Relative paths are relative to the playlist.
+pub fn parse_playlist<R: BufRead>(path: &Path, reader: R) -> io::Result<Vec<PathBuf>> {
+    let directory = path.parent().unwrap_or(Path::new("."));
+
+    let mut disks = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        disks.push(directory.join(line));
+    }
+
+    if disks.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Playlist doesn't contain any disks",
+        ));
+    }
+
+    Ok(disks)
+}
+
+/// Appends disks to those the core already knows about. Returns false if the core doesn't
+/// support adding disks.
+pub fn add_disks(core: &LibRetroCore, disks: &[PathBuf]) -> bool {
+    let callback = match get_current_backend().disk_control {
+        Some(v) => v,
+        None => return false,
+    };
+
+    let (get_num_images, add_image_index, replace_image_index) = match (
+        callback.get_num_images,
+        callback.add_image_index,
+        callback.replace_image_index,
+    ) {
+        (Some(a), Some(b), Some(c)) => (a, b, c),
+        _ => return false,
+    };
+
+    for disk in disks {
+        let contents = get_current_backend().vfs.get_virtual_file(disk);
+        let info = match core.build_game_info(disk, contents) {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Failed to read disk {:?}: {:?}", disk, e);
+                continue;
+            }
+        };
+
+        unsafe {
+            if !add_image_index() {
+                println!("Core refused another disk");
+                return false;
+            }
+
+            let index = get_num_images() - 1;
+            if !replace_image_index(index, &info.as_raw() as *const RawRetroGameInfo) {
+                println!("Core failed to load disk {:?}", disk);
+            }
+        }
+    }
+
+    true
+}
+
+/// Reads a name from the core through a callback which fills a buffer.
+unsafe fn read_name(function: RetroGetImagePathFn, index: u32) -> Option<String> {
+    let mut buffer = vec![0 as c_char; MAX_NAME_LENGTH];
+    if !function(index, buffer.as_mut_ptr(), buffer.len()) {
+        return None;
+    }
+
+    // Make sure that the string is terminated, even if the core didn't
+    buffer[MAX_NAME_LENGTH - 1] = 0;
+    let name = CStr::from_ptr(buffer.as_ptr()).to_string_lossy().into_owned();
+
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+/// Returns which disk the core has selected, if it has multiple.
+pub fn get_status() -> Option<DiskStatus> {
+    read_status(&get_current_backend().disk_control?)
+}
+
+/// Reads which disk is selected through a core's disk control interface.
+fn read_status(callback: &RetroDiskControlExtCallback) -> Option<DiskStatus> {
+    unsafe {
+        let index = callback.get_image_index?();
+        let count = callback.get_num_images?();
+        let ejected = callback.get_eject_state?();
+
+        // Prefer the label, but fall back to the file name of the disk
+        let label = callback
+            .get_image_label
+            .and_then(|x| read_name(x, index))
+            .or_else(|| {
+                callback
+                    .get_image_path
+                    .and_then(|x| read_name(x, index))
+                    .and_then(|x| {
+                        Path::new(&x)
+                            .file_stem()
+                            .map(|x| x.to_string_lossy().into_owned())
+                    })
+            });
+
+        Some(DiskStatus {
+            index,
+            count,
+            ejected,
+            label,
+        })
+    }
+}
+
+/// Changes the disk of the core. Returns false if this isn't possible right now.
+pub fn apply(action: DiskAction) -> bool {
+    let callback = match get_current_backend().disk_control {
+        Some(v) => v,
+        None => {
+            println!("Core doesn't support changing disks");
+            return false;
+        }
+    };
+
+    apply_to(&callback, action)
+}
+
+/// Changes the disk through a core's disk control interface.
+fn apply_to(callback: &RetroDiskControlExtCallback, action: DiskAction) -> bool {
+    let status = match read_status(callback) {
+        Some(v) => v,
+        None => return false,
+    };
+
+    unsafe {
+        match action {
+            DiskAction::Eject | DiskAction::Insert => match callback.set_eject_state {
+                Some(set_eject_state) => set_eject_state(match action {
+                    DiskAction::Eject => true,
+                    _ => false,
+                }),
+                None => false,
+            },
+            DiskAction::Next | DiskAction::Previous => {
+                if !status.ejected {
+                    println!("Eject the disk before changing it");
+                    return false;
+                }
+
+                if status.count == 0 {
+                    return false;
+                }
+
+                let index = match action {
+                    DiskAction::Next => (status.index + 1) % status.count,
+                    _ => (status.index + status.count - 1) % status.count,
+                };
+
+                match callback.set_image_index {
+                    Some(set_image_index) => set_image_index(index),
+                    None => false,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::os::raw::c_uint;
+    use std::ptr;
+
+    fn parse(text: &str) -> io::Result<Vec<PathBuf>> {
+        parse_playlist(Path::new("games/game.m3u"), Cursor::new(text.as_bytes()))
+    }
+
+    #[test]
+    fn playlists() {
+        assert_eq!(
+            parse("disk1.cue\nsub/disk2.cue\n/abs/disk3.cue").unwrap(),
+            vec![
+                PathBuf::from("games/disk1.cue"),
+                PathBuf::from("games/sub/disk2.cue"),
+                PathBuf::from("/abs/disk3.cue"),
+            ]
+        );
+
+        // Comments, blank lines and CRLF line endings are skipped
+        assert_eq!(
+            parse("#EXTM3U\r\n\r\ndisk1.cue\r\n  \r\n# Second disk\r\ndisk2.cue\r\n").unwrap(),
+            vec![
+                PathBuf::from("games/disk1.cue"),
+                PathBuf::from("games/disk2.cue"),
+            ]
+        );
+
+        // A playlist with only a file name is relative to the working directory
+        assert_eq!(
+            parse_playlist(Path::new("game.m3u"), Cursor::new("disk1.cue")).unwrap(),
+            vec![PathBuf::from("disk1.cue")]
+        );
+
+        assert!(parse("").is_err());
+        assert!(parse("# Nothing here\n\n").is_err());
+    }
+
+    /// The disks of a pretend core.
+    struct Disks {
+        index: c_uint,
+        count: c_uint,
+        ejected: bool,
+        label: Option<&'static str>,
+        path: Option<&'static str>,
+    }
+
+    thread_local! {
+        static DISKS: RefCell<Disks> = RefCell::new(Disks {
+            index: 0,
+            count: 3,
+            ejected: false,
+            label: None,
+            path: None,
+        });
+    }
+
+    unsafe extern "C" fn set_eject_state(ejected: bool) -> bool {
+        DISKS.with(|x| x.borrow_mut().ejected = ejected);
+        true
+    }
+
+    unsafe extern "C" fn get_eject_state() -> bool {
+        DISKS.with(|x| x.borrow().ejected)
+    }
+
+    unsafe extern "C" fn get_image_index() -> c_uint {
+        DISKS.with(|x| x.borrow().index)
+    }
+
+    unsafe extern "C" fn set_image_index(index: c_uint) -> bool {
+        DISKS.with(|x| x.borrow_mut().index = index);
+        true
+    }
+
+    unsafe extern "C" fn get_num_images() -> c_uint {
+        DISKS.with(|x| x.borrow().count)
+    }
+
+    unsafe fn write_name(name: Option<&str>, buffer: *mut c_char, length: usize) -> bool {
+        match name {
+            Some(name) if name.len() < length => {
+                ptr::copy_nonoverlapping(name.as_ptr() as *const c_char, buffer, name.len());
+                *buffer.offset(name.len() as isize) = 0;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    unsafe extern "C" fn get_image_path(_: c_uint, buffer: *mut c_char, length: usize) -> bool {
+        write_name(DISKS.with(|x| x.borrow().path), buffer, length)
+    }
+
+    unsafe extern "C" fn get_image_label(_: c_uint, buffer: *mut c_char, length: usize) -> bool {
+        write_name(DISKS.with(|x| x.borrow().label), buffer, length)
+    }
+
+    fn build_callback() -> RetroDiskControlExtCallback {
+        RetroDiskControlExtCallback {
+            set_eject_state: Some(set_eject_state),
+            get_eject_state: Some(get_eject_state),
+            get_image_index: Some(get_image_index),
+            set_image_index: Some(set_image_index),
+            get_num_images: Some(get_num_images),
+            replace_image_index: None,
+            add_image_index: None,
+            set_initial_image: None,
+            get_image_path: Some(get_image_path),
+            get_image_label: Some(get_image_label),
+        }
+    }
+
+    #[test]
+    fn status() {
+        let mut callback = build_callback();
+
+        let status = read_status(&callback).unwrap();
+        assert_eq!((status.index, status.count, status.ejected), (0, 3, false));
+        assert_eq!(status.label, None);
+
+        // Disks are named by their file when they don't have a label
+        DISKS.with(|x| x.borrow_mut().path = Some("/games/Game (Disk 1).cue"));
+        assert_eq!(
+            read_status(&callback).unwrap().label,
+            Some("Game (Disk 1)".to_owned())
+        );
+
+        DISKS.with(|x| x.borrow_mut().label = Some("Disk 1"));
+        assert_eq!(read_status(&callback).unwrap().label, Some("Disk 1".to_owned()));
+
+        // The index, count and eject state are all needed
+        callback.get_eject_state = None;
+        assert!(read_status(&callback).is_none());
+    }
+
+    #[test]
+    fn changing_disks() {
+        let callback = build_callback();
+        let index = || DISKS.with(|x| x.borrow().index);
+
+        // The disk can only be changed while the tray is open
+        assert!(!apply_to(&callback, DiskAction::Next));
+        assert_eq!(index(), 0);
+
+        assert!(apply_to(&callback, DiskAction::Eject));
+        assert!(read_status(&callback).unwrap().ejected);
+
+        assert!(apply_to(&callback, DiskAction::Next));
+        assert_eq!(index(), 1);
+
+        assert!(apply_to(&callback, DiskAction::Previous));
+        assert!(apply_to(&callback, DiskAction::Previous));
+        assert_eq!(index(), 2);
+
+        assert!(apply_to(&callback, DiskAction::Next));
+        assert_eq!(index(), 0);
+
+        assert!(apply_to(&callback, DiskAction::Insert));
+        assert!(!read_status(&callback).unwrap().ejected);
+    }
+}
@@ -4,8 +4,10 @@
 extern crate libloading as lib;
 
 pub mod core;
+pub mod disk;
 pub mod callbacks;
 pub mod protocol;
 pub mod state;
+pub mod vfs;
 
 pub use self::protocol::run;
@@ -2,9 +2,12 @@
 
 use backend::lib;
 use backend::core::LibRetroCore;
+use backend::disk;
 use backend::state::BackendState;
+use backend::state::get_current_backend;
 
 use retro_types::RetroPixelFormat;
+use retro_types::RETRO_MEMORY_SAVE_RAM;
 
 use core_protocol::ProtocolAdapter;
 use core_protocol::ProtocolMessageType;
@@ -14,6 +17,10 @@ use std::net::TcpStream;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::path::Path;
+use std::path::PathBuf;
+use std::fs::File;
+use std::io::Read;
+use std::io::Write;
 
 static mut ADAPTER: Option<Arc<Mutex<ProtocolAdapter>>> = None;
 
@@ -62,19 +69,175 @@ pub fn run(core: String, address: String) {
         // TODO: Error handling
         match event {
             ProtocolMessageType::Init => lock.init().unwrap(),
-            ProtocolMessageType::Deinit => lock.deinit().unwrap(),
+            ProtocolMessageType::Deinit => {
+                lock.deinit().unwrap();
+
+                // Nothing more can be done with this core
+                break;
+            }
             ProtocolMessageType::Load(name) => {
-                assert!(lock.load_game(Some(Path::new(&name))).unwrap())
+                let loaded = match name {
+                    Some(name) => {
+                        // Cores which don't understand playlists are given the first disk,
+                        // and the rest are added once it has loaded
+                        let supports_playlists = lock
+                            .get_system_info()
+                            .unwrap()
+                            .valid_extensions
+                            .iter()
+                            .any(|x| x.eq_ignore_ascii_case("m3u"));
+
+                        let disks = if disk::is_playlist(Path::new(&name)) && !supports_playlists
+                        {
+                            disk::read_playlist(Path::new(&name))
+                        } else {
+                            Ok(vec![PathBuf::from(&name)])
+                        };
+
+                        let disks = match disks {
+                            Ok(v) => v,
+                            Err(e) => {
+                                println!("Failed to read playlist {}: {}", name, e);
+                                callback(ProtocolMessageType::LoadResponse(false));
+                                continue;
+                            }
+                        };
+
+                        let contents = get_current_backend().vfs.get_virtual_file(&disks[0]);
+                        let loaded = match lock.load_game(Some(&disks[0]), contents) {
+                            Ok(v) => v,
+                            Err(e) => {
+                                println!("Failed to read disk {:?}: {:?}", disks[0], e);
+                                callback(ProtocolMessageType::LoadResponse(false));
+                                continue;
+                            }
+                        };
+                        if loaded {
+                            if disks.len() > 1 && !disk::add_disks(&lock, &disks[1..]) {
+                                println!("Core doesn't support multiple disks!");
+                            }
+
+                            get_current_backend().game_path = Some(PathBuf::from(name));
+                            load_save_ram(&lock);
+
+                            if let Some(status) = disk::get_status() {
+                                send_message(ProtocolMessageType::DiskStatus(status));
+                            }
+                        }
+                        loaded
+                    }
+                    None if get_current_backend().support_no_game => {
+                        lock.load_game(None, None).unwrap()
+                    }
+                    None => {
+                        println!("Core requires content to be loaded!");
+                        false
+                    }
+                };
+
+                callback(ProtocolMessageType::LoadResponse(loaded))
+            }
+            ProtocolMessageType::AddVirtualFile { path, data } => {
+                get_current_backend()
+                    .vfs
+                    .add_virtual_file(PathBuf::from(path), data)
+            }
+            ProtocolMessageType::DiskControl(action) => {
+                if disk::apply(action) {
+                    if let Some(status) = disk::get_status() {
+                        send_message(ProtocolMessageType::DiskStatus(status));
+                    }
+                } else {
+                    println!("Failed to change disk: {:?}", action);
+                }
+            }
+            ProtocolMessageType::SetControllerPortDevice { port, device } => {
+                lock.set_controller_port_device(port, device).unwrap()
+            }
+            ProtocolMessageType::KeyboardEvent(event) => {
+                let keyboard_callback = get_current_backend()
+                    .keyboard_callback
+                    .and_then(|v| v.callback);
+                if let Some(keyboard_callback) = keyboard_callback {
+                    unsafe {
+                        keyboard_callback(
+                            event.down,
+                            event.keycode as _,
+                            event.character,
+                            event.modifiers,
+                        );
+                    }
+                }
+            }
+            ProtocolMessageType::Unload => {
+                deinitialize_drivers();
+
+                let report = get_current_backend().get_perf_report();
+                if !report.counters.is_empty() {
+                    send_message(ProtocolMessageType::PerfReport(report));
+                }
+
+                flush_save_ram(&lock);
+                lock.unload_game().unwrap();
+                get_current_backend().game_path = None;
             }
-            ProtocolMessageType::Unload => lock.unload_game().unwrap(),
             ProtocolMessageType::APIVersion => callback(ProtocolMessageType::APIVersionResponse(
                 lock.get_api_version().unwrap(),
             )),
-            ProtocolMessageType::Run => {
+            ProtocolMessageType::Run { frame_time } => {
+                // Cameras and location services can only be started once the frontend is
+                // running, as it has to answer the core while they are starting
+                if !get_current_backend().drivers_initialized {
+                    initialize_drivers();
+                }
+
+                if let Some(frame_time_callback) = get_current_backend().frame_time_callback {
+                    let frame_time = frame_time.unwrap_or(frame_time_callback.reference);
+                    if let Some(callback) = frame_time_callback.callback {
+                        unsafe {
+                            callback(frame_time);
+                        }
+                    }
+                }
+
                 lock.run().unwrap();
                 callback(ProtocolMessageType::RunResponse)
             }
             ProtocolMessageType::Reset => lock.reset().unwrap(),
+            ProtocolMessageType::AudioRequest => {
+                let callback = get_current_backend()
+                    .audio_callback
+                    .and_then(|v| v.callback);
+                if let Some(callback) = callback {
+                    unsafe {
+                        callback();
+                    }
+                }
+            }
+            ProtocolMessageType::AudioSetState(enabled) => {
+                let set_state = get_current_backend()
+                    .audio_callback
+                    .and_then(|v| v.set_state);
+                if let Some(set_state) = set_state {
+                    unsafe {
+                        set_state(enabled);
+                    }
+                }
+            }
+            ProtocolMessageType::CameraFrame {
+                buffer,
+                width,
+                height,
+            } => {
+                let frame_raw_framebuffer = get_current_backend()
+                    .camera_callback
+                    .and_then(|v| v.frame_raw_framebuffer);
+                if let Some(frame_raw_framebuffer) = frame_raw_framebuffer {
+                    unsafe {
+                        frame_raw_framebuffer(buffer.as_ptr(), width, height, width as usize * 4);
+                    }
+                }
+            }
             ProtocolMessageType::SystemInfo => callback(ProtocolMessageType::SystemInfoResponse(
                 lock.get_system_info().unwrap(),
             )),
@@ -86,6 +249,77 @@ pub fn run(core: String, address: String) {
     }
 }
 
+/// Tells the core that the camera and location services are ready to be started.
+fn initialize_drivers() {
+    let state = get_current_backend();
+    state.drivers_initialized = true;
+
+    let camera = state.camera_callback.and_then(|v| v.initialized);
+    let location = state.location_callback.and_then(|v| v.initialized);
+
+    for initialized in camera.into_iter().chain(location) {
+        unsafe {
+            initialized();
+        }
+    }
+}
+
+/// Tells the core that the camera and location services are no longer available.
+fn deinitialize_drivers() {
+    let state = get_current_backend();
+    if !state.drivers_initialized {
+        return;
+    }
+    state.drivers_initialized = false;
+
+    let camera = state.camera_callback.and_then(|v| v.deinitialized);
+    let location = state.location_callback.and_then(|v| v.deinitialized);
+
+    for deinitialized in camera.into_iter().chain(location) {
+        unsafe {
+            deinitialized();
+        }
+    }
+}
+
+/// Restores the save RAM of the loaded content from disk, if any has been saved before.
+fn load_save_ram(core: &LibRetroCore) {
+    let path = match get_current_backend().get_save_ram_path() {
+        Some(v) => v,
+        None => return,
+    };
+
+    if !path.exists() {
+        return;
+    }
+
+    let mut data = Vec::new();
+    File::open(&path)
+        .unwrap()
+        .read_to_end(&mut data)
+        .unwrap();
+
+    if core.write_memory(RETRO_MEMORY_SAVE_RAM, &data).unwrap() {
+        println!("Loaded save RAM from {:?}", path);
+    }
+}
+
+/// Writes the save RAM of the loaded content to disk, if the core has any.
+fn flush_save_ram(core: &LibRetroCore) {
+    let path = match get_current_backend().get_save_ram_path() {
+        Some(v) => v,
+        None => return,
+    };
+
+    let data = match core.read_memory(RETRO_MEMORY_SAVE_RAM).unwrap() {
+        Some(v) => v,
+        None => return,
+    };
+
+    File::create(&path).unwrap().write_all(&data).unwrap();
+    println!("Saved save RAM to {:?}", path);
+}
+
 /// Sends a message to the frontend, with a optional response.
 pub fn send_message(message: ProtocolMessageType) -> Option<ProtocolFuture> {
     match unsafe { &ADAPTER } {
@@ -6,8 +6,26 @@ use std::fs::canonicalize;
 use std::fs::create_dir;
 
 use std::path::Path;
+use std::path::PathBuf;
+
+use std::time::Instant;
 
 use retro_types::RetroPixelFormat;
+use retro_types::RetroFrameTimeCallback;
+use retro_types::RetroAudioCallback;
+use retro_types::RetroKeyboardCallback;
+use retro_types::RetroCameraCallback;
+use retro_types::RetroLocationCallback;
+use retro_types::RetroDiskControlExtCallback;
+use retro_types::RetroPerfCounter;
+use retro_types::RetroPerfCounterReport;
+use retro_types::RetroPerfReport;
+
+use ffi::char_pointer_to_owned;
+
+use backend::vfs::Vfs;
+
+use input::snapshot::InputSnapshot;
 
 // Static callbacks
 pub struct BackendState {
@@ -17,6 +35,50 @@ pub struct BackendState {
     pub save_path: CString,
     pub system_path: CString,
 
+    /// If the core can be run without any content.
+    pub support_no_game: bool,
+
+    /// Callback to inform the core how much time has passed before each frame.
+    pub frame_time_callback: Option<RetroFrameTimeCallback>,
+
+    /// Callbacks for the core to be asked for audio.
+    pub audio_callback: Option<RetroAudioCallback>,
+
+    /// Callback for the core to be told about key presses as they happen.
+    pub keyboard_callback: Option<RetroKeyboardCallback>,
+
+    /// Callbacks for the core to be given camera frames.
+    pub camera_callback: Option<RetroCameraCallback>,
+    /// Callbacks for the core to be told where the user is.
+    pub location_callback: Option<RetroLocationCallback>,
+
+    /// If the core has been told that the camera and location services are ready to be
+    /// started.
+    pub drivers_initialized: bool,
+
+    /// Callbacks for swapping the disks of the core.
+    pub disk_control: Option<RetroDiskControlExtCallback>,
+
+    /// Performance counters that the core has registered. These are owned by the core.
+    pub perf_counters: Vec<*mut RetroPerfCounter>,
+    /// When this backend was started, which performance timing is relative to.
+    pub started: Instant,
+
+    /// Files that the core can access, other than those on disk.
+    pub vfs: Vfs,
+
+    /// The content that is currently loaded, if any.
+    pub game_path: Option<PathBuf>,
+
+    /// The last software frame sent to the frontend, used to detect duplicate frames.
+    pub last_frame: Vec<u8>,
+    pub last_frame_format: Option<RetroPixelFormat>,
+    pub last_frame_width: usize,
+    pub last_frame_height: usize,
+
+    /// Every device's state at the last input poll, which the core's queries are answered from.
+    pub input: InputSnapshot,
+
     is_global: bool,
 }
 
@@ -40,6 +102,44 @@ impl BackendState {
         }
     }
 
+    /// Returns where the save RAM for the currently loaded content should be stored.
+    pub fn get_save_ram_path(&self) -> Option<PathBuf> {
+        let game_path = match &self.game_path {
+            &Some(ref v) => v,
+            &None => return None,
+        };
+
+        let name = game_path.file_stem()?;
+
+        let mut path = PathBuf::from(self.save_path.to_str().unwrap());
+        path.push(name);
+        path.set_extension("srm");
+        Some(path)
+    }
+
+    /// Takes a snapshot of all registered performance counters, slowest first.
+    pub fn get_perf_report(&self) -> RetroPerfReport {
+        let mut counters = self.perf_counters
+            .iter()
+            .map(|&counter| unsafe {
+                let counter = &*counter;
+                RetroPerfCounterReport {
+                    ident: if counter.ident.is_null() {
+                        "(unnamed)".to_owned()
+                    } else {
+                        char_pointer_to_owned(counter.ident).unwrap_or_else(|_| "(invalid)".to_owned())
+                    },
+                    call_count: counter.call_count,
+                    total_ticks: counter.total,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        counters.sort_by(|a, b| b.total_ticks.cmp(&a.total_ticks));
+
+        RetroPerfReport { counters }
+    }
+
     /// Builds a new frontend state.
     pub fn new(format: RetroPixelFormat) -> BackendState {
         let saves_dir = Path::new("saves");
@@ -72,6 +172,26 @@ impl BackendState {
             save_path: CString::new(saves_dir).unwrap(),
             system_path: CString::new(systems_dir).unwrap(),
 
+            support_no_game: false,
+            frame_time_callback: None,
+            audio_callback: None,
+            keyboard_callback: None,
+            camera_callback: None,
+            location_callback: None,
+            drivers_initialized: false,
+            disk_control: None,
+            perf_counters: Vec::new(),
+            started: Instant::now(),
+            vfs: Vfs::new(),
+            game_path: None,
+
+            last_frame: Vec::new(),
+            last_frame_format: None,
+            last_frame_width: 0,
+            last_frame_height: 0,
+
+            input: InputSnapshot::default(),
+
             is_global: false,
         }
     }
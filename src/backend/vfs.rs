@@ -0,0 +1,702 @@
+//! Routes file I/O from cores through oxretro.
+//!
+//! Cores may only touch files within the content, save and system directories, and every
+//! access is logged. Files can also be virtual - held in memory rather than on disk, such as
+//! content which was sent over the protocol.
+
+use retro_types::RetroVfsInterface;
+use retro_types::RetroVfsFileHandle;
+use retro_types::RetroVfsDirHandle;
+use retro_types::{RETRO_VFS_FILE_ACCESS_READ, RETRO_VFS_FILE_ACCESS_UPDATE_EXISTING,
+                  RETRO_VFS_FILE_ACCESS_WRITE};
+use retro_types::{RETRO_VFS_SEEK_POSITION_CURRENT, RETRO_VFS_SEEK_POSITION_END,
+                  RETRO_VFS_SEEK_POSITION_START};
+use retro_types::{RETRO_VFS_STAT_IS_DIRECTORY, RETRO_VFS_STAT_IS_VALID};
+
+use backend::state::get_current_backend;
+
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Cursor;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::os::raw::*;
+use std::path::Path;
+use std::path::PathBuf;
+use std::slice;
+
+/// Files which are held in memory, rather than on disk.
+pub struct Vfs {
+    virtual_files: HashMap<PathBuf, Vec<u8>>,
+}
+
+impl Vfs {
+    /// Makes a file available at the specified path, without it existing on disk. Virtual
+    /// files are read only.
+    pub fn add_virtual_file(&mut self, path: PathBuf, data: Vec<u8>) {
+        println!("VFS: added virtual file {:?} ({} bytes)", path, data.len());
+        self.virtual_files.insert(path, data);
+    }
+
+    /// Returns a copy of the contents of a virtual file.
+    pub fn get_virtual_file(&self, path: &Path) -> Option<Vec<u8>> {
+        self.virtual_files.get(path).cloned()
+    }
+
+    /// Opens a virtual file, or returns None if there isn't one at the path. Virtual files
+    /// can't be written to.
+    fn open_virtual(&self, path: &Path, write: bool) -> Option<io::Result<Cursor<Vec<u8>>>> {
+        let data = self.get_virtual_file(path)?;
+
+        if write {
+            println!("VFS: denied write to virtual file {:?}", path);
+            return Some(Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "Virtual files are read only",
+            )));
+        }
+
+        println!("VFS: open virtual file {:?}", path);
+        Some(Ok(Cursor::new(data)))
+    }
+
+    /// Returns the names of the virtual files in a directory, as given by the core.
+    fn get_virtual_entries(&self, dir: &Path) -> Vec<String> {
+        self.virtual_files
+            .keys()
+            .filter(|x| x.parent() == Some(dir))
+            .filter_map(|x| x.file_name())
+            .map(|x| x.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    pub fn new() -> Vfs {
+        Vfs {
+            virtual_files: HashMap::new(),
+        }
+    }
+}
+
+/// Where the data of an open file comes from.
+enum FileSource {
+    Disk(File),
+    Virtual(Cursor<Vec<u8>>),
+}
+
+struct FileHandle {
+    path: CString,
+    source: FileSource,
+}
+
+struct DirHandle {
+    /// Names of entries, and if they are directories.
+    entries: Vec<(CString, bool)>,
+    current: Option<usize>,
+}
+
+/// Resolves a path to an absolute one without any links or `..`s, even if it doesn't exist
+/// yet.
+fn resolve(path: &Path) -> Option<PathBuf> {
+    let mut existing = path;
+    let mut missing = Vec::new();
+
+    loop {
+        let candidate = if existing.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            existing
+        };
+
+        if let Ok(mut resolved) = candidate.canonicalize() {
+            for component in missing.iter().rev() {
+                resolved.push(component);
+            }
+            return Some(resolved);
+        }
+
+        // file_name() is None for a trailing "..", which can't be resolved without the
+        // parent existing
+        missing.push(existing.file_name()?.to_owned());
+        existing = existing.parent()?;
+    }
+}
+
+/// Resolves a path, returning it if it is within one of the roots. Links and `..`s are
+/// resolved first, so that they can't be used to escape.
+fn check_path(path: &Path, roots: &[PathBuf]) -> Option<PathBuf> {
+    let resolved = resolve(path)?;
+
+    // Paths are compared by component, so `saves2` isn't within `saves`
+    if roots
+        .iter()
+        .filter_map(|x| resolve(x))
+        .any(|x| resolved.starts_with(x))
+    {
+        Some(resolved)
+    } else {
+        None
+    }
+}
+
+/// Returns the directories which cores are allowed to access.
+fn get_allowed_roots() -> Vec<PathBuf> {
+    let state = get_current_backend();
+
+    let mut roots = vec![
+        PathBuf::from(state.save_path.to_str().unwrap()),
+        PathBuf::from(state.system_path.to_str().unwrap()),
+    ];
+
+    if let Some(content_dir) = state.game_path.as_ref().and_then(|x| x.parent()) {
+        roots.push(content_dir.to_owned());
+    }
+
+    roots
+}
+
+/// Checks that a core is allowed to perform an action on a path, logging the attempt.
+unsafe fn sandbox(path: *const c_char, action: &str) -> Option<PathBuf> {
+    if path.is_null() {
+        return None;
+    }
+
+    let path = match CStr::from_ptr(path).to_str() {
+        Ok(v) => Path::new(v),
+        Err(_) => {
+            println!("VFS: denied {} of non UTF-8 path", action);
+            return None;
+        }
+    };
+
+    match check_path(path, &get_allowed_roots()) {
+        Some(resolved) => {
+            println!("VFS: {} {:?}", action, path);
+            Some(resolved)
+        }
+        None => {
+            println!("VFS: denied {} of {:?}", action, path);
+            None
+        }
+    }
+}
+
+/// Lists a directory (`resolved`, which has been sandboxed), along with the virtual files in it
+/// (`dir`, as given by the core). Entries are names, and if they are directories.
+fn list_dir(
+    vfs: &Vfs,
+    dir: &Path,
+    resolved: &Path,
+    include_hidden: bool,
+) -> io::Result<Vec<(CString, bool)>> {
+    let mut entries = Vec::new();
+
+    for entry in fs::read_dir(resolved)? {
+        let entry = match entry {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !include_hidden && name.starts_with('.') {
+            continue;
+        }
+
+        let is_dir = entry.file_type().map(|x| x.is_dir()).unwrap_or(false);
+        entries.push((CString::new(name).unwrap(), is_dir));
+    }
+
+    // Virtual files show up alongside the real ones
+    for name in vfs.get_virtual_entries(dir) {
+        entries.push((CString::new(name).unwrap(), false));
+    }
+
+    Ok(entries)
+}
+
+/// Returns the path as given by the core, for looking up virtual files.
+unsafe fn get_virtual_path(path: *const c_char) -> Option<PathBuf> {
+    if path.is_null() {
+        return None;
+    }
+
+    CStr::from_ptr(path).to_str().ok().map(PathBuf::from)
+}
+
+/// Converts an I/O result into the integers used by the VFS API.
+fn to_result(result: io::Result<u64>) -> i64 {
+    match result {
+        Ok(v) => v as i64,
+        Err(_) => -1,
+    }
+}
+
+pub unsafe extern "C" fn vfs_get_path(stream: *mut RetroVfsFileHandle) -> *const c_char {
+    let handle = &*(stream as *mut FileHandle);
+    handle.path.as_ptr()
+}
+
+pub unsafe extern "C" fn vfs_open(
+    path: *const c_char,
+    mode: c_uint,
+    _hints: c_uint,
+) -> *mut RetroVfsFileHandle {
+    let read = mode & RETRO_VFS_FILE_ACCESS_READ != 0;
+    let write = mode & RETRO_VFS_FILE_ACCESS_WRITE != 0;
+    let update = mode & RETRO_VFS_FILE_ACCESS_UPDATE_EXISTING != 0;
+
+    let virtual_path = match get_virtual_path(path) {
+        Some(v) => v,
+        None => return 0 as *mut RetroVfsFileHandle,
+    };
+
+    let source = match get_current_backend().vfs.open_virtual(&virtual_path, write) {
+        Some(Ok(cursor)) => FileSource::Virtual(cursor),
+        Some(Err(_)) => return 0 as *mut RetroVfsFileHandle,
+        None => {
+            let resolved = match sandbox(path, if write { "write" } else { "read" }) {
+                Some(v) => v,
+                None => return 0 as *mut RetroVfsFileHandle,
+            };
+
+            // Writing truncates, or creates the file, unless an existing file is being updated
+            let file = OpenOptions::new()
+                .read(read || update)
+                .write(write)
+                .create(write && !update)
+                .truncate(write && !update)
+                .open(resolved);
+
+            match file {
+                Ok(v) => FileSource::Disk(v),
+                Err(_) => return 0 as *mut RetroVfsFileHandle,
+            }
+        }
+    };
+
+    let handle = Box::new(FileHandle {
+        path: CStr::from_ptr(path).to_owned(),
+        source,
+    });
+
+    Box::into_raw(handle) as *mut RetroVfsFileHandle
+}
+
+pub unsafe extern "C" fn vfs_close(stream: *mut RetroVfsFileHandle) -> c_int {
+    if stream.is_null() {
+        return -1;
+    }
+
+    drop(Box::from_raw(stream as *mut FileHandle));
+    0
+}
+
+pub unsafe extern "C" fn vfs_size(stream: *mut RetroVfsFileHandle) -> i64 {
+    let handle = &mut *(stream as *mut FileHandle);
+    to_result(match handle.source {
+        FileSource::Disk(ref file) => file.metadata().map(|x| x.len()),
+        FileSource::Virtual(ref cursor) => Ok(cursor.get_ref().len() as u64),
+    })
+}
+
+pub unsafe extern "C" fn vfs_tell(stream: *mut RetroVfsFileHandle) -> i64 {
+    let handle = &mut *(stream as *mut FileHandle);
+    to_result(match handle.source {
+        FileSource::Disk(ref mut file) => file.seek(SeekFrom::Current(0)),
+        FileSource::Virtual(ref cursor) => Ok(cursor.position()),
+    })
+}
+
+pub unsafe extern "C" fn vfs_seek(
+    stream: *mut RetroVfsFileHandle,
+    offset: i64,
+    seek_position: c_int,
+) -> i64 {
+    let handle = &mut *(stream as *mut FileHandle);
+
+    let position = match seek_position {
+        RETRO_VFS_SEEK_POSITION_START if offset >= 0 => SeekFrom::Start(offset as u64),
+        RETRO_VFS_SEEK_POSITION_CURRENT => SeekFrom::Current(offset),
+        RETRO_VFS_SEEK_POSITION_END => SeekFrom::End(offset),
+        _ => return -1,
+    };
+
+    to_result(match handle.source {
+        FileSource::Disk(ref mut file) => file.seek(position),
+        FileSource::Virtual(ref mut cursor) => cursor.seek(position),
+    })
+}
+
+pub unsafe extern "C" fn vfs_read(stream: *mut RetroVfsFileHandle, s: *mut c_void, len: u64) -> i64 {
+    let handle = &mut *(stream as *mut FileHandle);
+    let buffer = slice::from_raw_parts_mut(s as *mut u8, len as usize);
+
+    to_result(
+        match handle.source {
+            FileSource::Disk(ref mut file) => file.read(buffer),
+            FileSource::Virtual(ref mut cursor) => cursor.read(buffer),
+        }.map(|x| x as u64),
+    )
+}
+
+pub unsafe extern "C" fn vfs_write(
+    stream: *mut RetroVfsFileHandle,
+    s: *const c_void,
+    len: u64,
+) -> i64 {
+    let handle = &mut *(stream as *mut FileHandle);
+    let buffer = slice::from_raw_parts(s as *const u8, len as usize);
+
+    match handle.source {
+        FileSource::Disk(ref mut file) => to_result(file.write(buffer).map(|x| x as u64)),
+        FileSource::Virtual(_) => -1,
+    }
+}
+
+pub unsafe extern "C" fn vfs_flush(stream: *mut RetroVfsFileHandle) -> c_int {
+    let handle = &mut *(stream as *mut FileHandle);
+
+    match handle.source {
+        FileSource::Disk(ref mut file) => match file.flush() {
+            Ok(_) => 0,
+            Err(_) => -1,
+        },
+        FileSource::Virtual(_) => 0,
+    }
+}
+
+pub unsafe extern "C" fn vfs_remove(path: *const c_char) -> c_int {
+    match sandbox(path, "remove").map(fs::remove_file) {
+        Some(Ok(_)) => 0,
+        _ => -1,
+    }
+}
+
+pub unsafe extern "C" fn vfs_rename(old_path: *const c_char, new_path: *const c_char) -> c_int {
+    let old_path = match sandbox(old_path, "rename") {
+        Some(v) => v,
+        None => return -1,
+    };
+
+    let new_path = match sandbox(new_path, "rename to") {
+        Some(v) => v,
+        None => return -1,
+    };
+
+    match fs::rename(old_path, new_path) {
+        Ok(_) => 0,
+        Err(_) => -1,
+    }
+}
+
+pub unsafe extern "C" fn vfs_truncate(stream: *mut RetroVfsFileHandle, length: i64) -> i64 {
+    let handle = &mut *(stream as *mut FileHandle);
+
+    match handle.source {
+        FileSource::Disk(ref file) if length >= 0 => match file.set_len(length as u64) {
+            Ok(_) => 0,
+            Err(_) => -1,
+        },
+        _ => -1,
+    }
+}
+
+pub unsafe extern "C" fn vfs_stat(path: *const c_char, size: *mut i32) -> c_int {
+    let virtual_path = match get_virtual_path(path) {
+        Some(v) => v,
+        None => return 0,
+    };
+
+    let (flags, length) = match get_current_backend().vfs.get_virtual_file(&virtual_path) {
+        Some(data) => (RETRO_VFS_STAT_IS_VALID, data.len() as u64),
+        None => {
+            let metadata = match sandbox(path, "stat").map(fs::metadata) {
+                Some(Ok(v)) => v,
+                _ => return 0,
+            };
+
+            let flags = if metadata.is_dir() {
+                RETRO_VFS_STAT_IS_VALID | RETRO_VFS_STAT_IS_DIRECTORY
+            } else {
+                RETRO_VFS_STAT_IS_VALID
+            };
+
+            (flags, metadata.len())
+        }
+    };
+
+    if !size.is_null() {
+        *size = length.min(i32::max_value() as u64) as i32;
+    }
+
+    flags
+}
+
+pub unsafe extern "C" fn vfs_mkdir(dir: *const c_char) -> c_int {
+    let dir = match sandbox(dir, "mkdir") {
+        Some(v) => v,
+        None => return -1,
+    };
+
+    if dir.exists() {
+        return -2;
+    }
+
+    match fs::create_dir(dir) {
+        Ok(_) => 0,
+        Err(_) => -1,
+    }
+}
+
+pub unsafe extern "C" fn vfs_opendir(dir: *const c_char, include_hidden: bool) -> *mut RetroVfsDirHandle {
+    let virtual_dir = match get_virtual_path(dir) {
+        Some(v) => v,
+        None => return 0 as *mut RetroVfsDirHandle,
+    };
+
+    let resolved = match sandbox(dir, "list") {
+        Some(v) => v,
+        None => return 0 as *mut RetroVfsDirHandle,
+    };
+
+    let entries = match list_dir(
+        &get_current_backend().vfs,
+        &virtual_dir,
+        &resolved,
+        include_hidden,
+    ) {
+        Ok(v) => v,
+        Err(_) => return 0 as *mut RetroVfsDirHandle,
+    };
+
+    let handle = Box::new(DirHandle {
+        entries,
+        current: None,
+    });
+
+    Box::into_raw(handle) as *mut RetroVfsDirHandle
+}
+
+pub unsafe extern "C" fn vfs_readdir(dirstream: *mut RetroVfsDirHandle) -> bool {
+    let handle = &mut *(dirstream as *mut DirHandle);
+
+    let next = match handle.current {
+        Some(v) => v + 1,
+        None => 0,
+    };
+
+    if next >= handle.entries.len() {
+        return false;
+    }
+
+    handle.current = Some(next);
+    true
+}
+
+pub unsafe extern "C" fn vfs_dirent_get_name(dirstream: *mut RetroVfsDirHandle) -> *const c_char {
+    let handle = &*(dirstream as *mut DirHandle);
+
+    match handle.current {
+        Some(v) => handle.entries[v].0.as_ptr(),
+        None => 0 as *const c_char,
+    }
+}
+
+pub unsafe extern "C" fn vfs_dirent_is_dir(dirstream: *mut RetroVfsDirHandle) -> bool {
+    let handle = &*(dirstream as *mut DirHandle);
+
+    match handle.current {
+        Some(v) => handle.entries[v].1,
+        None => false,
+    }
+}
+
+pub unsafe extern "C" fn vfs_closedir(dirstream: *mut RetroVfsDirHandle) -> c_int {
+    if dirstream.is_null() {
+        return -1;
+    }
+
+    drop(Box::from_raw(dirstream as *mut DirHandle));
+    0
+}
+
+/// The interface handed to cores, which supports up to v3.
+pub static VFS_INTERFACE: RetroVfsInterface = RetroVfsInterface {
+    get_path: vfs_get_path,
+    open: vfs_open,
+    close: vfs_close,
+    size: vfs_size,
+    tell: vfs_tell,
+    seek: vfs_seek,
+    read: vfs_read,
+    write: vfs_write,
+    flush: vfs_flush,
+    remove: vfs_remove,
+    rename: vfs_rename,
+    truncate: vfs_truncate,
+    stat: vfs_stat,
+    mkdir: vfs_mkdir,
+    opendir: vfs_opendir,
+    readdir: vfs_readdir,
+    dirent_get_name: vfs_dirent_get_name,
+    dirent_is_dir: vfs_dirent_is_dir,
+    closedir: vfs_closedir,
+};
+
+#[cfg(test)]
+mod test {
+    use super::Vfs;
+    use super::check_path;
+    use super::list_dir;
+    use super::resolve;
+
+    use std::env;
+    use std::ffi::CString;
+    use std::fs;
+    use std::fs::File;
+    use std::path::PathBuf;
+    use std::process;
+
+    /// Creates an empty directory for a test, which has `saves`, `saves2` and `outside`
+    /// directories in it.
+    fn make_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("oxretro-vfs-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+
+        for child in &["saves", "saves2", "outside"] {
+            fs::create_dir_all(dir.join(child)).unwrap();
+        }
+        File::create(dir.join("outside").join("secret")).unwrap();
+
+        dir.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn resolving() {
+        let dir = make_dir("resolving");
+
+        assert_eq!(
+            resolve(&dir.join("saves").join("..").join("outside")),
+            Some(dir.join("outside"))
+        );
+
+        // Paths which don't exist yet are resolved as far as they can be
+        assert_eq!(
+            resolve(&dir.join("saves").join("new").join("game.srm")),
+            Some(dir.join("saves").join("new").join("game.srm"))
+        );
+
+        // ".." can't be resolved without its parent existing
+        assert_eq!(resolve(&dir.join("saves").join("new").join("..")), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn sandboxing() {
+        let dir = make_dir("sandboxing");
+        let roots = vec![dir.join("saves")];
+
+        assert_eq!(
+            check_path(&dir.join("saves").join("game.srm"), &roots),
+            Some(dir.join("saves").join("game.srm"))
+        );
+        assert!(check_path(&dir.join("saves"), &roots).is_some());
+
+        // Escaping through ".."
+        assert_eq!(
+            check_path(&dir.join("saves").join("..").join("outside").join("secret"), &roots),
+            None
+        );
+        assert_eq!(
+            check_path(&dir.join("saves").join("new").join("..").join(".."), &roots),
+            None
+        );
+
+        // Directories which only share a prefix with a root
+        assert_eq!(check_path(&dir.join("saves2").join("game.srm"), &roots), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let dir = make_dir("symlinks");
+        let roots = vec![dir.join("saves")];
+
+        symlink(dir.join("outside"), dir.join("saves").join("link")).unwrap();
+        assert_eq!(
+            check_path(&dir.join("saves").join("link").join("secret"), &roots),
+            None
+        );
+        assert_eq!(
+            check_path(&dir.join("saves").join("link").join("new"), &roots),
+            None
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn virtual_files() {
+        let mut vfs = Vfs::new();
+        vfs.add_virtual_file(PathBuf::from("content/game.bin"), vec![1, 2, 3]);
+
+        assert_eq!(
+            vfs.open_virtual(&PathBuf::from("content/game.bin"), false)
+                .unwrap()
+                .unwrap()
+                .into_inner(),
+            vec![1, 2, 3]
+        );
+        assert!(
+            vfs.open_virtual(&PathBuf::from("content/game.bin"), true)
+                .unwrap()
+                .is_err()
+        );
+        assert!(
+            vfs.open_virtual(&PathBuf::from("content/other.bin"), false)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn listing() {
+        let dir = make_dir("listing");
+        File::create(dir.join("saves").join("game.srm")).unwrap();
+        File::create(dir.join("saves").join(".hidden")).unwrap();
+
+        let mut vfs = Vfs::new();
+        vfs.add_virtual_file(PathBuf::from("saves/virtual.bin"), Vec::new());
+        vfs.add_virtual_file(PathBuf::from("elsewhere/other.bin"), Vec::new());
+
+        let mut entries = list_dir(
+            &vfs,
+            &PathBuf::from("saves"),
+            &dir.join("saves"),
+            false,
+        ).unwrap();
+        entries.sort();
+        assert_eq!(
+            entries,
+            vec![
+                (CString::new("game.srm").unwrap(), false),
+                (CString::new("virtual.bin").unwrap(), false),
+            ]
+        );
+
+        let entries = list_dir(&vfs, &PathBuf::from("saves"), &dir.join("saves"), true).unwrap();
+        assert_eq!(entries.len(), 3);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,85 @@
+/// Camera sources backed by image files, for testing cores without a real camera.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use camera::CameraFrame;
+use camera::CameraSource;
+use camera::image;
+
+/// Shows a single image forever.
+pub struct ImageCamera {
+    frame: CameraFrame,
+}
+
+impl CameraSource for ImageCamera {
+    fn start(&mut self) -> bool {
+        true
+    }
+
+    fn stop(&mut self) {}
+
+    fn next_frame(&mut self) -> Option<CameraFrame> {
+        Some(self.frame.clone())
+    }
+}
+
+impl ImageCamera {
+    /// Loads the image to show.
+    pub fn load(path: &str) -> io::Result<ImageCamera> {
+        Ok(ImageCamera {
+            frame: image::load(Path::new(path))?,
+        })
+    }
+}
+
+/// Shows a sequence of images, one per frame, looping once the end has been reached.
+pub struct DirectoryCamera {
+    frames: Vec<CameraFrame>,
+    next_frame: usize,
+}
+
+impl CameraSource for DirectoryCamera {
+    fn start(&mut self) -> bool {
+        // Each time the camera is started, the sequence begins again
+        self.next_frame = 0;
+        true
+    }
+
+    fn stop(&mut self) {}
+
+    fn next_frame(&mut self) -> Option<CameraFrame> {
+        let frame = self.frames[self.next_frame].clone();
+        self.next_frame = (self.next_frame + 1) % self.frames.len();
+        Some(frame)
+    }
+}
+
+impl DirectoryCamera {
+    /// Loads every supported image in a directory, in name order.
+    pub fn load(path: &str) -> io::Result<DirectoryCamera> {
+        let mut paths = fs::read_dir(path)?
+            .map(|x| x.map(|x| x.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        paths.retain(|x| image::is_supported(x));
+        paths.sort();
+
+        if paths.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No PPM or BMP images in {}", path),
+            ));
+        }
+
+        let frames = paths
+            .iter()
+            .map(|x| image::load(x))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(DirectoryCamera {
+            frames,
+            next_frame: 0,
+        })
+    }
+}
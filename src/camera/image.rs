@@ -0,0 +1,212 @@
+/// Minimal decoders for the image formats which can be used as camera frames: binary PPM (P6)
+/// and uncompressed BMP.
+
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use camera::CameraFrame;
+
+/// Builds an error for a malformed image.
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Packs 8 bit channels into a XRGB8888 pixel.
+#[inline]
+fn pack(r: u8, g: u8, b: u8) -> u32 {
+    ((r as u32) << 16) | ((g as u32) << 8) | (b as u32)
+}
+
+/// Returns if a file looks like an image that can be decoded, judging by its extension.
+pub fn is_supported(path: &Path) -> bool {
+    match path.extension().and_then(|x| x.to_str()) {
+        Some(extension) => match extension.to_lowercase().as_str() {
+            "ppm" | "bmp" => true,
+            _ => false,
+        },
+        None => false,
+    }
+}
+
+/// Loads an image from disk.
+pub fn load(path: &Path) -> io::Result<CameraFrame> {
+    let mut data = Vec::new();
+    File::open(path)?.read_to_end(&mut data)?;
+    decode(&data)
+}
+
+/// Decodes an image in any supported format.
+pub fn decode(data: &[u8]) -> io::Result<CameraFrame> {
+    if data.starts_with(b"P6") {
+        decode_ppm(data)
+    } else if data.starts_with(b"BM") {
+        decode_bmp(data)
+    } else {
+        Err(invalid("Unsupported image format (expected PPM or BMP)"))
+    }
+}
+
+/// Reads the next whitespace separated number from a PPM header, skipping comments.
+fn read_ppm_value(data: &[u8], offset: &mut usize) -> io::Result<u32> {
+    loop {
+        match data.get(*offset) {
+            Some(&b'#') => while *offset < data.len() && data[*offset] != b'\n' {
+                *offset += 1;
+            },
+            Some(x) if x.is_ascii_whitespace() => *offset += 1,
+            Some(_) => break,
+            None => return Err(invalid("Truncated PPM header")),
+        }
+    }
+
+    let start = *offset;
+    while *offset < data.len() && data[*offset].is_ascii_digit() {
+        *offset += 1;
+    }
+
+    String::from_utf8_lossy(&data[start..*offset])
+        .parse::<u32>()
+        .map_err(|_| invalid("Malformed PPM header"))
+}
+
+/// Decodes a binary PPM image.
+fn decode_ppm(data: &[u8]) -> io::Result<CameraFrame> {
+    let mut offset = 2;
+    let width = read_ppm_value(data, &mut offset)?;
+    let height = read_ppm_value(data, &mut offset)?;
+    let max_value = read_ppm_value(data, &mut offset)?;
+
+    if max_value == 0 || max_value > 65535 {
+        return Err(invalid("Bad PPM maximum value"));
+    }
+
+    // A single whitespace character separates the header from the pixels
+    offset += 1;
+
+    let channel_size = if max_value > 255 { 2 } else { 1 };
+    let pixels = (width * height) as usize;
+
+    if data.len() < offset + pixels * 3 * channel_size {
+        return Err(invalid("Truncated PPM image"));
+    }
+
+    let channel = |index: usize| -> u8 {
+        let value = if channel_size == 2 {
+            ((data[offset + index * 2] as u32) << 8) | data[offset + index * 2 + 1] as u32
+        } else {
+            data[offset + index] as u32
+        };
+        (value * 255 / max_value) as u8
+    };
+
+    let buffer = (0..pixels)
+        .map(|i| pack(channel(i * 3), channel(i * 3 + 1), channel(i * 3 + 2)))
+        .collect();
+
+    Ok(CameraFrame {
+        buffer,
+        width,
+        height,
+    })
+}
+
+/// Decodes an uncompressed 24 or 32 bit BMP image.
+fn decode_bmp(data: &[u8]) -> io::Result<CameraFrame> {
+    if data.len() < 54 {
+        return Err(invalid("Truncated BMP header"));
+    }
+
+    let pixel_offset = LittleEndian::read_u32(&data[10..]) as usize;
+    let width = LittleEndian::read_i32(&data[18..]);
+    let height = LittleEndian::read_i32(&data[22..]);
+    let bits_per_pixel = LittleEndian::read_u16(&data[28..]);
+    let compression = LittleEndian::read_u32(&data[30..]);
+
+    // Bitfields are only accepted for 32 bit images, where they are almost always BGRA
+    let bytes_per_pixel = match (bits_per_pixel, compression) {
+        (24, 0) => 3,
+        (32, 0) | (32, 3) => 4,
+        _ => return Err(invalid("Unsupported BMP format (expected 24/32 bit uncompressed)")),
+    };
+
+    if width <= 0 || height == 0 {
+        return Err(invalid("Bad BMP dimensions"));
+    }
+
+    // Rows are stored bottom-up, unless the height is negative
+    let bottom_up = height > 0;
+    let width = width as usize;
+    let height = height.abs() as usize;
+
+    // Rows are padded to a multiple of 4 bytes
+    let stride = (width * bytes_per_pixel + 3) & !3;
+
+    if data.len() < pixel_offset + stride * height {
+        return Err(invalid("Truncated BMP image"));
+    }
+
+    let mut buffer = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let source_y = if bottom_up { height - 1 - y } else { y };
+        let row = &data[pixel_offset + source_y * stride..];
+
+        for x in 0..width {
+            let pixel = &row[x * bytes_per_pixel..];
+            buffer.push(pack(pixel[2], pixel[1], pixel[0]));
+        }
+    }
+
+    Ok(CameraFrame {
+        buffer,
+        width: width as u32,
+        height: height as u32,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::decode;
+
+    #[test]
+    fn decode_ppm() {
+        let mut data = b"P6\n# A comment\n2 1\n255\n".to_vec();
+        data.extend_from_slice(&[255, 0, 0, 0, 128, 255]);
+
+        let frame = decode(&data).unwrap();
+        assert_eq!(frame.width, 2);
+        assert_eq!(frame.height, 1);
+        assert_eq!(frame.buffer, [0x00FF0000, 0x000080FF]);
+    }
+
+    #[test]
+    fn decode_bmp_bottom_up() {
+        // 1x2, 24 bit, so each row is padded from 3 to 4 bytes
+        let mut data = vec![0 as u8; 54];
+        data[0] = b'B';
+        data[1] = b'M';
+        data[10] = 54;
+        data[14] = 40;
+        data[18] = 1;
+        data[22] = 2;
+        data[26] = 1;
+        data[28] = 24;
+
+        // Bottom row first, in BGR order
+        data.extend_from_slice(&[0x33, 0x22, 0x11, 0]);
+        data.extend_from_slice(&[0x66, 0x55, 0x44, 0]);
+
+        let frame = decode(&data).unwrap();
+        assert_eq!(frame.width, 1);
+        assert_eq!(frame.height, 2);
+        assert_eq!(frame.buffer, [0x00445566, 0x00112233]);
+    }
+
+    #[test]
+    fn reject_unknown_format() {
+        assert!(decode(b"GIF89a").is_err());
+    }
+}
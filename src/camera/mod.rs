@@ -0,0 +1,196 @@
+/// Cameras which cores can receive frames from, such as for augmented reality games.
+///
+/// Frames can come from real devices, or from files so that these cores can be tested without
+/// one.
+
+pub mod image;
+pub mod file;
+
+#[cfg(target_os = "linux")]
+pub mod v4l2;
+
+use std::path::Path;
+
+/// Where camera frames should come from.
+pub enum CameraSourceType {
+    /// A real device if one is present, otherwise no camera at all.
+    Auto,
+    /// A single image, shown forever.
+    Image(String),
+    /// A directory of images, shown one per frame in name order, looping at the end.
+    Directory(String),
+    /// A specific device (e.g. /dev/video1).
+    Device(String),
+}
+
+impl CameraSourceType {
+    /// Works out what kind of source a path points to.
+    pub fn from_path(path: &str) -> CameraSourceType {
+        if path.starts_with("/dev/") {
+            CameraSourceType::Device(path.to_owned())
+        } else if Path::new(path).is_dir() {
+            CameraSourceType::Directory(path.to_owned())
+        } else {
+            CameraSourceType::Image(path.to_owned())
+        }
+    }
+}
+
+/// A single XRGB8888 frame, with tightly packed rows.
+#[derive(Clone)]
+pub struct CameraFrame {
+    pub buffer: Vec<u32>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl CameraFrame {
+    /// Resizes this frame with nearest neighbour sampling.
+    pub fn scale(&self, width: u32, height: u32) -> CameraFrame {
+        let mut buffer = Vec::with_capacity((width * height) as usize);
+
+        for y in 0..height {
+            let source_y = (y as u64 * self.height as u64 / height as u64) as usize;
+            let row = &self.buffer[source_y * self.width as usize..];
+
+            for x in 0..width {
+                let source_x = (x as u64 * self.width as u64 / width as u64) as usize;
+                buffer.push(row[source_x]);
+            }
+        }
+
+        CameraFrame {
+            buffer,
+            width,
+            height,
+        }
+    }
+}
+
+pub trait CameraSource {
+    /// Starts capturing frames. Returns false if the source isn't available.
+    fn start(&mut self) -> bool;
+
+    /// Stops capturing frames.
+    fn stop(&mut self);
+
+    /// Returns the next frame, or `None` if a new one isn't available yet. Called once per
+    /// frame that the core runs.
+    fn next_frame(&mut self) -> Option<CameraFrame>;
+}
+
+/// Tracks if a core has started the camera, and fetches frames for it.
+pub struct Camera {
+    source: Option<Box<CameraSource>>,
+    running: bool,
+
+    /// The dimensions the core wants frames in, or 0 if it doesn't mind.
+    width: u32,
+    height: u32,
+}
+
+impl Camera {
+    /// Starts the camera on behalf of a core.
+    pub fn start(&mut self, width: u32, height: u32) -> bool {
+        let source = match &mut self.source {
+            &mut Some(ref mut v) => v,
+            &mut None => return false,
+        };
+
+        if !self.running {
+            self.running = source.start();
+        }
+
+        self.width = width;
+        self.height = height;
+
+        self.running
+    }
+
+    /// Stops the camera on behalf of a core.
+    pub fn stop(&mut self) {
+        if let Some(ref mut source) = self.source {
+            if self.running {
+                source.stop();
+            }
+        }
+
+        self.running = false;
+    }
+
+    /// Returns the next frame for the core, if the camera is running and one is available.
+    pub fn next_frame(&mut self) -> Option<CameraFrame> {
+        if !self.running {
+            return None;
+        }
+
+        let frame = match &mut self.source {
+            &mut Some(ref mut v) => v.next_frame()?,
+            &mut None => return None,
+        };
+
+        let width = if self.width == 0 {
+            frame.width
+        } else {
+            self.width
+        };
+        let height = if self.height == 0 {
+            frame.height
+        } else {
+            self.height
+        };
+
+        if width == frame.width && height == frame.height {
+            Some(frame)
+        } else {
+            Some(frame.scale(width, height))
+        }
+    }
+
+    /// Wraps a source of camera frames, if any are available.
+    pub fn new(source: Option<Box<CameraSource>>) -> Camera {
+        Camera {
+            source,
+            running: false,
+
+            width: 0,
+            height: 0,
+        }
+    }
+}
+
+/// Builds a new source of camera frames, if one is available.
+pub fn build(source_type: &CameraSourceType) -> Option<Box<CameraSource>> {
+    match source_type {
+        &CameraSourceType::Image(ref path) => {
+            println!("Using camera image: {}", path);
+            Some(Box::new(file::ImageCamera::load(path).unwrap()))
+        }
+        &CameraSourceType::Directory(ref path) => {
+            println!("Using camera frames from: {}", path);
+            Some(Box::new(file::DirectoryCamera::load(path).unwrap()))
+        }
+        &CameraSourceType::Device(ref path) => match build_device(path) {
+            Some(v) => Some(v),
+            None => panic!("Camera device {} is not available!", path),
+        },
+        &CameraSourceType::Auto => build_device("/dev/video0"),
+    }
+}
+
+/// Attempts to use a real camera on this machine.
+#[cfg(target_os = "linux")]
+fn build_device(path: &str) -> Option<Box<CameraSource>> {
+    if !Path::new(path).exists() {
+        return None;
+    }
+
+    println!("Using camera device: {}", path);
+    Some(Box::new(v4l2::V4L2Camera::new(path)))
+}
+
+/// Attempts to use a real camera on this machine.
+#[cfg(not(target_os = "linux"))]
+fn build_device(_path: &str) -> Option<Box<CameraSource>> {
+    None
+}
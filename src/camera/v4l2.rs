@@ -0,0 +1,400 @@
+/// Captures frames from a Video4Linux2 device (e.g. a webcam) through memory mapped streaming.
+///
+/// Only YUYV frames are requested, as it is supported by practically every webcam.
+
+use libc;
+
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::mem;
+use std::ptr;
+use libc::{c_int, c_ulong, c_void};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+
+use camera::CameraFrame;
+use camera::CameraSource;
+
+const V4L2_BUF_TYPE_VIDEO_CAPTURE: u32 = 1;
+const V4L2_MEMORY_MMAP: u32 = 1;
+const V4L2_FIELD_NONE: u32 = 1;
+const V4L2_CAP_VIDEO_CAPTURE: u32 = 0x00000001;
+const V4L2_CAP_STREAMING: u32 = 0x04000000;
+const V4L2_PIX_FMT_YUYV: u32 =
+    (b'Y' as u32) | ((b'U' as u32) << 8) | ((b'Y' as u32) << 16) | ((b'V' as u32) << 24);
+
+/// The number of buffers the driver should fill while we are busy with others.
+const BUFFER_COUNT: u32 = 4;
+
+/// The frame size asked of the device, which it may adjust to something it supports.
+const DEFAULT_WIDTH: u32 = 640;
+const DEFAULT_HEIGHT: u32 = 480;
+
+#[repr(C)]
+struct V4L2Capability {
+    driver: [u8; 16],
+    card: [u8; 32],
+    bus_info: [u8; 32],
+    version: u32,
+    capabilities: u32,
+    device_caps: u32,
+    reserved: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct V4L2PixFormat {
+    width: u32,
+    height: u32,
+    pixelformat: u32,
+    field: u32,
+    bytesperline: u32,
+    sizeimage: u32,
+    colorspace: u32,
+    private: u32,
+    flags: u32,
+    ycbcr_enc: u32,
+    quantization: u32,
+    xfer_func: u32,
+}
+
+/// The format union contains pointers, so is aligned to them.
+#[repr(C)]
+struct V4L2Format {
+    buffer_type: u32,
+    #[cfg(target_pointer_width = "64")]
+    _padding: u32,
+    pix: V4L2PixFormat,
+    _raw_data: [u8; 200 - 48],
+}
+
+#[repr(C)]
+struct V4L2RequestBuffers {
+    count: u32,
+    buffer_type: u32,
+    memory: u32,
+    capabilities: u32,
+    reserved: [u32; 1],
+}
+
+#[repr(C)]
+struct V4L2Timecode {
+    timecode_type: u32,
+    flags: u32,
+    frames: u8,
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    userbits: [u8; 4],
+}
+
+#[repr(C)]
+union V4L2BufferMemory {
+    offset: u32,
+    userptr: c_ulong,
+    fd: i32,
+}
+
+#[repr(C)]
+struct V4L2Buffer {
+    index: u32,
+    buffer_type: u32,
+    bytesused: u32,
+    flags: u32,
+    field: u32,
+    timestamp: libc::timeval,
+    timecode: V4L2Timecode,
+    sequence: u32,
+    memory: u32,
+    m: V4L2BufferMemory,
+    length: u32,
+    reserved2: u32,
+    request_fd: i32,
+}
+
+/// Builds an ioctl request number, as the _IOC macro does.
+fn ioc(direction: c_ulong, number: c_ulong, size: usize) -> c_ulong {
+    (direction << 30) | ((size as c_ulong) << 16) | ((b'V' as c_ulong) << 8) | number
+}
+
+fn ior<T>(number: c_ulong) -> c_ulong {
+    ioc(2, number, mem::size_of::<T>())
+}
+
+fn iow<T>(number: c_ulong) -> c_ulong {
+    ioc(1, number, mem::size_of::<T>())
+}
+
+fn iowr<T>(number: c_ulong) -> c_ulong {
+    ioc(3, number, mem::size_of::<T>())
+}
+
+/// Performs an ioctl, retrying if it was interrupted.
+unsafe fn xioctl<T>(fd: c_int, request: c_ulong, arg: *mut T) -> io::Result<()> {
+    loop {
+        if libc::ioctl(fd, request as _, arg as *mut c_void) != -1 {
+            return Ok(());
+        }
+
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+}
+
+/// A buffer which has been mapped from the device into our memory.
+struct MappedBuffer {
+    data: *mut c_void,
+    length: usize,
+}
+
+/// An open device, which is streaming frames.
+struct Stream {
+    file: File,
+    buffers: Vec<MappedBuffer>,
+    width: u32,
+    height: u32,
+    bytes_per_line: u32,
+}
+
+impl Stream {
+    /// Opens a device, and starts it streaming.
+    fn open(path: &str) -> io::Result<Stream> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)?;
+        let fd = file.as_raw_fd();
+
+        unsafe {
+            let mut capability: V4L2Capability = mem::zeroed();
+            xioctl(fd, ior::<V4L2Capability>(0), &mut capability)?;
+
+            let required = V4L2_CAP_VIDEO_CAPTURE | V4L2_CAP_STREAMING;
+            if capability.capabilities & required != required {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "Device cannot stream video captures",
+                ));
+            }
+
+            let mut format: V4L2Format = mem::zeroed();
+            format.buffer_type = V4L2_BUF_TYPE_VIDEO_CAPTURE;
+            format.pix.width = DEFAULT_WIDTH;
+            format.pix.height = DEFAULT_HEIGHT;
+            format.pix.pixelformat = V4L2_PIX_FMT_YUYV;
+            format.pix.field = V4L2_FIELD_NONE;
+            xioctl(fd, iowr::<V4L2Format>(5), &mut format)?;
+
+            if format.pix.pixelformat != V4L2_PIX_FMT_YUYV {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "Device does not support YUYV frames",
+                ));
+            }
+
+            let mut request: V4L2RequestBuffers = mem::zeroed();
+            request.count = BUFFER_COUNT;
+            request.buffer_type = V4L2_BUF_TYPE_VIDEO_CAPTURE;
+            request.memory = V4L2_MEMORY_MMAP;
+            xioctl(fd, iowr::<V4L2RequestBuffers>(8), &mut request)?;
+
+            // Built up front, so that anything already mapped is cleaned up on failure
+            let mut stream = Stream {
+                file,
+                buffers: Vec::new(),
+                width: format.pix.width,
+                height: format.pix.height,
+                bytes_per_line: format.pix.bytesperline.max(format.pix.width * 2),
+            };
+
+            for index in 0..request.count {
+                let mut buffer: V4L2Buffer = mem::zeroed();
+                buffer.index = index;
+                buffer.buffer_type = V4L2_BUF_TYPE_VIDEO_CAPTURE;
+                buffer.memory = V4L2_MEMORY_MMAP;
+                xioctl(fd, iowr::<V4L2Buffer>(9), &mut buffer)?;
+
+                let data = libc::mmap(
+                    ptr::null_mut(),
+                    buffer.length as usize,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_SHARED,
+                    fd,
+                    buffer.m.offset as libc::off_t,
+                );
+
+                if data == libc::MAP_FAILED {
+                    return Err(io::Error::last_os_error());
+                }
+
+                stream.buffers.push(MappedBuffer {
+                    data,
+                    length: buffer.length as usize,
+                });
+
+                xioctl(fd, iowr::<V4L2Buffer>(15), &mut buffer)?;
+            }
+
+            let mut buffer_type = V4L2_BUF_TYPE_VIDEO_CAPTURE as c_int;
+            xioctl(fd, iow::<c_int>(18), &mut buffer_type)?;
+
+            Ok(stream)
+        }
+    }
+
+    /// Takes the next filled buffer from the device, if one is ready.
+    fn read_frame(&mut self) -> io::Result<Option<CameraFrame>> {
+        let fd = self.file.as_raw_fd();
+
+        unsafe {
+            let mut buffer: V4L2Buffer = mem::zeroed();
+            buffer.buffer_type = V4L2_BUF_TYPE_VIDEO_CAPTURE;
+            buffer.memory = V4L2_MEMORY_MMAP;
+
+            match xioctl(fd, iowr::<V4L2Buffer>(17), &mut buffer) {
+                Ok(_) => {}
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(e) => return Err(e),
+            }
+
+            let frame = {
+                let mapped = &self.buffers[buffer.index as usize];
+                let data = ::std::slice::from_raw_parts(mapped.data as *const u8, mapped.length);
+                convert_yuyv(data, self.width, self.height, self.bytes_per_line)
+            };
+
+            // Give the buffer back to the driver to fill again
+            xioctl(fd, iowr::<V4L2Buffer>(15), &mut buffer)?;
+
+            Ok(Some(frame))
+        }
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        unsafe {
+            let mut buffer_type = V4L2_BUF_TYPE_VIDEO_CAPTURE as c_int;
+            xioctl(self.file.as_raw_fd(), iow::<c_int>(19), &mut buffer_type).ok();
+
+            for buffer in &self.buffers {
+                libc::munmap(buffer.data, buffer.length);
+            }
+        }
+    }
+}
+
+/// Clamps a colour channel to 8 bits.
+#[inline]
+fn clamp(value: i32) -> u32 {
+    value.max(0).min(255) as u32
+}
+
+/// Converts a YUV (BT.601) pixel to XRGB8888.
+#[inline]
+fn yuv_to_xrgb(y: u8, u: u8, v: u8) -> u32 {
+    let c = y as i32 - 16;
+    let d = u as i32 - 128;
+    let e = v as i32 - 128;
+
+    let r = clamp((298 * c + 409 * e + 128) >> 8);
+    let g = clamp((298 * c - 100 * d - 208 * e + 128) >> 8);
+    let b = clamp((298 * c + 516 * d + 128) >> 8);
+
+    (r << 16) | (g << 8) | b
+}
+
+/// Converts a YUYV frame, where each pair of pixels shares chroma samples.
+fn convert_yuyv(data: &[u8], width: u32, height: u32, bytes_per_line: u32) -> CameraFrame {
+    let mut buffer = Vec::with_capacity((width * height) as usize);
+
+    for y in 0..height as usize {
+        let row = &data[y * bytes_per_line as usize..];
+
+        for x in 0..width as usize {
+            let pair = &row[(x / 2) * 4..];
+            let luma = if x % 2 == 0 { pair[0] } else { pair[2] };
+            buffer.push(yuv_to_xrgb(luma, pair[1], pair[3]));
+        }
+    }
+
+    CameraFrame {
+        buffer,
+        width,
+        height,
+    }
+}
+
+/// A camera device, which is only opened while a core is using it.
+pub struct V4L2Camera {
+    path: String,
+    stream: Option<Stream>,
+}
+
+impl CameraSource for V4L2Camera {
+    fn start(&mut self) -> bool {
+        match Stream::open(&self.path) {
+            Ok(stream) => {
+                println!(
+                    "Camera streaming at {}x{}",
+                    stream.width, stream.height
+                );
+                self.stream = Some(stream);
+                true
+            }
+            Err(e) => {
+                println!("Failed to start camera {}: {}", self.path, e);
+                false
+            }
+        }
+    }
+
+    fn stop(&mut self) {
+        self.stream = None;
+    }
+
+    fn next_frame(&mut self) -> Option<CameraFrame> {
+        let result = match &mut self.stream {
+            &mut Some(ref mut v) => v.read_frame(),
+            &mut None => return None,
+        };
+
+        match result {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Camera {} failed: {}", self.path, e);
+                self.stream = None;
+                None
+            }
+        }
+    }
+}
+
+impl V4L2Camera {
+    /// Prepares to use a device. Nothing is opened until the camera is started.
+    pub fn new(path: &str) -> V4L2Camera {
+        V4L2Camera {
+            path: path.to_owned(),
+            stream: None,
+        }
+    }
+}
+
+#[cfg(all(test, target_pointer_width = "64"))]
+mod test {
+    use std::mem::size_of;
+
+    use super::{V4L2Buffer, V4L2Capability, V4L2Format, V4L2RequestBuffers};
+
+    #[test]
+    fn struct_layouts_match_kernel() {
+        assert_eq!(size_of::<V4L2Capability>(), 104);
+        assert_eq!(size_of::<V4L2Format>(), 208);
+        assert_eq!(size_of::<V4L2RequestBuffers>(), 20);
+        assert_eq!(size_of::<V4L2Buffer>(), 88);
+    }
+}
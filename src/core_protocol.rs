@@ -18,6 +18,17 @@ use std::error::Error;
 use retro_types::RetroSystemInfo;
 use retro_types::RetroVariable;
 use retro_types::RetroAvInfo;
+use retro_types::RetroGameGeometry;
+use retro_types::RetroPixelFormat;
+use retro_types::RetroRumbleEffect;
+use retro_types::RetroSensorAction;
+use retro_types::RetroSensor;
+use retro_types::RetroPosition;
+use retro_types::RetroPerfReport;
+use retro_types::RetroKey;
+use retro_types::RetroControllerDescription;
+
+use input::snapshot::InputSnapshot;
 
 use bincode::{deserialize, serialize};
 
@@ -27,15 +38,52 @@ use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 #[derive(Deserialize, Serialize)]
 pub enum VideoRefreshType {
     /// A software refresh is where a core rasterises images on its own, and
-    /// sends it as a byte array.
+    /// sends it as a byte array in its own pixel format. Rows are `pitch` bytes apart.
     Software {
         framebuffer: Vec<u8>,
         width: u64,
         height: u64,
+        pitch: u64,
+        format: RetroPixelFormat,
     },
     /// A hardware refresh is when the image is already on the GPU (i.e a
     /// OpenGL framebuffer).
     Hardware,
+    /// A dupe is when the previous frame should be presented again.
+    Dupe,
+}
+
+/// Ways that the disk of a core can be changed.
+#[derive(Deserialize, Serialize, Debug, Copy, Clone)]
+pub enum DiskAction {
+    Eject,
+    Insert,
+    /// Selects the next disk. The tray must be open.
+    Next,
+    /// Selects the previous disk. The tray must be open.
+    Previous,
+}
+
+/// Which disk a core currently has selected.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DiskStatus {
+    pub index: u32,
+    pub count: u32,
+    pub ejected: bool,
+    /// A human readable name for the disk, if one is known.
+    pub label: Option<String>,
+}
+
+/// A key being pressed or released, or a character being typed.
+#[derive(Deserialize, Serialize, Debug, Copy, Clone)]
+pub struct KeyboardEvent {
+    pub down: bool,
+    /// The key which changed, or `RetroKey::Unknown` for typed characters.
+    pub keycode: RetroKey,
+    /// The UTF-32 character that was typed, or 0 if there isn't one.
+    pub character: u32,
+    /// A combination of the `RETROKMOD_*` bits.
+    pub modifiers: u16,
 }
 
 /// Contains data used to hold various messages.
@@ -50,15 +98,56 @@ pub enum ProtocolMessageType {
     VideoRefresh(VideoRefreshType),
     /// Core submitting >=1 audio samples.
     AudioSample(Vec<i16>),
-    /// Core requesting that input be updated on the frontend.
+    /// Core requesting that input be updated on the frontend. Blocking.
     PollInput,
-    /// Core asking for the current state of a particular input mechanism. Blocking.
-    InputState {
+    /// Core changing the dimensions/aspect ratio of its output, without changing the
+    /// rest of its A/V configuration.
+    SetGeometry(RetroGameGeometry),
+    /// Core replacing its entire A/V configuration (e.g. a new sample rate or framerate).
+    SetSystemAVInfo(RetroAvInfo),
+    /// Core setting the strength of a controller's rumble motor.
+    SetRumbleState {
         port: u32,
-        device: u32,
-        index: u32,
-        id: u32,
+        effect: RetroRumbleEffect,
+        strength: u16,
     },
+    /// Core enabling or disabling a sensor. Blocking.
+    SetSensorState {
+        port: u32,
+        action: RetroSensorAction,
+        rate: u32,
+    },
+    /// Core asking for the current value of a sensor. Blocking.
+    SensorInput { port: u32, sensor: RetroSensor },
+    /// Core starting the camera, with the dimensions it would like frames in (0 if it doesn't
+    /// mind). Blocking.
+    CameraStart { width: u32, height: u32 },
+    /// Core stopping the camera.
+    CameraStop,
+    /// Core starting location services. Blocking.
+    LocationStart,
+    /// Core stopping location services.
+    LocationStop,
+    /// Core setting how often its position should be updated, in milliseconds and meters.
+    LocationSetInterval { interval_ms: u32, interval_distance: u32 },
+    /// Core asking where the user is. Blocking.
+    GetPosition,
+    /// The performance counters of the core, sent when content is unloaded.
+    PerfReport(RetroPerfReport),
+    /// The disk that the core has selected, sent when content is loaded or disks change.
+    DiskStatus(DiskStatus),
+    /// Core requesting that the frontend shuts down.
+    Shutdown,
+    /// Core wants to be asked for audio through `AudioRequest`, rather than submitting
+    /// it while running.
+    EnableAudioCallback,
+    /// The controllers which the core supports, for each of its ports.
+    SetControllerInfo(Vec<Vec<RetroControllerDescription>>),
+    /// Core wants key presses to be sent through `KeyboardEvent` as they happen.
+    EnableKeyboardCallback,
+    /// Core requesting that its output be rotated by the specified amount of 90 degree
+    /// counter-clockwise turns.
+    SetRotation(u32),
     /// A response to a System Info query.
     SystemInfoResponse(RetroSystemInfo),
     /// A response to an API version query.
@@ -67,14 +156,27 @@ pub enum ProtocolMessageType {
     AVInfoResponse(RetroAvInfo),
     /// A response to a run query.
     RunResponse,
+    /// A response to a load query, with if the content was loaded successfully. This arrives as
+    /// an event, so that the frontend can answer the core's requests while it loads.
+    LoadResponse(bool),
 
     // Frontend -> Backend messages
     /// Informs the core to warmup.
     Init,
     /// Informs the core to shutdown.
     Deinit,
-    /// Informs the core to load something.
-    Load(String),
+    /// Informs the core to load something, or to start without content. Answered with
+    /// `LoadResponse`.
+    Load(Option<String>),
+    /// Makes a file available to the core at the specified path, without it having to exist
+    /// on the backend's disk (e.g. content for a remote backend).
+    AddVirtualFile { path: String, data: Vec<u8> },
+    /// Asks the core to change its disk.
+    DiskControl(DiskAction),
+    /// Plugs a different controller (a `RETRO_DEVICE_*` value) into a port of the core.
+    SetControllerPortDevice { port: u32, device: u32 },
+    /// A key being pressed or released. Only sent to cores using a keyboard callback.
+    KeyboardEvent(KeyboardEvent),
     /// Informs the core to unload.
     Unload,
     /// Returns the API version from the core. Blocking.
@@ -82,27 +184,59 @@ pub enum ProtocolMessageType {
     /// Returns the A/V info for this core. Blocking.
     AVInfo,
     /// Informs the core to run for a frame. Blocking.
-    Run,
+    Run {
+        /// Microseconds since the last frame was run. `None` if the core's reference frame
+        /// time should be used instead (e.g. for the first frame).
+        frame_time: Option<i64>,
+    },
     /// Informs the core to reset the application from the beginning.
     Reset,
+    /// Informs the core that more audio is needed. Only sent to cores using an audio callback.
+    AudioRequest,
+    /// Informs the core that audio has been enabled or disabled. Only sent to cores using an
+    /// audio callback.
+    AudioSetState(bool),
+    /// A new XRGB8888 frame from the camera, with tightly packed rows. Only sent while the
+    /// camera is running.
+    CameraFrame {
+        buffer: Vec<u32>,
+        width: u32,
+        height: u32,
+    },
     /// Returns the current core information. Blocking.
     SystemInfo,
-    /// A response for what the current input state is.
-    InputResponse(i16),
+    /// A response to an input poll, with the state of every device which the core's queries
+    /// are answered from until the next poll.
+    InputSnapshot(InputSnapshot),
     /// Returns a value contained within a variable.
     GetVariableResponse(Option<String>),
+    /// A response for if a sensor could be enabled/disabled.
+    SensorStateResponse(bool),
+    /// A response for what the current value of a sensor is.
+    SensorInputResponse(f32),
+    /// A response for if the camera could be started.
+    CameraStartResponse(bool),
+    /// A response for if location services could be started.
+    LocationStartResponse(bool),
+    /// A response for where the user is, if known.
+    PositionResponse(Option<RetroPosition>),
 }
 
 impl ProtocolMessageType {
     /// Returns if this message should be blocked on.
     pub fn is_blocking(&self) -> bool {
         match self {
-            &ProtocolMessageType::InputState { .. } => true,
+            &ProtocolMessageType::PollInput => true,
+            &ProtocolMessageType::SetSensorState { .. } => true,
+            &ProtocolMessageType::SensorInput { .. } => true,
+            &ProtocolMessageType::CameraStart { .. } => true,
+            &ProtocolMessageType::LocationStart => true,
+            &ProtocolMessageType::GetPosition => true,
             &ProtocolMessageType::APIVersion { .. } => true,
             &ProtocolMessageType::SystemInfo { .. } => true,
             &ProtocolMessageType::GetVariable { .. } => true,
             &ProtocolMessageType::AVInfo { .. } => true,
-            &ProtocolMessageType::Run => true,
+            &ProtocolMessageType::Run { .. } => true,
             _ => false,
         }
     }
@@ -111,7 +245,12 @@ impl ProtocolMessageType {
     /// a callback.
     pub fn is_response(&self) -> bool {
         match self {
-            &ProtocolMessageType::InputResponse(..) => true,
+            &ProtocolMessageType::InputSnapshot(..) => true,
+            &ProtocolMessageType::SensorStateResponse(..) => true,
+            &ProtocolMessageType::SensorInputResponse(..) => true,
+            &ProtocolMessageType::CameraStartResponse(..) => true,
+            &ProtocolMessageType::LocationStartResponse(..) => true,
+            &ProtocolMessageType::PositionResponse(..) => true,
             &ProtocolMessageType::SystemInfoResponse(..) => true,
             &ProtocolMessageType::APIVersionResponse(..) => true,
             &ProtocolMessageType::GetVariableResponse(..) => true,
@@ -210,6 +349,7 @@ impl ProtocolEvents {
 }
 
 /// A server (which can run on either end) which handles I/O.
+#[derive(Clone)]
 pub struct ProtocolAdapter {
     outgoing_tx: Sender<
         (
@@ -399,6 +539,9 @@ impl ProtocolAdapter {
                         };
                     }
                 }
+
+                // Nothing can respond to pending requests anymore - wake up anyone waiting
+                incoming_callbacks.lock().unwrap().clear();
             })
             .unwrap();
 
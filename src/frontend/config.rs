@@ -0,0 +1,34 @@
+//! Options which the frontend has been started with.
+
+use sensor::SensorSourceType;
+use camera::CameraSourceType;
+use location::LocationSourceType;
+use input::analog::AnalogConfig;
+use input::ports::PortDeviceType;
+
+pub struct FrontendConfig {
+    /// The core that the backend should be started with.
+    pub core: Option<String>,
+    /// The content to load, if any.
+    pub rom: Option<String>,
+    /// address:port to bind to.
+    pub address: Option<String>,
+    /// If content should be sent to the backend, rather than it reading it from disk.
+    pub send_content: bool,
+    /// If a backend should not be spawned, as it will be connecting on its own.
+    pub dont_spawn_core: bool,
+    /// Where sensor readings (e.g. tilt) should come from.
+    pub sensor: SensorSourceType,
+    /// Where camera frames should come from.
+    pub camera: CameraSourceType,
+    /// Where positions for location services should come from.
+    pub location: LocationSourceType,
+    /// How analog sticks should be adjusted.
+    pub analog: AnalogConfig,
+    /// Devices which the user has plugged into ports. Other ports are assigned automatically.
+    pub ports: Vec<(usize, PortDeviceType)>,
+    /// Controllers (by name or ID) which the core should be told are plugged into ports.
+    pub controllers: Vec<(usize, String)>,
+    /// The file that bindings are loaded from and saved to.
+    pub bindings: String,
+}
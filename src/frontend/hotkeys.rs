@@ -0,0 +1,58 @@
+//! Keys which control the frontend, rather than being passed to the core.
+
+/// Actions that hotkeys can trigger.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Hotkey {
+    /// Opens the disk tray, or closes it if it is already open.
+    ToggleEject,
+    NextDisk,
+    PreviousDisk,
+    /// Sends all keys to the core, disabling every other hotkey until it is pressed again.
+    ToggleGameFocus,
+    /// Plugs the next controller that the core supports into the selected port.
+    NextController,
+    /// Selects which port `NextController` changes.
+    NextControllerPort,
+    /// Asks for the key of each RetroPad button in turn, saving them to the bindings.
+    BindKeys,
+}
+
+static ALL_HOTKEYS: [Hotkey; 7] = [
+    Hotkey::ToggleEject,
+    Hotkey::NextDisk,
+    Hotkey::PreviousDisk,
+    Hotkey::ToggleGameFocus,
+    Hotkey::NextController,
+    Hotkey::NextControllerPort,
+    Hotkey::BindKeys,
+];
+
+/// Tracks which hotkeys are held, so that each press only triggers once.
+pub struct Hotkeys {
+    held: Vec<Hotkey>,
+}
+
+impl Hotkeys {
+    /// Returns the hotkeys which have been pressed since the last poll.
+    pub fn poll(&mut self, is_key_down: &Fn(&Hotkey) -> bool) -> Vec<Hotkey> {
+        let mut pressed = Vec::new();
+
+        for hotkey in ALL_HOTKEYS.iter() {
+            let down = is_key_down(hotkey);
+            let was_down = self.held.contains(hotkey);
+
+            if down && !was_down {
+                self.held.push(*hotkey);
+                pressed.push(*hotkey);
+            } else if !down && was_down {
+                self.held.retain(|x| x != hotkey);
+            }
+        }
+
+        pressed
+    }
+
+    pub fn new() -> Hotkeys {
+        Hotkeys { held: Vec::new() }
+    }
+}
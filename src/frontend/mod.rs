@@ -3,5 +3,7 @@
 
 pub mod state;
 pub mod protocol;
+pub mod config;
+pub mod hotkeys;
 
 pub use self::protocol::run;
@@ -1,12 +1,21 @@
 //! The main event handler of the frontend.
 
 use core_protocol::ProtocolAdapter;
+use core_protocol::ProtocolEvents;
 use core_protocol::ProtocolMessageType;
 
 use frontend::state::FrontendState;
+use backend::disk;
+use frontend::config::FrontendConfig;
 
 use std::env::current_exe;
 
+use std::fs::File;
+use std::path::Path;
+use std::path::PathBuf;
+use std::io::BufReader;
+use std::io::Read;
+
 use std::net::TcpListener;
 
 use std::process::Command;
@@ -15,17 +24,167 @@ use std::process::Stdio;
 use std::thread;
 use graphics;
 use audio;
+use input;
+use sensor;
+use sensor::Sensors;
+use camera;
+use camera::Camera;
+use location;
+use location::Location;
 use core_protocol::VideoRefreshType;
-use input::InputKey;
+use core_protocol::DiskAction;
+use frontend::hotkeys::Hotkey;
+use input::aggregator::InputAggregator;
+use input::bindings::Bindings;
+use input::controllers::Controllers;
+use input::ports::PortDeviceType;
+use input::ports::Ports;
+use input::snapshot::InputSnapshot;
 use std::time::Duration;
+use std::time::Instant;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use retro_types::RetroGameGeometry;
+use retro_types::RetroPixelFormat;
+
+/// State shared between the main thread and the frontend ticker.
+struct TickerState {
+    /// Returns true once the audio backend is ready for another frame.
+    audio_done: Box<Fn() -> bool + Send>,
+    /// If the core provides audio through a callback, rather than while running.
+    audio_callback: bool,
+    fps: f64,
+    /// If frames are run as the user plays them, rather than replaying input. Cores are only
+    /// told how long frames took in real time, so that replays always run the same way.
+    real_time: bool,
+}
+
+/// A message from the backend, with a handler to reply to it.
+type Event = (ProtocolMessageType, Box<Fn(ProtocolMessageType)>);
+
+/// Answers requests from the backend which it blocks on, and which only need the core's
+/// environment (variables, sensors, the camera and location services). Returns any other event.
+fn answer_request(frontend: &mut FrontendState, event: Event) -> Option<Event> {
+    let (event, callback) = event;
+
+    match event {
+        ProtocolMessageType::GetVariable(_) => {
+            callback(ProtocolMessageType::GetVariableResponse(None))
+        }
+        ProtocolMessageType::SetSensorState { port, action, rate } => {
+            let result = match &mut frontend.sensors {
+                &mut Some(ref mut v) => v.set_sensor_state(port, action, rate),
+                &mut None => panic!("No sensors available!"),
+            };
+
+            callback(ProtocolMessageType::SensorStateResponse(result));
+        }
+        ProtocolMessageType::SensorInput { port, sensor } => {
+            let result = match &frontend.sensors {
+                &Some(ref v) => v.get_sensor_input(port, sensor),
+                &None => panic!("No sensors available!"),
+            };
+
+            callback(ProtocolMessageType::SensorInputResponse(result));
+        }
+        ProtocolMessageType::CameraStart { width, height } => {
+            let result = match &mut frontend.camera {
+                &mut Some(ref mut v) => v.start(width, height),
+                &mut None => panic!("No camera available!"),
+            };
+
+            callback(ProtocolMessageType::CameraStartResponse(result));
+        }
+        ProtocolMessageType::LocationStart => {
+            let result = match &mut frontend.location {
+                &mut Some(ref mut v) => v.start(),
+                &mut None => panic!("No location services available!"),
+            };
+
+            callback(ProtocolMessageType::LocationStartResponse(result));
+        }
+        ProtocolMessageType::GetPosition => {
+            let result = match &frontend.location {
+                &Some(ref v) => v.get_position(),
+                &None => panic!("No location services available!"),
+            };
+
+            callback(ProtocolMessageType::PositionResponse(result));
+        }
+        event => return Some((event, callback)),
+    }
+
+    None
+}
+
+/// Waits for the backend to respond to `Load`, answering the requests that the core blocks on
+/// meanwhile (most read their variables while loading). Other events are kept in `pending`,
+/// as they can only be handled once the frontend has started. Returns if content was loaded,
+/// or None if the backend disconnected.
+fn wait_for_load(
+    frontend: &mut FrontendState,
+    events: &ProtocolEvents,
+    pending: &mut Vec<Event>,
+) -> Option<bool> {
+    loop {
+        let (event, callback) = match answer_request(frontend, events.poll()?) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        match event {
+            ProtocolMessageType::LoadResponse(loaded) => return Some(loaded),
+            // Nothing can be pressed before the window exists
+            ProtocolMessageType::PollInput => {
+                callback(ProtocolMessageType::InputSnapshot(InputSnapshot::default()))
+            }
+            event => pending.push((event, callback)),
+        }
+    }
+}
+
+/// Builds a callback for the audio backend to ask the core for more audio.
+fn build_audio_request(protocol: &ProtocolAdapter) -> Box<Fn() + Send> {
+    let protocol = protocol.clone();
+    Box::new(move || {
+        protocol.send(ProtocolMessageType::AudioRequest);
+    })
+}
+
+/// Picks a sane window size for the specified geometry.
+fn select_display_size(geometry: &RetroGameGeometry) -> (u32, u32) {
+    let mut display_width = geometry.base_width;
+    let mut display_height = geometry.base_height;
+    let mut scale = 1;
+    // TODO: Don't hardcode this
+    while scale < 4 {
+        scale += 1;
+        let new_display_width = geometry.base_width * scale;
+        let new_display_height = geometry.base_height * scale;
+
+        // TODO: Don't hardcode this
+        if new_display_width > 1280 || new_display_height > 1024 {
+            break;
+        }
+
+        display_width = new_display_width;
+        display_height = new_display_height;
+    }
+
+    println!(
+        "Selected resolution {}x{} at {}x.",
+        display_width, display_height, scale
+    );
+
+    (display_width, display_height)
+}
 
 /// Starts listening for messages over a socket. Binds to the port as a server.
-pub fn run(core: Option<String>, rom: String, address: Option<String>, dont_spawn_core: bool) {
+pub fn run(config: FrontendConfig) {
     // Bind to our target port
-    let server = match address {
+    let server = match config.address {
         Some(v) => TcpListener::bind(v).unwrap(),
         None => TcpListener::bind("127.0.0.1:0").unwrap(),
     };
@@ -33,21 +192,24 @@ pub fn run(core: Option<String>, rom: String, address: Option<String>, dont_spaw
     let port = server.local_addr().unwrap().port();
 
     // Start up a client
-    if !dont_spawn_core {
+    let process = if !config.dont_spawn_core {
         let exe_path = current_exe().unwrap();
-        let _process = Command::new(exe_path)
+        let process = Command::new(exe_path)
             .arg("--type")
             .arg("backend")
             .arg("--address")
             .arg(&format!("127.0.0.1:{}", port))
             .arg("--core")
-            .arg(&core.unwrap())
+            .arg(&config.core.unwrap())
             .stdin(Stdio::null())
             .stdout(Stdio::inherit())
             .stderr(Stdio::inherit())
             .spawn()
             .expect("Unable to launch core process");
-    }
+        Some(process)
+    } else {
+        None
+    };
 
     // Wait for this client to come online
     println!("Waiting for client...");
@@ -73,54 +235,123 @@ pub fn run(core: Option<String>, rom: String, address: Option<String>, dont_spaw
     println!("Loaded core: {:?}", data.library_name);
     frontend.info = Some(data);
 
-    let av_info = match protocol.send(ProtocolMessageType::AVInfo).unwrap().unwrap() {
-        ProtocolMessageType::AVInfoResponse(info) => info,
-        _ => panic!("Unknown A/V info"),
-    };
-
     protocol.send(ProtocolMessageType::Init);
-    protocol.send(ProtocolMessageType::Load(rom));
 
-    // Scale the window to a sane size
-    let mut display_width = av_info.geometry.base_width;
-    let mut display_height = av_info.geometry.base_height;
-    let mut scale = 1;
-    // TODO: Don't hardcode this
-    while scale < 4 {
-        scale += 1;
-        let new_display_width = av_info.geometry.base_width * scale;
-        let new_display_height = av_info.geometry.base_height * scale;
+    // The backend may be on another machine, which can't see our content
+    if config.send_content {
+        if let Some(ref rom) = config.rom {
+            let mut files = vec![PathBuf::from(rom)];
+
+            // Playlists need the disks they list as well
+            if disk::is_playlist(Path::new(rom)) {
+                let disks = File::open(rom)
+                    .and_then(|x| disk::parse_playlist(Path::new(rom), BufReader::new(x)));
+                match disks {
+                    Ok(disks) => files.extend(disks),
+                    Err(e) => println!("Failed to read playlist {}: {}", rom, e),
+                }
+            }
 
-        // TODO: Don't hardcode this
-        if new_display_width > 1280 || new_display_height > 1024 {
-            break;
+            for file in files {
+                let mut data = Vec::new();
+                File::open(&file).unwrap().read_to_end(&mut data).unwrap();
+                protocol.send(ProtocolMessageType::AddVirtualFile {
+                    path: file.to_string_lossy().into_owned(),
+                    data,
+                });
+            }
         }
+    }
 
-        display_width = new_display_width;
-        display_height = new_display_height;
+    // Games are known by their file name, for their own bindings
+    let game = config
+        .rom
+        .as_ref()
+        .and_then(|x| Path::new(x).file_stem())
+        .and_then(|x| x.to_str())
+        .map(|x| x.to_owned());
+
+    // Cores can ask for these while loading
+    frontend.sensors = Some(Sensors::new(sensor::build(&config.sensor)));
+    frontend.camera = Some(Camera::new(camera::build(&config.camera)));
+    frontend.location = Some(Location::new(location::build(&config.location)));
+
+    // No content is only valid if the core has told the backend it supports it
+    protocol.send(ProtocolMessageType::Load(config.rom));
+
+    let mut pending = Vec::new();
+    let loaded = match wait_for_load(&mut frontend, &events, &mut pending) {
+        Some(v) => v,
+        None => panic!("Backend disconnected while loading!"),
+    };
+
+    if !loaded {
+        println!("Core failed to load content!");
+        protocol.send(ProtocolMessageType::Deinit);
+
+        if let Some(mut process) = process {
+            process.wait().unwrap();
+        }
+        return;
     }
 
-    println!(
-        "Selected resolution {}x{} at {}x.",
-        display_width, display_height, scale
-    );
+    // A/V info is only valid once content has been loaded
+    let av_info = match protocol.send(ProtocolMessageType::AVInfo).unwrap().unwrap() {
+        ProtocolMessageType::AVInfoResponse(info) => info,
+        _ => panic!("Unknown A/V info"),
+    };
+
+    // Scale the window to a sane size
+    let (display_width, display_height) = select_display_size(&av_info.geometry);
 
     // Finish up our frontend
     let mut renderer = graphics::build(display_width, display_height, false, false).unwrap();
+    renderer.set_geometry(&av_info.geometry);
 
-    match &frontend.info {
-        &Some(ref v) => renderer.set_title(format!(
-            "OxRetro - {} ({})",
-            v.library_name, v.library_version
-        )),
-        _ => panic!("Missing frontend info?"),
-    }
+    renderer.set_title(frontend.get_title());
 
     frontend.renderer = Some(renderer);
+    let bindings = {
+        let core = &frontend.info.as_ref().unwrap().library_name;
+        match Bindings::load(&config.bindings, core, game.as_ref().map(|x| x.as_str())) {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Failed to load bindings from {}: {}", config.bindings, e);
+                Bindings::default()
+            }
+        }
+    };
 
-    let audio = audio::build(av_info.timing.sample_rate as u32).unwrap();
-    let audio_size_callback = audio.get_done_callback();
+    // Scripted pads replay input, which should play out the same regardless of timing
+    let replaying = config.ports.iter().any(|x| match x.1 {
+        PortDeviceType::Script(_) => true,
+        _ => false,
+    });
+
+    frontend.input = InputAggregator::new(
+        input::build(),
+        Ports::new(&config.ports),
+        config.analog,
+        bindings,
+    );
+    frontend.bindings_path = config.bindings;
+    frontend.controllers = Controllers::new(config.controllers);
+
+    let audio = audio::build(av_info.timing.sample_rate as u32, av_info.timing.fps).unwrap();
+    // The audio backend can be rebuilt by the core at runtime, so this is shared with the ticker
+    let ticker_state = Arc::new(Mutex::new(TickerState {
+        audio_done: audio.get_done_callback(),
+        audio_callback: false,
+        fps: av_info.timing.fps,
+        real_time: !replaying,
+    }));
     frontend.audio = Some(audio);
+    frontend.av_info = Some(av_info);
+
+    let thread_ticker_state = ticker_state.clone();
+
+    // The ticker takes ownership of the main adapter, but we still need to talk to the backend
+    let main_protocol = protocol.clone();
 
     // Signals to the frontend ticker that we should shutdown
     let shutdown_signal = Arc::new(AtomicBool::new(false));
@@ -128,76 +359,127 @@ pub fn run(core: Option<String>, rom: String, address: Option<String>, dont_spaw
     let thread_signal = shutdown_signal.clone();
 
     // Create a thread for managing events
-    thread::Builder::new()
+    let ticker = thread::Builder::new()
         .name("frontend-ticker".to_owned())
         .spawn(move || {
+            let mut last_frame: Option<Instant> = None;
+
             loop {
-                match protocol.send(ProtocolMessageType::Run).unwrap().try_poll() {
+                let real_time = thread_ticker_state.lock().unwrap().real_time;
+
+                let now = Instant::now();
+                let frame_time = match last_frame {
+                    Some(last_frame) if real_time => {
+                        let elapsed = now.duration_since(last_frame);
+                        let micros = elapsed.as_secs() * 1_000_000 + elapsed.subsec_micros() as u64;
+                        Some(micros as i64)
+                    }
+                    _ => None,
+                };
+                last_frame = Some(now);
+
+                match protocol
+                    .send(ProtocolMessageType::Run { frame_time })
+                    .unwrap()
+                    .try_poll()
+                {
                     // Main thread has been destroyed
                     None => break,
                     _ => {}
                 };
 
-                // TODO: busy loop
-                while !audio_size_callback() {
-                    thread::sleep(Duration::from_millis(1));
+                let (audio_callback, fps) = {
+                    let state = thread_ticker_state.lock().unwrap();
+                    (state.audio_callback, state.fps)
+                };
+
+                if audio_callback {
+                    // Audio is pulled separately, so pace ourselves against the core's framerate
+                    let frame_duration = Duration::from_micros((1_000_000.0 / fps) as u64);
+                    let elapsed = now.elapsed();
+                    if elapsed < frame_duration {
+                        thread::sleep(frame_duration - elapsed);
+                    }
+                } else {
+                    // TODO: busy loop
+                    while !(thread_ticker_state.lock().unwrap().audio_done)() {
+                        thread::sleep(Duration::from_millis(1));
+                    }
                 }
 
                 if thread_signal.load(Ordering::Relaxed) {
-                    protocol.send(ProtocolMessageType::Unload).unwrap();
-                    protocol.send(ProtocolMessageType::Deinit).unwrap();
+                    if audio_callback {
+                        protocol.send(ProtocolMessageType::AudioSetState(false));
+                    }
+
+                    // The backend exits once it has been deinitialised
+                    protocol.send(ProtocolMessageType::Unload);
+                    protocol.send(ProtocolMessageType::Deinit);
                     break;
                 }
             }
         })
         .unwrap();
 
-    // Start up our main loop - we no longer need to talk to the frontend
+    // Start up our main loop - we no longer need to talk to the frontend. Events from while
+    // content was loading come first.
+    let mut pending = pending.into_iter();
     loop {
-        let (event, callback) = match events.poll() {
+        let event = match pending.next().or_else(|| events.poll()) {
             Some(v) => v,
             None => break,
         };
 
+        let (event, callback) = match answer_request(&mut frontend, event) {
+            Some(v) => v,
+            None => continue,
+        };
+
         match event {
-            ProtocolMessageType::GetVariable(name) => {
-                callback(ProtocolMessageType::GetVariableResponse(None))
-            }
-            ProtocolMessageType::PollInput => frontend.poll_input(),
-            ProtocolMessageType::InputState { id, .. } => {
-                let key = match id {
-                    0 => InputKey::B,
-                    1 => InputKey::Y,
-                    2 => InputKey::Select,
-                    3 => InputKey::Start,
-                    4 => InputKey::Up,
-                    5 => InputKey::Down,
-                    6 => InputKey::Left,
-                    7 => InputKey::Right,
-                    8 => InputKey::A,
-                    9 => InputKey::X,
-                    10 => InputKey::L,
-                    11 => InputKey::R,
-                    12 => InputKey::L2,
-                    13 => InputKey::R2,
-                    14 => InputKey::L3,
-                    15 => InputKey::R3,
-                    _ => panic!("Unknown input ID: {}", id),
-                };
+            ProtocolMessageType::PollInput => {
+                frontend.poll_input();
 
-                let result: i16;
-                match &mut frontend.renderer {
-                    &mut Some(ref mut v) => {
-                        if v.is_key_down(&key) {
-                            result = 1;
-                        } else {
-                            result = 0;
+                for keyboard_event in frontend.take_keyboard_events() {
+                    main_protocol.send(ProtocolMessageType::KeyboardEvent(keyboard_event));
+                }
+
+                for hotkey in frontend.poll_hotkeys() {
+                    let action = match hotkey {
+                        Hotkey::ToggleGameFocus => {
+                            let game_focus = !frontend.input.is_game_focused();
+                            frontend.set_game_focus(game_focus);
+                            continue;
                         }
-                    }
-                    &mut None => panic!("No renderer available!"),
+                        Hotkey::NextController => {
+                            if let Some((port, device)) = frontend.controllers.cycle() {
+                                main_protocol.send(ProtocolMessageType::SetControllerPortDevice {
+                                    port,
+                                    device,
+                                });
+                            }
+                            continue;
+                        }
+                        Hotkey::NextControllerPort => {
+                            frontend.controllers.next_port();
+                            continue;
+                        }
+                        Hotkey::BindKeys => {
+                            frontend.input.start_bind_wizard();
+                            continue;
+                        }
+                        Hotkey::ToggleEject => match &frontend.disk {
+                            &Some(ref v) if v.ejected => DiskAction::Insert,
+                            _ => DiskAction::Eject,
+                        },
+                        Hotkey::NextDisk => DiskAction::Next,
+                        Hotkey::PreviousDisk => DiskAction::Previous,
+                    };
+
+                    main_protocol.send(ProtocolMessageType::DiskControl(action));
                 }
 
-                callback(ProtocolMessageType::InputResponse(result));
+                let snapshot = frontend.input.get_snapshot().clone();
+                callback(ProtocolMessageType::InputSnapshot(snapshot));
             }
             ProtocolMessageType::VideoRefresh(refresh) => {
                 match &mut frontend.renderer {
@@ -206,18 +488,66 @@ pub fn run(core: Option<String>, rom: String, address: Option<String>, dont_spaw
                             framebuffer,
                             width,
                             height,
+                            pitch,
+                            format,
                         } => {
-                            v.submit_frame(&framebuffer, width as usize, height as usize);
+                            let width = width as usize;
+                            let height = height as usize;
+                            let pitch = pitch as usize;
+
+                            if v.supports_pixel_format(format) {
+                                v.submit_frame(&framebuffer, width, height, pitch, format);
+                            } else {
+                                let framebuffer = graphics::conversions::convert(
+                                    format,
+                                    &framebuffer,
+                                    width,
+                                    height,
+                                    pitch,
+                                );
+                                v.submit_frame(
+                                    &framebuffer,
+                                    width,
+                                    height,
+                                    width * 4,
+                                    RetroPixelFormat::FormatXRGB8888,
+                                );
+                            }
                         }
                         VideoRefreshType::Hardware => {
                             panic!("Hardware accelerated cores not supported!")
                         }
+                        VideoRefreshType::Dupe => v.redraw(),
                     },
                     &mut None => panic!("No renderer available!"),
                 }
 
+                // Cameras are polled once per frame that the core runs
+                let frame = match &mut frontend.camera {
+                    &mut Some(ref mut v) => v.next_frame(),
+                    &mut None => panic!("No camera available!"),
+                };
+                if let Some(frame) = frame {
+                    main_protocol.send(ProtocolMessageType::CameraFrame {
+                        buffer: frame.buffer,
+                        width: frame.width,
+                        height: frame.height,
+                    });
+                }
+
+                // Location services only see time passing while the core runs
+                let fps = match &frontend.av_info {
+                    &Some(ref v) => v.timing.fps,
+                    &None => panic!("No A/V info available!"),
+                };
+                match &mut frontend.location {
+                    &mut Some(ref mut v) => v.advance(1.0 / fps),
+                    &mut None => panic!("No location services available!"),
+                }
+
+                // Keep handling events until the backend has shut down
                 if !frontend.is_alive() {
-                    break;
+                    shutdown_signal.store(true, Ordering::Relaxed);
                 }
             }
             ProtocolMessageType::AudioSample(samples) => match &mut frontend.audio {
@@ -226,11 +556,195 @@ pub fn run(core: Option<String>, rom: String, address: Option<String>, dont_spaw
                 }
                 &mut None => panic!("No audio core available!"),
             },
+            ProtocolMessageType::SetGeometry(geometry) => match &mut frontend.renderer {
+                &mut Some(ref mut v) => v.set_geometry(&geometry),
+                &mut None => panic!("No renderer available!"),
+            },
+            ProtocolMessageType::SetRumbleState {
+                port,
+                effect,
+                strength,
+            } => frontend.input.set_rumble_state(port, effect, strength),
+            ProtocolMessageType::CameraStop => match &mut frontend.camera {
+                &mut Some(ref mut v) => v.stop(),
+                &mut None => panic!("No camera available!"),
+            },
+            ProtocolMessageType::LocationStop => match &mut frontend.location {
+                &mut Some(ref mut v) => v.stop(),
+                &mut None => panic!("No location services available!"),
+            },
+            ProtocolMessageType::LocationSetInterval {
+                interval_ms,
+                interval_distance,
+            } => match &mut frontend.location {
+                &mut Some(ref mut v) => v.set_interval(interval_ms, interval_distance),
+                &mut None => panic!("No location services available!"),
+            },
+            ProtocolMessageType::SetControllerInfo(ports) => {
+                for (port, controllers) in ports.iter().enumerate() {
+                    let names = controllers.iter().map(|x| &x.name).collect::<Vec<_>>();
+                    println!("Port {} supports: {:?}", port, names);
+                }
+
+                for (port, device) in frontend.controllers.set_info(ports) {
+                    main_protocol.send(ProtocolMessageType::SetControllerPortDevice {
+                        port,
+                        device,
+                    });
+                }
+            }
+            ProtocolMessageType::EnableKeyboardCallback => {
+                frontend.keyboard_callback = true;
+
+                // Cores which want typing are likely to use keys that clash with hotkeys
+                if !frontend.input.is_game_focused() {
+                    frontend.set_game_focus(true);
+                }
+            }
+            ProtocolMessageType::EnableAudioCallback => {
+                match &mut frontend.audio {
+                    &mut Some(ref mut v) => {
+                        v.set_request_callback(build_audio_request(&main_protocol))
+                    }
+                    &mut None => panic!("No audio core available!"),
+                }
+                ticker_state.lock().unwrap().audio_callback = true;
+
+                // The audio thread only asks once it starts running low, so prime it now
+                main_protocol.send(ProtocolMessageType::AudioSetState(true));
+                main_protocol.send(ProtocolMessageType::AudioRequest);
+            }
+            ProtocolMessageType::PerfReport(report) => {
+                println!("Core performance counters:");
+                print!("{}", report);
+            }
+            ProtocolMessageType::DiskStatus(status) => {
+                println!("Disk changed: {:?}", status);
+                frontend.disk = Some(status);
+
+                let title = frontend.get_title();
+                match &mut frontend.renderer {
+                    &mut Some(ref mut v) => v.set_title(title),
+                    &mut None => panic!("No renderer available!"),
+                }
+            }
+            ProtocolMessageType::Shutdown => {
+                println!("Core requested shutdown.");
+                shutdown_signal.store(true, Ordering::Relaxed);
+            }
+            ProtocolMessageType::SetRotation(rotation) => match &mut frontend.renderer {
+                &mut Some(ref mut v) => v.set_rotation(rotation),
+                &mut None => panic!("No renderer available!"),
+            },
+            ProtocolMessageType::SetSystemAVInfo(av_info) => {
+                println!("Core changed A/V info: {:?}", av_info);
+
+                let (display_width, display_height) = select_display_size(&av_info.geometry);
+                match &mut frontend.renderer {
+                    &mut Some(ref mut v) => {
+                        v.set_window_size(display_width, display_height);
+                        v.set_geometry(&av_info.geometry);
+                    }
+                    &mut None => panic!("No renderer available!"),
+                }
+
+                // Rebuild audio at the new sample rate, and pace frames against the new fps
+                let mut audio =
+                    audio::build(av_info.timing.sample_rate as u32, av_info.timing.fps).unwrap();
+
+                let mut state = ticker_state.lock().unwrap();
+                if state.audio_callback {
+                    audio.set_request_callback(build_audio_request(&main_protocol));
+                    main_protocol.send(ProtocolMessageType::AudioRequest);
+                }
+                state.audio_done = audio.get_done_callback();
+                state.fps = av_info.timing.fps;
+
+                frontend.audio = Some(audio);
+                frontend.av_info = Some(av_info);
+            }
             _ => {
                 //println!("Ignoring!")
             }
         }
     }
 
+    // The backend has disconnected - make sure everything else has finished up
     shutdown_signal.store(true, Ordering::Relaxed);
+    ticker.join().unwrap();
+
+    if let Some(mut process) = process {
+        process.wait().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::wait_for_load;
+
+    use core_protocol::ProtocolAdapter;
+    use core_protocol::ProtocolMessageType;
+
+    use frontend::state::FrontendState;
+
+    use std::net::TcpListener;
+    use std::net::TcpStream;
+    use std::thread;
+
+    #[test]
+    fn requests_while_loading() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let backend_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (frontend_stream, _) = listener.accept().unwrap();
+
+        let (protocol, events) = ProtocolAdapter::new(
+            "frontend".to_owned(),
+            Box::new(frontend_stream.try_clone().unwrap()),
+            Box::new(frontend_stream),
+        );
+
+        // Reads a variable while loading, as most cores do
+        let backend = thread::spawn(move || {
+            let (backend_protocol, backend_events) = ProtocolAdapter::new(
+                "backend".to_owned(),
+                Box::new(backend_stream.try_clone().unwrap()),
+                Box::new(backend_stream),
+            );
+
+            let (event, callback) = backend_events.poll().unwrap();
+            match event {
+                ProtocolMessageType::Load(None) => {}
+                _ => panic!("Expected a load"),
+            }
+
+            backend_protocol.send(ProtocolMessageType::SetRotation(1));
+            let variable = backend_protocol
+                .send(ProtocolMessageType::GetVariable("region".to_owned()))
+                .unwrap()
+                .unwrap();
+            callback(ProtocolMessageType::LoadResponse(true));
+
+            match variable {
+                ProtocolMessageType::GetVariableResponse(None) => {}
+                _ => panic!("Expected a variable"),
+            }
+        });
+
+        let mut frontend = FrontendState::new(None, None, None);
+        protocol.send(ProtocolMessageType::Load(None));
+
+        let mut pending = Vec::new();
+        assert_eq!(
+            wait_for_load(&mut frontend, &events, &mut pending),
+            Some(true)
+        );
+        backend.join().unwrap();
+
+        // Events which need the frontend to have started are kept for later
+        assert_eq!(pending.len(), 1);
+        match pending[0].0 {
+            ProtocolMessageType::SetRotation(1) => {}
+            _ => panic!("Expected the rotation to be pending"),
+        }
+    }
 }
@@ -2,25 +2,141 @@
 
 use graphics::Renderer;
 use audio::AudioBackend;
-use retro_types::{RetroSystemInfo, RetroVariable};
+use input::aggregator::InputAggregator;
+use input::analog::AnalogConfig;
+use input::bindings;
+use input::bindings::Bindings;
+use input::controllers::Controllers;
+use input::ports::Ports;
+use sensor::Sensors;
+use camera::Camera;
+use location::Location;
+use frontend::hotkeys::Hotkey;
+use frontend::hotkeys::Hotkeys;
+use core_protocol::DiskStatus;
+use core_protocol::KeyboardEvent;
+use retro_types::{RetroAvInfo, RetroSystemInfo, RetroVariable};
 
 // Static callbacks
 pub struct FrontendState {
     pub renderer: Option<Box<Renderer>>,
     pub audio: Option<Box<AudioBackend>>,
+    /// Merges every input device into the ports of the core.
+    pub input: InputAggregator,
+    /// The file that bindings are loaded from and saved to.
+    pub bindings_path: String,
+    pub sensors: Option<Sensors>,
+    pub camera: Option<Camera>,
+    pub location: Option<Location>,
     pub info: Option<RetroSystemInfo>,
+    pub av_info: Option<RetroAvInfo>,
+
+    /// Which of the core's supported controllers are plugged into its ports.
+    pub controllers: Controllers,
+
+    pub hotkeys: Hotkeys,
+    /// If the core wants to be told about key presses as they happen.
+    pub keyboard_callback: bool,
+    /// The disk the core has selected, if it has multiple.
+    pub disk: Option<DiskStatus>,
 
     pub variables: Vec<RetroVariable>,
     pub variables_dirty: bool,
 }
 
 impl FrontendState {
-    /// Polls the input backend for available input.
+    /// Polls the window and input devices, taking a snapshot of the input which the core's
+    /// queries are answered from.
     pub fn poll_input(&mut self) {
-        match &mut self.renderer {
+        let events = match &mut self.renderer {
             &mut Some(ref mut v) => v.poll_events(),
             &mut None => panic!("No renderer when input callback was called!"),
         };
+
+        self.input.poll(events);
+
+        if let Some((device, binds)) = self.input.take_new_bindings() {
+            match bindings::save(&self.bindings_path, &device, &binds) {
+                Ok(_) => println!("Saved bindings to {}", self.bindings_path),
+                Err(e) => println!("Failed to save bindings to {}: {}", self.bindings_path, e),
+            }
+        }
+
+        if let Some(ref mut sensors) = self.sensors {
+            let input = &self.input;
+            sensors.poll(&|key| input.is_sensor_key_down(key));
+        }
+    }
+
+    /// Returns the hotkeys which have been pressed since input was last polled.
+    pub fn poll_hotkeys(&mut self) -> Vec<Hotkey> {
+        let input = &self.input;
+        let pressed = self.hotkeys.poll(&|key| input.is_hotkey_down(key));
+
+        // Every key belongs to the bind wizard while it is running
+        if input.is_binding() {
+            Vec::new()
+        } else if input.is_game_focused() {
+            pressed
+                .into_iter()
+                .filter(|x| *x == Hotkey::ToggleGameFocus)
+                .collect()
+        } else {
+            pressed
+        }
+    }
+
+    /// Returns the keyboard events which should be sent to the core since input was last
+    /// polled.
+    pub fn take_keyboard_events(&mut self) -> Vec<KeyboardEvent> {
+        let events = self.input.take_keyboard_events();
+
+        if self.keyboard_callback {
+            events
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Enables or disables game focus, updating the window title to match.
+    pub fn set_game_focus(&mut self, game_focus: bool) {
+        self.input.set_game_focus(game_focus);
+
+        if game_focus {
+            println!("Game focus enabled, hotkeys are disabled until Scroll Lock is pressed");
+        } else {
+            println!("Game focus disabled");
+        }
+
+        let title = self.get_title();
+        match &mut self.renderer {
+            &mut Some(ref mut v) => v.set_title(title),
+            &mut None => panic!("No renderer available!"),
+        }
+    }
+
+    /// Returns the title that the window should have.
+    pub fn get_title(&self) -> String {
+        let mut title = match &self.info {
+            &Some(ref v) => format!("OxRetro - {} ({})", v.library_name, v.library_version),
+            &None => panic!("Missing frontend info?"),
+        };
+
+        if let Some(ref disk) = self.disk {
+            title += &format!(" - Disk {}/{}", disk.index + 1, disk.count);
+            if let Some(ref label) = disk.label {
+                title += &format!(": {}", label);
+            }
+            if disk.ejected {
+                title += " (ejected)";
+            }
+        }
+
+        if self.input.is_game_focused() {
+            title += " (game focus)";
+        }
+
+        title
     }
 
     /// Checks to see if all the components are alive.
@@ -42,7 +158,22 @@ impl FrontendState {
         FrontendState {
             renderer,
             audio,
+            input: InputAggregator::new(
+                None,
+                Ports::new(&[]),
+                AnalogConfig::default(),
+                Bindings::default(),
+            ),
+            bindings_path: String::new(),
+            sensors: None,
+            camera: None,
+            location: None,
             info,
+            av_info: None,
+            controllers: Controllers::new(Vec::new()),
+            hotkeys: Hotkeys::new(),
+            keyboard_callback: false,
+            disk: None,
             variables: Vec::new(),
             variables_dirty: true,
         }
@@ -0,0 +1,146 @@
+/// Converts framebuffers from the native formats of cores into XRGB8888, for renderers which
+/// are unable to upload them directly.
+
+use byteorder::{ByteOrder, NativeEndian};
+
+use retro_types::RetroPixelFormat;
+
+/// Expands a 5 bit colour channel to 8 bits.
+static EXPAND_5_BITS: [u8; 32] = [
+    0, 8, 16, 25, 33, 41, 49, 58, 66, 74, 82, 90, 99, 107, 115, 123, 132, 140, 148, 156, 165, 173,
+    181, 189, 197, 206, 214, 222, 230, 239, 247, 255,
+];
+
+/// Expands a 6 bit colour channel to 8 bits.
+static EXPAND_6_BITS: [u8; 64] = [
+    0, 4, 8, 12, 16, 20, 24, 28, 32, 36, 40, 45, 49, 53, 57, 61, 65, 69, 73, 77, 81, 85, 89, 93,
+    97, 101, 105, 109, 113, 117, 121, 125, 130, 134, 138, 142, 146, 150, 154, 158, 162, 166, 170,
+    174, 178, 182, 186, 190, 194, 198, 202, 206, 210, 215, 219, 223, 227, 231, 235, 239, 243, 247,
+    251, 255,
+];
+
+/// Packs 8 bit channels into a XRGB8888 pixel.
+#[inline]
+fn pack(r: u8, g: u8, b: u8) -> u32 {
+    ((r as u32) << 16) | ((g as u32) << 8) | (b as u32)
+}
+
+/// Converts a 0RGB1555 pixel to XRGB8888.
+#[inline]
+fn convert_0rgb1555(pixel: u16) -> u32 {
+    pack(
+        EXPAND_5_BITS[((pixel >> 10) & 0b11111) as usize],
+        EXPAND_5_BITS[((pixel >> 5) & 0b11111) as usize],
+        EXPAND_5_BITS[(pixel & 0b11111) as usize],
+    )
+}
+
+/// Converts a RGB565 pixel to XRGB8888.
+#[inline]
+fn convert_rgb565(pixel: u16) -> u32 {
+    pack(
+        EXPAND_5_BITS[((pixel >> 11) & 0b11111) as usize],
+        EXPAND_6_BITS[((pixel >> 5) & 0b111111) as usize],
+        EXPAND_5_BITS[(pixel & 0b11111) as usize],
+    )
+}
+
+/// Converts a framebuffer in the specified format, with rows `pitch` bytes apart, to a tightly
+/// packed XRGB8888 framebuffer.
+pub fn convert(
+    format: RetroPixelFormat,
+    data: &[u8],
+    width: usize,
+    height: usize,
+    pitch: usize,
+) -> Vec<u8> {
+    let mut result = vec![0 as u8; width * height * 4];
+
+    let pixel_size = format.get_pixel_size();
+
+    for y in 0..height {
+        let row = &data[y * pitch..y * pitch + width * pixel_size];
+        let output = &mut result[y * width * 4..(y + 1) * width * 4];
+
+        match format {
+            RetroPixelFormat::Format0RGB1555 => for x in 0..width {
+                let pixel = NativeEndian::read_u16(&row[x * 2..]);
+                NativeEndian::write_u32(&mut output[x * 4..], convert_0rgb1555(pixel));
+            },
+            RetroPixelFormat::FormatRGB565 => for x in 0..width {
+                let pixel = NativeEndian::read_u16(&row[x * 2..]);
+                NativeEndian::write_u32(&mut output[x * 4..], convert_rgb565(pixel));
+            },
+            RetroPixelFormat::FormatXRGB8888 => output.copy_from_slice(row),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    extern crate test;
+
+    use self::test::Bencher;
+
+    use super::convert;
+
+    use byteorder::{ByteOrder, NativeEndian};
+
+    use retro_types::RetroPixelFormat;
+
+    fn build_frame(pixel: u16, width: usize, height: usize, pitch: usize) -> Vec<u8> {
+        let mut data = vec![0 as u8; pitch * height];
+        for y in 0..height {
+            for x in 0..width {
+                NativeEndian::write_u16(&mut data[y * pitch + x * 2..], pixel);
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn convert_0rgb1555() {
+        let data = build_frame(0b0_11111_10000_00000, 2, 2, 8);
+        let output = convert(RetroPixelFormat::Format0RGB1555, &data, 2, 2, 8);
+        assert_eq!(output.len(), 2 * 2 * 4);
+        for pixel in output.chunks(4) {
+            assert_eq!(NativeEndian::read_u32(pixel), 0x00FF8400);
+        }
+    }
+
+    #[test]
+    fn convert_rgb565() {
+        let data = build_frame(0b00000_111111_11111, 2, 2, 4);
+        let output = convert(RetroPixelFormat::FormatRGB565, &data, 2, 2, 4);
+        for pixel in output.chunks(4) {
+            assert_eq!(NativeEndian::read_u32(pixel), 0x0000FFFF);
+        }
+    }
+
+    #[test]
+    fn strip_xrgb8888_pitch() {
+        let data = vec![1, 2, 3, 4, 0, 0, 0, 0, 5, 6, 7, 8, 0, 0, 0, 0];
+        let output = convert(RetroPixelFormat::FormatXRGB8888, &data, 1, 2, 8);
+        assert_eq!(output, [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[bench]
+    fn bench_0rgb1555(b: &mut Bencher) {
+        let data = build_frame(0x1234, 320, 240, 640);
+        b.iter(|| convert(RetroPixelFormat::Format0RGB1555, &data, 320, 240, 640));
+    }
+
+    #[bench]
+    fn bench_rgb565(b: &mut Bencher) {
+        let data = build_frame(0x1234, 320, 240, 640);
+        b.iter(|| convert(RetroPixelFormat::FormatRGB565, &data, 320, 240, 640));
+    }
+
+    #[bench]
+    fn bench_xrgb8888(b: &mut Bencher) {
+        let data = vec![0x12 as u8; 320 * 240 * 4];
+        b.iter(|| convert(RetroPixelFormat::FormatXRGB8888, &data, 320, 240, 320 * 4));
+    }
+}
@@ -0,0 +1,165 @@
+/// Translates Glutin's keyboard input into the keys and modifiers of the LibRetro API.
+
+use super::glutin::ModifiersState;
+use super::glutin::VirtualKeyCode;
+
+use retro_types::RetroKey;
+use retro_types::{RETROKMOD_ALT, RETROKMOD_CTRL, RETROKMOD_META, RETROKMOD_SHIFT};
+
+/// Returns the LibRetro key for a Glutin key, or `RetroKey::Unknown` if there isn't one.
+pub fn to_retro_key(key: VirtualKeyCode) -> RetroKey {
+    match key {
+        VirtualKeyCode::Key1 => RetroKey::Num1,
+        VirtualKeyCode::Key2 => RetroKey::Num2,
+        VirtualKeyCode::Key3 => RetroKey::Num3,
+        VirtualKeyCode::Key4 => RetroKey::Num4,
+        VirtualKeyCode::Key5 => RetroKey::Num5,
+        VirtualKeyCode::Key6 => RetroKey::Num6,
+        VirtualKeyCode::Key7 => RetroKey::Num7,
+        VirtualKeyCode::Key8 => RetroKey::Num8,
+        VirtualKeyCode::Key9 => RetroKey::Num9,
+        VirtualKeyCode::Key0 => RetroKey::Num0,
+
+        VirtualKeyCode::A => RetroKey::A,
+        VirtualKeyCode::B => RetroKey::B,
+        VirtualKeyCode::C => RetroKey::C,
+        VirtualKeyCode::D => RetroKey::D,
+        VirtualKeyCode::E => RetroKey::E,
+        VirtualKeyCode::F => RetroKey::F,
+        VirtualKeyCode::G => RetroKey::G,
+        VirtualKeyCode::H => RetroKey::H,
+        VirtualKeyCode::I => RetroKey::I,
+        VirtualKeyCode::J => RetroKey::J,
+        VirtualKeyCode::K => RetroKey::K,
+        VirtualKeyCode::L => RetroKey::L,
+        VirtualKeyCode::M => RetroKey::M,
+        VirtualKeyCode::N => RetroKey::N,
+        VirtualKeyCode::O => RetroKey::O,
+        VirtualKeyCode::P => RetroKey::P,
+        VirtualKeyCode::Q => RetroKey::Q,
+        VirtualKeyCode::R => RetroKey::R,
+        VirtualKeyCode::S => RetroKey::S,
+        VirtualKeyCode::T => RetroKey::T,
+        VirtualKeyCode::U => RetroKey::U,
+        VirtualKeyCode::V => RetroKey::V,
+        VirtualKeyCode::W => RetroKey::W,
+        VirtualKeyCode::X => RetroKey::X,
+        VirtualKeyCode::Y => RetroKey::Y,
+        VirtualKeyCode::Z => RetroKey::Z,
+
+        VirtualKeyCode::Escape => RetroKey::Escape,
+
+        VirtualKeyCode::F1 => RetroKey::F1,
+        VirtualKeyCode::F2 => RetroKey::F2,
+        VirtualKeyCode::F3 => RetroKey::F3,
+        VirtualKeyCode::F4 => RetroKey::F4,
+        VirtualKeyCode::F5 => RetroKey::F5,
+        VirtualKeyCode::F6 => RetroKey::F6,
+        VirtualKeyCode::F7 => RetroKey::F7,
+        VirtualKeyCode::F8 => RetroKey::F8,
+        VirtualKeyCode::F9 => RetroKey::F9,
+        VirtualKeyCode::F10 => RetroKey::F10,
+        VirtualKeyCode::F11 => RetroKey::F11,
+        VirtualKeyCode::F12 => RetroKey::F12,
+        VirtualKeyCode::F13 => RetroKey::F13,
+        VirtualKeyCode::F14 => RetroKey::F14,
+        VirtualKeyCode::F15 => RetroKey::F15,
+
+        VirtualKeyCode::Snapshot => RetroKey::Print,
+        VirtualKeyCode::Scroll => RetroKey::ScrollLock,
+        VirtualKeyCode::Pause => RetroKey::Pause,
+
+        VirtualKeyCode::Insert => RetroKey::Insert,
+        VirtualKeyCode::Home => RetroKey::Home,
+        VirtualKeyCode::Delete => RetroKey::Delete,
+        VirtualKeyCode::End => RetroKey::End,
+        VirtualKeyCode::PageDown => RetroKey::PageDown,
+        VirtualKeyCode::PageUp => RetroKey::PageUp,
+
+        VirtualKeyCode::Left => RetroKey::Left,
+        VirtualKeyCode::Up => RetroKey::Up,
+        VirtualKeyCode::Right => RetroKey::Right,
+        VirtualKeyCode::Down => RetroKey::Down,
+
+        VirtualKeyCode::Back => RetroKey::Backspace,
+        VirtualKeyCode::Return => RetroKey::Return,
+        VirtualKeyCode::Space => RetroKey::Space,
+        VirtualKeyCode::Compose => RetroKey::Compose,
+        VirtualKeyCode::Caret => RetroKey::Caret,
+
+        VirtualKeyCode::Numlock => RetroKey::NumLock,
+        VirtualKeyCode::Numpad0 => RetroKey::Keypad0,
+        VirtualKeyCode::Numpad1 => RetroKey::Keypad1,
+        VirtualKeyCode::Numpad2 => RetroKey::Keypad2,
+        VirtualKeyCode::Numpad3 => RetroKey::Keypad3,
+        VirtualKeyCode::Numpad4 => RetroKey::Keypad4,
+        VirtualKeyCode::Numpad5 => RetroKey::Keypad5,
+        VirtualKeyCode::Numpad6 => RetroKey::Keypad6,
+        VirtualKeyCode::Numpad7 => RetroKey::Keypad7,
+        VirtualKeyCode::Numpad8 => RetroKey::Keypad8,
+        VirtualKeyCode::Numpad9 => RetroKey::Keypad9,
+        VirtualKeyCode::Add => RetroKey::KeypadPlus,
+        VirtualKeyCode::Subtract => RetroKey::KeypadMinus,
+        VirtualKeyCode::Multiply => RetroKey::KeypadMultiply,
+        VirtualKeyCode::Divide => RetroKey::KeypadDivide,
+        VirtualKeyCode::Decimal => RetroKey::KeypadPeriod,
+        VirtualKeyCode::NumpadComma => RetroKey::KeypadPeriod,
+        VirtualKeyCode::NumpadEnter => RetroKey::KeypadEnter,
+        VirtualKeyCode::NumpadEquals => RetroKey::KeypadEquals,
+
+        VirtualKeyCode::Apostrophe => RetroKey::Quote,
+        VirtualKeyCode::At => RetroKey::At,
+        VirtualKeyCode::Backslash => RetroKey::Backslash,
+        VirtualKeyCode::Colon => RetroKey::Colon,
+        VirtualKeyCode::Comma => RetroKey::Comma,
+        VirtualKeyCode::Equals => RetroKey::Equals,
+        VirtualKeyCode::Grave => RetroKey::Backquote,
+        VirtualKeyCode::LBracket => RetroKey::LeftBracket,
+        VirtualKeyCode::RBracket => RetroKey::RightBracket,
+        VirtualKeyCode::Minus => RetroKey::Minus,
+        VirtualKeyCode::Period => RetroKey::Period,
+        VirtualKeyCode::Semicolon => RetroKey::Semicolon,
+        VirtualKeyCode::Slash => RetroKey::Slash,
+        VirtualKeyCode::Underline => RetroKey::Underscore,
+        VirtualKeyCode::OEM102 => RetroKey::Oem102,
+        VirtualKeyCode::Tab => RetroKey::Tab,
+
+        VirtualKeyCode::Capital => RetroKey::CapsLock,
+        VirtualKeyCode::LShift => RetroKey::LeftShift,
+        VirtualKeyCode::RShift => RetroKey::RightShift,
+        VirtualKeyCode::LControl => RetroKey::LeftCtrl,
+        VirtualKeyCode::RControl => RetroKey::RightCtrl,
+        // Some platforms report Alt as "Menu", as Windows does
+        VirtualKeyCode::LAlt | VirtualKeyCode::LMenu => RetroKey::LeftAlt,
+        VirtualKeyCode::RAlt | VirtualKeyCode::RMenu => RetroKey::RightAlt,
+        VirtualKeyCode::LWin => RetroKey::LeftSuper,
+        VirtualKeyCode::RWin => RetroKey::RightSuper,
+        VirtualKeyCode::Apps => RetroKey::Menu,
+
+        VirtualKeyCode::Sysrq => RetroKey::SysReq,
+        VirtualKeyCode::Power => RetroKey::Power,
+
+        _ => RetroKey::Unknown,
+    }
+}
+
+/// Returns the `RETROKMOD_*` bits for the modifiers that are held. Glutin doesn't report
+/// the state of lock keys, so these are never set.
+pub fn to_modifiers(state: ModifiersState) -> u16 {
+    let mut modifiers = 0;
+
+    if state.shift {
+        modifiers |= RETROKMOD_SHIFT;
+    }
+    if state.ctrl {
+        modifiers |= RETROKMOD_CTRL;
+    }
+    if state.alt {
+        modifiers |= RETROKMOD_ALT;
+    }
+    if state.logo {
+        modifiers |= RETROKMOD_META;
+    }
+
+    modifiers
+}
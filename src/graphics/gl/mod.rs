@@ -2,6 +2,8 @@ extern crate fps_counter;
 extern crate gl;
 extern crate glutin;
 
+mod keyboard;
+
 use self::glutin::EventsLoop;
 use self::glutin::GlContext;
 use self::glutin::GlWindow;
@@ -14,7 +16,11 @@ use std::ptr;
 use graphics::Renderer;
 use graphics::RendererInfo;
 
-use input::InputKey;
+use input::InputEvent;
+use input::mouse::MouseButton;
+
+use retro_types::RetroGameGeometry;
+use retro_types::RetroPixelFormat;
 
 pub struct GLRenderer {
     gl_window: GlWindow,
@@ -27,10 +33,107 @@ pub struct GLRenderer {
     vb: u32,
     program: u32,
 
-    keys: Vec<self::glutin::VirtualKeyCode>,
+    /// Where the cursor is over the window in pixels, if it is.
+    cursor: Option<(f64, f64)>,
     events_polled: bool,
     title: String,
     fps: FPSCounter,
+
+    window_width: u32,
+    window_height: u32,
+    aspect_ratio: f32,
+    rotation: u32,
+    rotation_dirty: bool,
+}
+
+impl GLRenderer {
+    /// Returns the area of the window that content is displayed in, as
+    /// `(x, y, width, height)`. Content is letterboxed to keep the correct aspect ratio.
+    fn get_viewport(&self) -> (f32, f32, f32, f32) {
+        let window_width = self.window_width as f32;
+        let window_height = self.window_height as f32;
+
+        // Content on its side has its aspect ratio flipped
+        let aspect_ratio = if self.rotation % 2 == 1 {
+            1.0 / self.aspect_ratio
+        } else {
+            self.aspect_ratio
+        };
+
+        let (width, height) = if window_width / window_height > aspect_ratio {
+            (window_height * aspect_ratio, window_height)
+        } else {
+            (window_width, window_width / aspect_ratio)
+        };
+
+        (
+            (window_width - width) / 2.0,
+            (window_height - height) / 2.0,
+            width,
+            height,
+        )
+    }
+
+    /// Letterboxes the viewport so that content is displayed at the correct aspect ratio.
+    fn update_viewport(&self) {
+        let (x, y, width, height) = self.get_viewport();
+
+        unsafe {
+            self::gl::Viewport(x as _, y as _, width as _, height as _);
+        }
+    }
+
+    /// Maps a position in the window to content, from (-1, -1) at its top-left to (1, 1) at
+    /// its bottom-right.
+    fn to_content_position(&self, x: f64, y: f64) -> (f64, f64) {
+        let (left, top, width, height) = self.get_viewport();
+
+        let x = (x - left as f64) / width as f64 * 2.0 - 1.0;
+        let y = (y - top as f64) / height as f64 * 2.0 - 1.0;
+
+        unrotate_position(x, y, self.rotation)
+    }
+
+    /// Draws the current contents of the texture to the screen.
+    fn draw(&mut self) {
+        unsafe {
+            if self.rotation_dirty {
+                let vertex_data = build_vertex_data(self.rotation);
+                self::gl::BindBuffer(self::gl::ARRAY_BUFFER, self.vb);
+                self::gl::BufferSubData(
+                    self::gl::ARRAY_BUFFER,
+                    0,
+                    (vertex_data.len() * mem::size_of::<f32>()) as self::gl::types::GLsizeiptr,
+                    vertex_data.as_ptr() as *const _,
+                );
+                self.rotation_dirty = false;
+            }
+
+            self::gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+            self::gl::Clear(self::gl::COLOR_BUFFER_BIT);
+
+            self::gl::UseProgram(self.program);
+
+            self::gl::ActiveTexture(self::gl::TEXTURE0);
+            self::gl::BindTexture(self::gl::TEXTURE_2D, self.tex);
+
+            self::gl::BindVertexArray(self.vao);
+            self::gl::Uniform1i(
+                self::gl::GetUniformLocation(self.program, b"tex\0".as_ptr() as *const _),
+                0,
+            );
+
+            self::gl::BindBuffer(self::gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            self::gl::DrawElements(
+                self::gl::TRIANGLES,
+                6,
+                self::gl::UNSIGNED_INT,
+                (0 * mem::size_of::<f32>()) as *const () as *const _,
+            );
+        }
+
+        self.gl_window.swap_buffers().unwrap();
+    }
 }
 
 impl Drop for GLRenderer {
@@ -45,25 +148,31 @@ impl Drop for GLRenderer {
 }
 
 impl Renderer for GLRenderer {
-    fn submit_frame(&mut self, frame: &[u8], width: usize, height: usize) {
+    fn submit_frame(
+        &mut self,
+        frame: &[u8],
+        width: usize,
+        height: usize,
+        pitch: usize,
+        format: RetroPixelFormat,
+    ) {
         if !self.is_alive {
             return;
         }
 
-        unsafe {
-            self::gl::ClearColor(0.0, 0.0, 0.0, 1.0);
-            self::gl::Clear(self::gl::COLOR_BUFFER_BIT);
-
-            self::gl::UseProgram(self.program);
+        // Upload the framebuffer as-is, letting the GPU unpack it
+        let (pixel_format, pixel_type) = match format {
+            RetroPixelFormat::FormatXRGB8888 => {
+                (self::gl::BGRA, self::gl::UNSIGNED_INT_8_8_8_8_REV)
+            }
+            RetroPixelFormat::FormatRGB565 => (self::gl::RGB, self::gl::UNSIGNED_SHORT_5_6_5),
+            _ => panic!("Unsupported pixel format: {:?}", format),
+        };
 
+        unsafe {
             self::gl::ActiveTexture(self::gl::TEXTURE0);
             self::gl::BindTexture(self::gl::TEXTURE_2D, self.tex);
 
-            self::gl::BindVertexArray(self.vao);
-            self::gl::Uniform1i(
-                self::gl::GetUniformLocation(self.program, b"tex\0".as_ptr() as *const _),
-                0,
-            );
             self::gl::TexParameteri(
                 self::gl::TEXTURE_2D,
                 self::gl::TEXTURE_WRAP_S,
@@ -85,6 +194,12 @@ impl Renderer for GLRenderer {
                 self::gl::NEAREST as self::gl::types::GLint,
             );
 
+            self::gl::PixelStorei(self::gl::UNPACK_ALIGNMENT, 1);
+            self::gl::PixelStorei(
+                self::gl::UNPACK_ROW_LENGTH,
+                (pitch / format.get_pixel_size()) as self::gl::types::GLint,
+            );
+
             self::gl::TexImage2D(
                 self::gl::TEXTURE_2D,
                 0,
@@ -92,28 +207,37 @@ impl Renderer for GLRenderer {
                 width as i32,
                 height as i32,
                 0,
-                self::gl::RGBA,
-                self::gl::UNSIGNED_BYTE,
+                pixel_format,
+                pixel_type,
                 frame.as_ptr() as *const _,
             );
-
-            self::gl::BindBuffer(self::gl::ELEMENT_ARRAY_BUFFER, self.ebo);
-            self::gl::DrawElements(
-                self::gl::TRIANGLES,
-                6,
-                self::gl::UNSIGNED_INT,
-                (0 * mem::size_of::<f32>()) as *const () as *const _,
-            );
         }
 
-        self.gl_window.swap_buffers().unwrap();
+        self.draw();
+    }
+
+    fn supports_pixel_format(&self, format: RetroPixelFormat) -> bool {
+        match format {
+            RetroPixelFormat::FormatXRGB8888 | RetroPixelFormat::FormatRGB565 => true,
+            _ => false,
+        }
     }
 
-    fn poll_events(&mut self) {
+    fn redraw(&mut self) {
         if !self.is_alive {
             return;
         }
 
+        // The texture still holds the last frame
+        self.draw();
+    }
+
+    fn poll_events(&mut self) -> Vec<InputEvent> {
+        let mut input = Vec::new();
+        if !self.is_alive {
+            return input;
+        }
+
         self.events_polled = true;
 
         let mut events = Vec::new();
@@ -127,63 +251,119 @@ impl Renderer for GLRenderer {
                     glutin::WindowEvent::Closed => self.is_alive = false,
                     glutin::WindowEvent::Resized(w, h) => {
                         self.gl_window.resize(w, h);
-                        unsafe {
-                            self::gl::Viewport(0, 0, w as _, h as _);
+                        self.window_width = w;
+                        self.window_height = h;
+                        self.update_viewport();
+
+                        // Content moves from under the cursor as the window is resized
+                        if let Some((x, y)) = self.cursor {
+                            let (x, y) = self.to_content_position(x, y);
+                            input.push(InputEvent::CursorMoved(x, y));
                         }
                     }
-                    glutin::WindowEvent::KeyboardInput { input, .. } => match input.virtual_keycode
-                    {
-                        Some(v) => {
-                            if input.state == glutin::ElementState::Pressed
-                                && !self.keys.contains(&v)
-                            {
-                                self.keys.push(v);
-                            } else if input.state == glutin::ElementState::Released {
-                                self.keys.remove_item(&v);
-                            }
+                    glutin::WindowEvent::KeyboardInput { input: key, .. } => {
+                        if let Some(v) = key.virtual_keycode {
+                            input.push(InputEvent::Key {
+                                key: keyboard::to_retro_key(v),
+                                down: key.state == glutin::ElementState::Pressed,
+                                modifiers: keyboard::to_modifiers(key.modifiers),
+                            });
                         }
-                        _ => {}
-                    },
+                    }
+                    glutin::WindowEvent::CursorMoved { position, .. } => {
+                        self.cursor = Some(position);
+
+                        let (x, y) = self.to_content_position(position.0, position.1);
+                        input.push(InputEvent::CursorMoved(x, y));
+                    }
+                    glutin::WindowEvent::CursorLeft { .. } => {
+                        self.cursor = None;
+                        input.push(InputEvent::CursorLeft);
+                    }
+                    glutin::WindowEvent::MouseInput { state, button, .. } => {
+                        let button = match button {
+                            glutin::MouseButton::Left => MouseButton::Left,
+                            glutin::MouseButton::Right => MouseButton::Right,
+                            glutin::MouseButton::Middle => MouseButton::Middle,
+                            // X11 numbers the side buttons after the scroll wheel
+                            glutin::MouseButton::Other(4) | glutin::MouseButton::Other(8) => {
+                                MouseButton::Button4
+                            }
+                            glutin::MouseButton::Other(5) | glutin::MouseButton::Other(9) => {
+                                MouseButton::Button5
+                            }
+                            _ => continue,
+                        };
+
+                        input.push(InputEvent::MouseButton {
+                            button,
+                            down: state == glutin::ElementState::Pressed,
+                        });
+                    }
+                    glutin::WindowEvent::MouseWheel { delta, .. } => {
+                        // Only the direction of scrolling matters to cores
+                        let (x, y) = match delta {
+                            glutin::MouseScrollDelta::LineDelta(x, y) => (x, y),
+                            glutin::MouseScrollDelta::PixelDelta(x, y) => (x, y),
+                        };
+                        input.push(InputEvent::MouseWheel(x as f64, y as f64));
+                    }
+                    glutin::WindowEvent::Focused(focused) => {
+                        input.push(InputEvent::Focused(focused))
+                    }
+                    glutin::WindowEvent::ReceivedCharacter(character) => {
+                        input.push(InputEvent::Character(character))
+                    }
                     _ => (),
                 },
+                glutin::Event::DeviceEvent {
+                    event: glutin::DeviceEvent::MouseMotion { delta },
+                    ..
+                } => input.push(InputEvent::MouseMotion(delta.0, delta.1)),
                 _ => (),
             }
         }
+
+        input
     }
 
     fn is_alive(&self) -> bool {
         self.is_alive
     }
 
-    fn is_key_down(&self, key: &InputKey) -> bool {
-        // Map input keys to glutin keys
-        // TODO: this should be configurable
-
-        let native_key = match key {
-            &InputKey::A => self::glutin::VirtualKeyCode::A,
-            &InputKey::B => self::glutin::VirtualKeyCode::S,
-            &InputKey::X => self::glutin::VirtualKeyCode::Z,
-            &InputKey::Y => self::glutin::VirtualKeyCode::X,
-            &InputKey::Select => self::glutin::VirtualKeyCode::V,
-            &InputKey::Start => self::glutin::VirtualKeyCode::B,
-            &InputKey::Up => self::glutin::VirtualKeyCode::Up,
-            &InputKey::Down => self::glutin::VirtualKeyCode::Down,
-            &InputKey::Left => self::glutin::VirtualKeyCode::Left,
-            &InputKey::Right => self::glutin::VirtualKeyCode::Right,
-            &InputKey::L => self::glutin::VirtualKeyCode::Q,
-            &InputKey::R => self::glutin::VirtualKeyCode::W,
-            &InputKey::L2 => self::glutin::VirtualKeyCode::Key1,
-            &InputKey::R2 => self::glutin::VirtualKeyCode::Key2,
-            &InputKey::L3 => self::glutin::VirtualKeyCode::Key3,
-            &InputKey::R3 => self::glutin::VirtualKeyCode::Key4,
+    fn set_title(&mut self, title: String) {
+        self.gl_window.set_title(&title);
+        self.title = title;
+    }
+
+    fn set_geometry(&mut self, geometry: &RetroGameGeometry) {
+        self.aspect_ratio = if geometry.aspect_ratio > 0.0 {
+            geometry.aspect_ratio
+        } else {
+            geometry.base_width as f32 / geometry.base_height as f32
         };
 
-        self.keys.contains(&native_key)
+        self.update_viewport();
     }
 
-    fn set_title(&mut self, title: String) {
-        self.gl_window.set_title(&title);
-        self.title = title;
+    fn set_rotation(&mut self, rotation: u32) {
+        // Turning content onto its side also turns the window onto its side
+        if rotation % 2 != self.rotation % 2 {
+            self.gl_window.set_inner_size(self.window_height, self.window_width);
+        }
+
+        self.rotation = rotation;
+        self.rotation_dirty = true;
+        self.update_viewport();
+    }
+
+    fn set_window_size(&mut self, width: u32, height: u32) {
+        // Sideways content needs a sideways window
+        if self.rotation % 2 == 1 {
+            self.gl_window.set_inner_size(height, width);
+        } else {
+            self.gl_window.set_inner_size(width, height);
+        }
     }
 }
 
@@ -295,10 +475,16 @@ pub fn build(width: u32, height: u32) -> Box<Renderer> {
         vb,
         program,
 
-        keys: Vec::new(),
+        cursor: None,
         events_polled: true,
         title: "OxRetro".to_owned(),
         fps: FPSCounter::new(),
+
+        window_width: width,
+        window_height: height,
+        aspect_ratio: width as f32 / height as f32,
+        rotation: 0,
+        rotation_dirty: false,
     })
 }
 
@@ -317,6 +503,32 @@ static VERTEX_DATA: [f32; 28] = [
     -1.0, -1.0, 1.0, 1.0, 1.0, 0.0, 1.0  // Bottom-left
 ];
 
+/// Builds the vertex data for our quad, with texture coordinates rotated by the specified
+/// amount of 90 degree counter-clockwise turns.
+fn build_vertex_data(rotation: u32) -> [f32; 28] {
+    let mut data = VERTEX_DATA;
+
+    for vertex in 0..4 {
+        // Each corner of the screen takes the texture coordinates of the corner after it
+        let source = (vertex + rotation as usize) % 4;
+        data[vertex * 7 + 5] = VERTEX_DATA[source * 7 + 5];
+        data[vertex * 7 + 6] = VERTEX_DATA[source * 7 + 6];
+    }
+
+    data
+}
+
+/// Maps a position on the screen back to content that has been rotated by the specified
+/// amount of 90 degree counter-clockwise turns. Positions range from -1 to 1.
+fn unrotate_position(x: f64, y: f64, rotation: u32) -> (f64, f64) {
+    match rotation % 4 {
+        0 => (x, y),
+        1 => (-y, x),
+        2 => (-x, -y),
+        _ => (y, -x),
+    }
+}
+
 static ELEMENTS: [u32; 6] = [0, 1, 2, 2, 3, 0];
 
 const VS_SRC: &'static [u8] = b"
@@ -352,3 +564,17 @@ const FS_SRC: &'static [u8] = b"
         outColor = texture(tex, Texcoord) * vec4(Color, 1.0);
     }
 \0";
+
+#[cfg(test)]
+mod test {
+    use super::unrotate_position;
+
+    #[test]
+    fn rotated_positions() {
+        // The top-left of the screen shows the top-right of content after a quarter turn
+        assert_eq!(unrotate_position(-1.0, -1.0, 1), (1.0, -1.0));
+        assert_eq!(unrotate_position(1.0, -1.0, 1), (1.0, 1.0));
+        assert_eq!(unrotate_position(0.5, 0.25, 2), (-0.5, -0.25));
+        assert_eq!(unrotate_position(0.5, 0.25, 4), (0.5, 0.25));
+    }
+}
@@ -5,7 +5,12 @@
 #[cfg(feature = "graphics_opengl")]
 pub mod gl;
 
-use input::InputKey;
+pub mod conversions;
+
+use input::InputEvent;
+
+use retro_types::RetroGameGeometry;
+use retro_types::RetroPixelFormat;
 
 #[derive(Debug)]
 pub struct RendererInfo {
@@ -15,17 +20,40 @@ pub struct RendererInfo {
 }
 
 pub trait Renderer {
-    fn submit_frame(&mut self, frame: &[u8], width: usize, height: usize);
+    /// Submits a frame in the specified format, with rows `pitch` bytes apart. The format
+    /// must be one that this renderer supports.
+    fn submit_frame(
+        &mut self,
+        frame: &[u8],
+        width: usize,
+        height: usize,
+        pitch: usize,
+        format: RetroPixelFormat,
+    );
 
-    // TODO: This shouldn't be here
-    fn poll_events(&mut self);
+    /// Returns if this renderer can upload frames in the specified format directly.
+    /// XRGB8888 must always be supported - other formats are converted to it.
+    fn supports_pixel_format(&self, format: RetroPixelFormat) -> bool;
 
-    fn is_alive(&self) -> bool;
+    /// Presents the last submitted frame again.
+    fn redraw(&mut self);
+
+    /// Handles events from the window, returning the input it has received since this was
+    /// last called.
+    fn poll_events(&mut self) -> Vec<InputEvent>;
 
-    // TODO: This shouldn't be here
-    fn is_key_down(&self, key: &InputKey) -> bool;
+    fn is_alive(&self) -> bool;
 
     fn set_title(&mut self, title: String);
+
+    /// Updates the dimensions and aspect ratio of the content being displayed.
+    fn set_geometry(&mut self, geometry: &RetroGameGeometry);
+
+    /// Rotates content by the specified amount of 90 degree counter-clockwise turns.
+    fn set_rotation(&mut self, rotation: u32);
+
+    /// Resizes the window that content is being displayed in.
+    fn set_window_size(&mut self, width: u32, height: u32);
 }
 
 static AVAILABLE_RENDERERS: &'static [(&'static RendererInfo, fn(u32, u32) -> Box<Renderer>)] = &[
@@ -0,0 +1,467 @@
+/// Merges the keyboard, mouse, gamepads and scripted pads into the devices plugged into each
+/// port, taking a snapshot of them each time the core polls input. Turbo buttons and macros
+/// are applied to the snapshot, so every core sees them the same way.
+
+use input::InputAxis;
+use input::InputBackend;
+use input::InputEvent;
+use input::InputKey;
+use input::analog;
+use input::analog::AnalogConfig;
+use input::analog::AnalogKey;
+use input::bindings::BindWizard;
+use input::bindings::Binding;
+use input::bindings::BindingDevice;
+use input::bindings::Bindings;
+use input::keyboard;
+use input::keyboard::Keyboard;
+use input::macros::Macro;
+use input::mouse::MouseState;
+use input::ports::KeyboardLayout;
+use input::ports::PortDevice;
+use input::ports::Ports;
+use input::ports::MAX_PORTS;
+use input::snapshot::InputSnapshot;
+use input::snapshot::PortSnapshot;
+use input::snapshot::JOYPAD_BUTTONS;
+use input::turbo::TurboPresses;
+
+use sensor::SensorKey;
+
+use frontend::hotkeys::Hotkey;
+
+use core_protocol::KeyboardEvent;
+
+use retro_types::RetroKey;
+use retro_types::RetroRumbleEffect;
+
+pub struct InputAggregator {
+    /// Where gamepads come from.
+    backend: Option<Box<InputBackend>>,
+    keyboard: Keyboard,
+    /// Mouse movement, scrolling and buttons since the last snapshot.
+    mouse: MouseState,
+    /// If the window has focus, so that the mouse belongs to it.
+    focused: bool,
+    /// The devices plugged into each port of the core.
+    ports: Ports,
+    /// How analog sticks should be adjusted.
+    analog: AnalogConfig,
+    /// If keys are only being sent to the core's keyboard.
+    game_focus: bool,
+    /// The keys and buttons which press each RetroPad button.
+    bindings: Bindings,
+    /// Asks for new bindings while it is running, taking over the keyboard.
+    wizard: Option<BindWizard>,
+    /// Bindings from a finished wizard, which haven't been saved yet.
+    new_bindings: Option<(BindingDevice, Vec<(InputKey, Vec<Binding>)>)>,
+    /// Macros which loaded, with the key that plays them and their port.
+    macros: Vec<(RetroKey, u32, Macro)>,
+    /// Macros which are playing, by index, with the poll they started on.
+    playing: Vec<(usize, u64)>,
+    /// When each held turbo binding was pressed, by the gamepad it is held on (or None for
+    /// the keyboard).
+    turbo: TurboPresses<(Option<usize>, Binding)>,
+    /// How many times input has been polled, which times turbo buttons and macros.
+    polls: u64,
+    snapshot: InputSnapshot,
+}
+
+impl InputAggregator {
+    /// Handles input that the window has received.
+    fn handle_event(&mut self, event: InputEvent) {
+        match event {
+            InputEvent::Key {
+                key,
+                down,
+                modifiers,
+            } => {
+                if self.wizard.is_none() {
+                    // Held keys repeat, which shouldn't restart macros
+                    if down && self.is_keyboard_free() && !self.keyboard.is_key_down(key) {
+                        self.play_macros(key);
+                    }
+
+                    self.keyboard.key_event(key, down, modifiers);
+                    return;
+                }
+
+                self.keyboard.set_held(key, down);
+                if !down {
+                    return;
+                }
+
+                let binds = match &mut self.wizard {
+                    &mut Some(ref mut v) => v.key_pressed(key),
+                    &mut None => None,
+                };
+                if let Some(binds) = binds {
+                    let device = self.wizard.take().unwrap().get_device();
+                    for &(key, ref binds) in &binds {
+                        self.bindings.set(device, key, binds.clone());
+                    }
+
+                    println!("Finished binding keys");
+                    self.new_bindings = Some((device, binds));
+                }
+            }
+            InputEvent::Character(character) => if self.wizard.is_none() {
+                self.keyboard.character_event(character)
+            },
+            InputEvent::CursorMoved(x, y) => self.mouse.position = Some((x, y)),
+            InputEvent::CursorLeft => self.mouse.position = None,
+            // Raw movement isn't limited by the edges of the window
+            InputEvent::MouseMotion(x, y) => if self.focused {
+                self.mouse.delta.0 += x;
+                self.mouse.delta.1 += y;
+            },
+            InputEvent::MouseButton { button, down } => {
+                if down {
+                    if !self.mouse.buttons.contains(&button) {
+                        self.mouse.buttons.push(button);
+                    }
+                } else {
+                    self.mouse.buttons.retain(|x| *x != button);
+                }
+            }
+            InputEvent::MouseWheel(x, y) => {
+                self.mouse.wheel.0 += x;
+                self.mouse.wheel.1 += y;
+            }
+            InputEvent::Focused(focused) => {
+                self.focused = focused;
+                if !focused {
+                    self.mouse.buttons.clear();
+                }
+            }
+        }
+    }
+
+    /// Returns if the keyboard is standing in for other devices, rather than typing or binding.
+    fn is_keyboard_free(&self) -> bool {
+        !self.game_focus && self.wizard.is_none()
+    }
+
+    /// Starts (or restarts) the macros played by a key.
+    fn play_macros(&mut self, key: RetroKey) {
+        for (index, &(macro_key, _, _)) in self.macros.iter().enumerate() {
+            if macro_key == key {
+                self.playing.retain(|x| x.0 != index);
+                self.playing.push((index, self.polls));
+            }
+        }
+    }
+
+    /// Records when turbo keys and buttons were pressed, so that they start pressed.
+    fn update_turbo(&mut self) {
+        let mut held = Vec::new();
+        for bind in self.bindings.get_turbo() {
+            match (bind, &self.backend) {
+                (Binding::TurboKey(v), _) => if self.keyboard.is_key_down(v) {
+                    held.push((None, bind));
+                },
+                (Binding::TurboButton(ref v), &Some(ref backend)) => {
+                    for id in backend.get_gamepads() {
+                        if backend.is_key_down(id, v) {
+                            held.push((Some(id), bind));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.turbo.update(&held, self.polls);
+    }
+
+    /// Returns if a turbo binding held on a gamepad (or None for the keyboard) presses its
+    /// button on this poll.
+    fn is_turbo_pressed(&self, gamepad: Option<usize>, bind: Binding) -> bool {
+        self.turbo
+            .is_pressed(&self.bindings.turbo, &(gamepad, bind), self.polls)
+    }
+
+    /// Returns if a key bound to a RetroPad button in a layout is held.
+    fn is_bound_key_down(&self, layout: KeyboardLayout, key: &InputKey) -> bool {
+        self.is_keyboard_free()
+            && self.bindings
+                .get(&BindingDevice::Keyboard(layout), key)
+                .iter()
+                .any(|bind| match bind {
+                    &Binding::Key(v) => self.keyboard.is_key_down(v),
+                    &Binding::TurboKey(v) => {
+                        self.keyboard.is_key_down(v) && self.is_turbo_pressed(None, *bind)
+                    }
+                    _ => false,
+                })
+    }
+
+    /// Returns how far the buttons bound to a RetroPad button on a gamepad are pressed.
+    fn get_bound_button_value(&self, gamepad: usize, key: &InputKey) -> f32 {
+        let backend = match &self.backend {
+            &Some(ref v) => v,
+            &None => return 0.0,
+        };
+
+        self.bindings
+            .get(&BindingDevice::Gamepad, key)
+            .iter()
+            .map(|bind| match bind {
+                &Binding::Button(ref v) => backend.get_button_value(gamepad, v),
+                &Binding::TurboButton(ref v) if self.is_turbo_pressed(Some(gamepad), *bind) => {
+                    backend.get_button_value(gamepad, v)
+                }
+                _ => 0.0,
+            })
+            .fold(0.0, f32::max)
+    }
+
+    /// Returns if a RetroPad button is held on any device plugged into a port.
+    fn is_joypad_down(&self, port: u32, key: &InputKey) -> bool {
+        self.ports.get_devices(port).iter().any(|device| match device {
+            // Keys belong to the core's keyboard while it has focus
+            &PortDevice::Keyboard(layout) => self.is_bound_key_down(layout, key),
+            &PortDevice::Gamepad(id) => match &self.backend {
+                &Some(ref v) => self.bindings
+                    .get(&BindingDevice::Gamepad, key)
+                    .iter()
+                    .any(|bind| match bind {
+                        &Binding::Button(ref button) => v.is_key_down(id, button),
+                        &Binding::TurboButton(ref button) => {
+                            v.is_key_down(id, button) && self.is_turbo_pressed(Some(id), *bind)
+                        }
+                        _ => false,
+                    }),
+                &None => false,
+            },
+            &PortDevice::Script(ref script) => script.is_key_down(key),
+        })
+    }
+
+    /// Returns how far a RetroPad button is pressed on a port, from 0 to 1.
+    fn get_button_value(&self, port: u32, key: &InputKey) -> f32 {
+        // Only gamepads have analog buttons, everything else is fully pressed
+        let pressed = |down: bool| if down { 1.0 } else { 0.0 };
+
+        self.ports
+            .get_devices(port)
+            .iter()
+            .map(|device| match device {
+                &PortDevice::Gamepad(id) => self.get_bound_button_value(id, key),
+                &PortDevice::Keyboard(layout) => pressed(self.is_bound_key_down(layout, key)),
+                &PortDevice::Script(ref script) => pressed(script.is_key_down(key)),
+            })
+            .fold(0.0, f32::max)
+    }
+
+    /// Returns where a stick on a port is pushed. Sticks are read from the first device
+    /// plugged into the port which isn't centred.
+    fn get_stick(&self, port: u32, x_axis: InputAxis, y_axis: InputAxis) -> (f32, f32) {
+        for device in self.ports.get_devices(port) {
+            let position = match (device, &self.backend) {
+                (&PortDevice::Gamepad(id), &Some(ref v)) => self.analog
+                    .apply(v.get_axis(id, &x_axis), v.get_axis(id, &y_axis)),
+                (&PortDevice::Keyboard(KeyboardLayout::Full), _) if self.is_keyboard_free() => {
+                    let is_key_down = |key: &AnalogKey| {
+                        self.keyboard.is_key_down(keyboard::get_analog_key(key))
+                    };
+                    (
+                        analog::get_keyboard_axis(&x_axis, &is_key_down),
+                        analog::get_keyboard_axis(&y_axis, &is_key_down),
+                    )
+                }
+                _ => continue,
+            };
+
+            if position != (0.0, 0.0) {
+                return position;
+            }
+        }
+
+        (0.0, 0.0)
+    }
+
+    /// Presses the buttons held by playing macros on their ports.
+    fn apply_macros(&self, ports: &mut [PortSnapshot]) {
+        for &(index, start) in &self.playing {
+            let (_, port, ref mac) = self.macros[index];
+            let state = &mut ports[port as usize];
+
+            for key in mac.get_keys(self.polls - start).unwrap_or(&[]) {
+                let id = (0..JOYPAD_BUTTONS as u32)
+                    .position(|x| InputKey::from(x) == Some(*key))
+                    .unwrap();
+                state.joypad |= 1 << id;
+                state.buttons[id] = analog::to_retro_button(1.0);
+            }
+        }
+    }
+
+    /// Takes a snapshot of every port.
+    fn take_snapshot(&mut self) -> InputSnapshot {
+        let mut ports: Vec<PortSnapshot> = (0..MAX_PORTS as u32)
+            .map(|port| {
+                let mut state = PortSnapshot::default();
+
+                for id in 0..JOYPAD_BUTTONS as u32 {
+                    let key = InputKey::from(id).unwrap();
+                    if self.is_joypad_down(port, &key) {
+                        state.joypad |= 1 << id;
+                    }
+                    state.buttons[id as usize] =
+                        analog::to_retro_button(self.get_button_value(port, &key));
+                }
+
+                let left = self.get_stick(port, InputAxis::LeftX, InputAxis::LeftY);
+                let right = self.get_stick(port, InputAxis::RightX, InputAxis::RightY);
+                state.sticks = [
+                    analog::to_retro_axis(left.0),
+                    analog::to_retro_axis(left.1),
+                    analog::to_retro_axis(right.0),
+                    analog::to_retro_axis(right.1),
+                ];
+
+                state
+            })
+            .collect();
+
+        self.apply_macros(&mut ports);
+
+        let mouse = self.mouse.clone();
+        self.mouse.delta = (0.0, 0.0);
+        self.mouse.wheel = (0.0, 0.0);
+
+        InputSnapshot {
+            ports,
+            keys: self.keyboard
+                .get_held()
+                .iter()
+                .map(|x| *x as u32)
+                .collect(),
+            mouse,
+        }
+    }
+
+    /// Handles the window's input since the last poll, then polls every device and takes a
+    /// new snapshot.
+    pub fn poll(&mut self, events: Vec<InputEvent>) {
+        for event in events {
+            self.handle_event(event);
+        }
+
+        if let Some(ref mut backend) = self.backend {
+            backend.poll_events();
+            self.ports.update_gamepads(&backend.get_gamepads());
+        }
+        self.ports.poll();
+
+        let polls = self.polls;
+        let macros = &self.macros;
+        self.playing
+            .retain(|&(index, start)| macros[index].2.get_keys(polls - start).is_some());
+
+        self.update_turbo();
+        self.snapshot = self.take_snapshot();
+        self.polls += 1;
+    }
+
+    /// Returns the snapshot taken at the last poll.
+    pub fn get_snapshot(&self) -> &InputSnapshot {
+        &self.snapshot
+    }
+
+    /// Returns the keyboard events which have occurred since this was last called.
+    pub fn take_keyboard_events(&mut self) -> Vec<KeyboardEvent> {
+        self.keyboard.take_events()
+    }
+
+    pub fn is_sensor_key_down(&self, key: &SensorKey) -> bool {
+        // Sensor keys would clash with typing
+        self.is_keyboard_free() && self.keyboard.is_key_down(keyboard::get_sensor_key(key))
+    }
+
+    pub fn is_hotkey_down(&self, key: &Hotkey) -> bool {
+        self.keyboard.is_key_down(keyboard::get_hotkey(key))
+    }
+
+    /// Starts asking the user for the keys of each RetroPad button, for the keyboard layout
+    /// which is plugged into the earliest port.
+    pub fn start_bind_wizard(&mut self) {
+        let layout = (0..MAX_PORTS as u32)
+            .flat_map(|port| self.ports.get_devices(port))
+            .filter_map(|device| match device {
+                &PortDevice::Keyboard(layout) => Some(layout),
+                _ => None,
+            })
+            .next()
+            .unwrap_or(KeyboardLayout::Full);
+
+        self.wizard = Some(BindWizard::new(layout));
+    }
+
+    /// Returns if the bind wizard is running.
+    pub fn is_binding(&self) -> bool {
+        self.wizard.is_some()
+    }
+
+    /// Returns the bindings from the last bind wizard, if they haven't been taken yet.
+    pub fn take_new_bindings(&mut self) -> Option<(BindingDevice, Vec<(InputKey, Vec<Binding>)>)> {
+        self.new_bindings.take()
+    }
+
+    pub fn is_game_focused(&self) -> bool {
+        self.game_focus
+    }
+
+    pub fn set_game_focus(&mut self, game_focus: bool) {
+        self.game_focus = game_focus;
+    }
+
+    /// Sets the strength of a rumble motor on every gamepad plugged into a port.
+    pub fn set_rumble_state(&mut self, port: u32, effect: RetroRumbleEffect, strength: u16) {
+        let gamepads = self.ports.get_gamepads(port);
+        match &mut self.backend {
+            &mut Some(ref mut v) => for gamepad in gamepads {
+                v.set_rumble_state(gamepad, effect, strength);
+            },
+            &mut None => panic!("No input backend available!"),
+        }
+    }
+
+    pub fn new(
+        backend: Option<Box<InputBackend>>,
+        ports: Ports,
+        analog: AnalogConfig,
+        bindings: Bindings,
+    ) -> InputAggregator {
+        let macros = bindings
+            .macros
+            .iter()
+            .filter_map(|x| match Macro::load(&x.path) {
+                Ok(v) => Some((x.key, x.port, v)),
+                Err(e) => {
+                    println!("Failed to load macro {} from {}: {}", x.name, x.path, e);
+                    None
+                }
+            })
+            .collect();
+
+        InputAggregator {
+            backend,
+            keyboard: Keyboard::new(),
+            mouse: MouseState::default(),
+            focused: true,
+            ports,
+            analog,
+            game_focus: false,
+            bindings,
+            wizard: None,
+            new_bindings: None,
+            macros,
+            playing: Vec::new(),
+            turbo: TurboPresses::default(),
+            polls: 0,
+            snapshot: InputSnapshot::default(),
+        }
+    }
+}
@@ -0,0 +1,138 @@
+/// Shapes analog sticks (deadzone and sensitivity), and emulates them with the keyboard.
+
+use input::InputAxis;
+
+/// How far the keyboard pushes a stick while slow walking.
+const SLOW_WALK_SCALE: f32 = 0.5;
+
+/// Keys that can be used to emulate analog sticks.
+pub enum AnalogKey {
+    LeftStickUp,
+    LeftStickDown,
+    LeftStickLeft,
+    LeftStickRight,
+    RightStickUp,
+    RightStickDown,
+    RightStickLeft,
+    RightStickRight,
+    /// Only pushes sticks part of the way while held.
+    SlowWalk,
+}
+
+/// How stick positions from real devices are adjusted before being given to cores.
+#[derive(Debug, Copy, Clone)]
+pub struct AnalogConfig {
+    /// How far a stick must be pushed (from 0 to 1) before it leaves the centre.
+    pub deadzone: f32,
+    /// Multiplies how far sticks are pushed, after the deadzone has been removed.
+    pub sensitivity: f32,
+}
+
+impl AnalogConfig {
+    /// Applies the deadzone and sensitivity to a stick position. The deadzone is circular,
+    /// and positions are rescaled so that the edge of the deadzone is the centre.
+    pub fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        let magnitude = (x * x + y * y).sqrt();
+        if magnitude <= self.deadzone || magnitude == 0.0 {
+            return (0.0, 0.0);
+        }
+
+        let scaled = ((magnitude - self.deadzone) / (1.0 - self.deadzone) * self.sensitivity)
+            .min(1.0);
+
+        (x / magnitude * scaled, y / magnitude * scaled)
+    }
+}
+
+impl Default for AnalogConfig {
+    fn default() -> Self {
+        AnalogConfig {
+            deadzone: 0.15,
+            sensitivity: 1.0,
+        }
+    }
+}
+
+/// Returns where the keyboard is pushing the stick that an axis belongs to, from -1 to 1.
+pub fn get_keyboard_axis(axis: &InputAxis, is_key_down: &Fn(&AnalogKey) -> bool) -> f32 {
+    let (negative, positive) = match axis {
+        &InputAxis::LeftX => (AnalogKey::LeftStickLeft, AnalogKey::LeftStickRight),
+        &InputAxis::LeftY => (AnalogKey::LeftStickUp, AnalogKey::LeftStickDown),
+        &InputAxis::RightX => (AnalogKey::RightStickLeft, AnalogKey::RightStickRight),
+        &InputAxis::RightY => (AnalogKey::RightStickUp, AnalogKey::RightStickDown),
+    };
+
+    let mut value = 0.0;
+    if is_key_down(&negative) {
+        value -= 1.0;
+    }
+    if is_key_down(&positive) {
+        value += 1.0;
+    }
+
+    if is_key_down(&AnalogKey::SlowWalk) {
+        value *= SLOW_WALK_SCALE;
+    }
+
+    value
+}
+
+/// Converts a stick position from -1 to 1 into the range used by cores.
+pub fn to_retro_axis(value: f32) -> i16 {
+    (value.max(-1.0).min(1.0) * 0x7fff as f32).round() as i16
+}
+
+/// Converts how far a button is pressed from 0 to 1 into the range used by cores.
+pub fn to_retro_button(value: f32) -> i16 {
+    (value.max(0.0).min(1.0) * 0x7fff as f32).round() as i16
+}
+
+#[cfg(test)]
+mod test {
+    use super::AnalogConfig;
+    use super::AnalogKey;
+    use super::get_keyboard_axis;
+    use super::to_retro_axis;
+
+    use input::InputAxis;
+
+    #[test]
+    fn deadzone() {
+        let config = AnalogConfig {
+            deadzone: 0.25,
+            sensitivity: 1.0,
+        };
+
+        assert_eq!(config.apply(0.1, -0.1), (0.0, 0.0));
+        assert_eq!(config.apply(0.625, 0.0), (0.5, 0.0));
+        assert_eq!(config.apply(0.0, -1.0), (0.0, -1.0));
+    }
+
+    #[test]
+    fn sensitivity() {
+        let config = AnalogConfig {
+            deadzone: 0.0,
+            sensitivity: 2.0,
+        };
+
+        assert_eq!(config.apply(0.25, 0.0), (0.5, 0.0));
+        assert_eq!(config.apply(0.0, 0.75), (0.0, 1.0));
+    }
+
+    #[test]
+    fn keyboard_slow_walk() {
+        let walking = |key: &AnalogKey| match key {
+            &AnalogKey::LeftStickUp => true,
+            _ => false,
+        };
+        let slow_walking = |key: &AnalogKey| match key {
+            &AnalogKey::LeftStickUp | &AnalogKey::SlowWalk => true,
+            _ => false,
+        };
+
+        assert_eq!(get_keyboard_axis(&InputAxis::LeftY, &walking), -1.0);
+        assert_eq!(get_keyboard_axis(&InputAxis::LeftY, &slow_walking), -0.5);
+        assert_eq!(get_keyboard_axis(&InputAxis::LeftX, &slow_walking), 0.0);
+        assert_eq!(to_retro_axis(-0.5), -0x4000);
+    }
+}
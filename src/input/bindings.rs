@@ -0,0 +1,775 @@
+/// Binds keys on the keyboard and buttons on gamepads to the RetroPad, from a config file.
+///
+/// The file is split into sections, which apply to everything (`[global]`), a core by its
+/// library name (`[core:Snes9x]`) or a game by the name of its file without the extension
+/// (`[game:Super Mario World]`). Each line binds a RetroPad button on a device to one or more
+/// keys, separated by commas:
+///
+/// ```text
+/// [global]
+/// keyboard.a = X, Space
+/// keyboard-left.start = Return
+/// # Swaps A and B on gamepads
+/// gamepad.a = b
+/// gamepad.b = a
+/// ```
+///
+/// Devices are `keyboard`, `keyboard-left`, `keyboard-right` and `gamepad`. Keyboard keys are
+/// named as in `RetroKey`, and gamepad buttons are named as RetroPad buttons. Keys prefixed
+/// with `turbo:` repeatedly press the button while held, at the rate set by `turbo.period`
+/// (in polls) and `turbo.duty` (how much of it the button is pressed for):
+///
+/// ```text
+/// keyboard.a = X, turbo:C
+/// turbo.period = 4
+/// turbo.duty = 0.5
+/// ```
+///
+/// Macros play an input script (see `ports::parse_steps`) on a port when a key is pressed, as
+/// `macro.name = key, path` with an optional port after the path (the first by default):
+///
+/// ```text
+/// macro.hadouken = F1, hadouken.txt
+/// ```
+///
+/// A core's lines replace the global ones for the same button, setting or macro, and a
+/// game's replace both.
+
+use input::InputKey;
+use input::ports::KeyboardLayout;
+use input::ports::MAX_PORTS;
+use input::turbo::TurboConfig;
+
+use retro_types::RetroKey;
+
+use serde::Deserialize;
+use serde::de::IntoDeserializer;
+use serde::de::value::Error as ValueError;
+use serde::de::value::StrDeserializer;
+
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::mem;
+
+static ALL_KEYS: [InputKey; 16] = [
+    InputKey::Up,
+    InputKey::Down,
+    InputKey::Left,
+    InputKey::Right,
+    InputKey::A,
+    InputKey::B,
+    InputKey::X,
+    InputKey::Y,
+    InputKey::L,
+    InputKey::R,
+    InputKey::L2,
+    InputKey::R2,
+    InputKey::L3,
+    InputKey::R3,
+    InputKey::Select,
+    InputKey::Start,
+];
+
+/// A device which can have its buttons bound.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BindingDevice {
+    Keyboard(KeyboardLayout),
+    /// Every gamepad, whose buttons are already mapped to the RetroPad by the input backend.
+    Gamepad,
+}
+
+impl BindingDevice {
+    fn parse(name: &str) -> Option<BindingDevice> {
+        Some(match name {
+            "keyboard" => BindingDevice::Keyboard(KeyboardLayout::Full),
+            "keyboard-left" => BindingDevice::Keyboard(KeyboardLayout::LeftHalf),
+            "keyboard-right" => BindingDevice::Keyboard(KeyboardLayout::RightHalf),
+            "gamepad" => BindingDevice::Gamepad,
+            _ => return None,
+        })
+    }
+
+    fn get_name(&self) -> &'static str {
+        match self {
+            &BindingDevice::Keyboard(KeyboardLayout::Full) => "keyboard",
+            &BindingDevice::Keyboard(KeyboardLayout::LeftHalf) => "keyboard-left",
+            &BindingDevice::Keyboard(KeyboardLayout::RightHalf) => "keyboard-right",
+            &BindingDevice::Gamepad => "gamepad",
+        }
+    }
+}
+
+/// Parses the name of a key on the keyboard, as in `RetroKey`.
+fn parse_key(name: &str) -> Option<RetroKey> {
+    let deserializer: StrDeserializer<ValueError> = name.into_deserializer();
+    RetroKey::deserialize(deserializer).ok()
+}
+
+/// Something which presses a RetroPad button.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Binding {
+    Key(RetroKey),
+    /// A button on a gamepad, as mapped by the input backend.
+    Button(InputKey),
+    /// A key which presses the button on and off while held.
+    TurboKey(RetroKey),
+    /// A button on a gamepad which presses the button on and off while held.
+    TurboButton(InputKey),
+}
+
+impl Binding {
+    /// Parses a key or button for a device.
+    fn parse(device: &BindingDevice, name: &str) -> Option<Binding> {
+        let turbo = name.starts_with("turbo:");
+        let name = if turbo { &name["turbo:".len()..] } else { name };
+
+        match (device, turbo) {
+            (&BindingDevice::Keyboard(_), false) => parse_key(name).map(Binding::Key),
+            (&BindingDevice::Keyboard(_), true) => parse_key(name).map(Binding::TurboKey),
+            (&BindingDevice::Gamepad, false) => InputKey::from_name(name).map(Binding::Button),
+            (&BindingDevice::Gamepad, true) => {
+                InputKey::from_name(name).map(Binding::TurboButton)
+            }
+        }
+    }
+
+    fn get_name(&self) -> String {
+        match self {
+            &Binding::Key(key) => format!("{:?}", key),
+            &Binding::Button(key) => format!("{:?}", key).to_lowercase(),
+            &Binding::TurboKey(key) => format!("turbo:{:?}", key),
+            &Binding::TurboButton(key) => format!("turbo:{:?}", key).to_lowercase(),
+        }
+    }
+}
+
+/// A macro from the config file, which is loaded when the core starts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MacroBinding {
+    pub name: String,
+    /// The key which plays it.
+    pub key: RetroKey,
+    /// The input script it plays.
+    pub path: String,
+    /// The port it plays on.
+    pub port: u32,
+}
+
+impl MacroBinding {
+    /// Parses a macro's value, as `key, path` or `key, path, port`.
+    fn parse(name: &str, value: &str) -> Option<MacroBinding> {
+        let parts: Vec<&str> = value.split(',').map(|x| x.trim()).collect();
+
+        let port = match parts.get(2) {
+            Some(v) => match v.parse::<u32>() {
+                Ok(v) if (v as usize) < MAX_PORTS => v,
+                _ => return None,
+            },
+            None => 0,
+        };
+
+        if parts.len() > 3 {
+            return None;
+        }
+
+        match (parse_key(parts[0]), parts.get(1)) {
+            (Some(key), Some(path)) if !path.is_empty() => Some(MacroBinding {
+                name: name.to_owned(),
+                key,
+                path: path.to_string(),
+                port,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// A line of the config file.
+#[derive(Debug, Clone)]
+enum Line {
+    Bind(BindingDevice, InputKey, Vec<Binding>),
+    TurboPeriod(u32),
+    TurboDuty(f32),
+    Macro(MacroBinding),
+}
+
+impl Line {
+    /// Parses a `name = value` line.
+    fn parse(name: &str, value: &str) -> io::Result<Line> {
+        let mut parts = name.splitn(2, '.');
+        let (prefix, rest) = match (parts.next(), parts.next()) {
+            (Some(prefix), Some(rest)) => (prefix, rest),
+            _ => return Err(invalid_data(format!("Unknown device or button: {}", name))),
+        };
+
+        match (prefix, rest) {
+            ("turbo", "period") => match value.parse::<u32>() {
+                Ok(v) if v > 0 => Ok(Line::TurboPeriod(v)),
+                _ => Err(invalid_data(format!("Bad turbo period: {}", value))),
+            },
+            ("turbo", "duty") => match value.parse::<f32>() {
+                Ok(v) if v > 0.0 && v <= 1.0 => Ok(Line::TurboDuty(v)),
+                _ => Err(invalid_data(format!("Bad turbo duty cycle: {}", value))),
+            },
+            ("macro", _) => match MacroBinding::parse(rest, value) {
+                Some(v) => Ok(Line::Macro(v)),
+                None => Err(invalid_data(format!(
+                    "Expected key, path[, port] for macro {}, got: {}",
+                    rest, value
+                ))),
+            },
+            _ => {
+                let (device, key) = match (BindingDevice::parse(prefix), InputKey::from_name(rest))
+                {
+                    (Some(device), Some(key)) => (device, key),
+                    _ => return Err(invalid_data(format!("Unknown device or button: {}", name))),
+                };
+
+                let mut binds = Vec::new();
+                for bind in value.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+                    match Binding::parse(&device, bind) {
+                        Some(v) => binds.push(v),
+                        None => return Err(invalid_data(format!("Unknown key: {}", bind))),
+                    }
+                }
+
+                Ok(Line::Bind(device, key, binds))
+            }
+        }
+    }
+}
+
+/// What a section of the config file applies to.
+#[derive(Debug, Clone, PartialEq)]
+enum Scope {
+    Global,
+    Core(String),
+    Game(String),
+}
+
+impl Scope {
+    /// Parses the header of a section, without its brackets.
+    fn parse(header: &str) -> Option<Scope> {
+        if header == "global" {
+            Some(Scope::Global)
+        } else if header.starts_with("core:") {
+            Some(Scope::Core(header["core:".len()..].trim().to_owned()))
+        } else if header.starts_with("game:") {
+            Some(Scope::Game(header["game:".len()..].trim().to_owned()))
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns the `[section]` header or `name = value` pair on a line, without comments.
+fn split_line(line: &str) -> (&str, Option<&str>) {
+    let line = line.split('#').next().unwrap().trim();
+    let mut parts = line.splitn(2, '=');
+    (parts.next().unwrap().trim(), parts.next().map(|x| x.trim()))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The keys and buttons bound to the RetroPad on each device.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bindings {
+    binds: Vec<(BindingDevice, InputKey, Vec<Binding>)>,
+    /// How quickly turbo bindings press their buttons.
+    pub turbo: TurboConfig,
+    pub macros: Vec<MacroBinding>,
+}
+
+impl Bindings {
+    /// Returns what presses a RetroPad button on a device.
+    pub fn get(&self, device: &BindingDevice, key: &InputKey) -> &[Binding] {
+        match self.binds.iter().find(|x| x.0 == *device && x.1 == *key) {
+            Some(v) => &v.2,
+            None => &[],
+        }
+    }
+
+    /// Returns every turbo key and button, on any device.
+    pub fn get_turbo(&self) -> Vec<Binding> {
+        let mut turbo = Vec::new();
+        for bind in self.binds.iter().flat_map(|x| x.2.iter()) {
+            match bind {
+                &Binding::TurboKey(_) | &Binding::TurboButton(_) if !turbo.contains(bind) => {
+                    turbo.push(*bind)
+                }
+                _ => {}
+            }
+        }
+
+        turbo
+    }
+
+    /// Replaces what presses a RetroPad button on a device.
+    pub fn set(&mut self, device: BindingDevice, key: InputKey, binds: Vec<Binding>) {
+        self.binds.retain(|x| !(x.0 == device && x.1 == key));
+        self.binds.push((device, key, binds));
+    }
+
+    /// Loads bindings for a core (by its library name) and game (by its file name without the
+    /// extension). The defaults are used if the file doesn't exist.
+    pub fn load(path: &str, core: &str, game: Option<&str>) -> io::Result<Bindings> {
+        match File::open(path) {
+            Ok(v) => Bindings::parse(BufReader::new(v), core, game),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(Bindings::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn parse<R: BufRead>(reader: R, core: &str, game: Option<&str>) -> io::Result<Bindings> {
+        // Lines before the first section apply to everything
+        let mut sections = vec![(Scope::Global, Vec::new())];
+
+        for line in reader.lines() {
+            let line = line?;
+            let (name, value) = match split_line(&line) {
+                ("", None) => continue,
+                (header, None) if header.starts_with('[') && header.ends_with(']') => {
+                    match Scope::parse(&header[1..header.len() - 1]) {
+                        Some(scope) => {
+                            sections.push((scope, Vec::new()));
+                            continue;
+                        }
+                        None => return Err(invalid_data(format!("Unknown section: {}", header))),
+                    }
+                }
+                (name, Some(value)) => (name, value),
+                _ => return Err(invalid_data(format!("Expected name = value, got: {}", line))),
+            };
+
+            sections.last_mut().unwrap().1.push(Line::parse(name, value)?);
+        }
+
+        let mut scopes = vec![Scope::Global, Scope::Core(core.to_owned())];
+        if let Some(game) = game {
+            scopes.push(Scope::Game(game.to_owned()));
+        }
+
+        // Later sections replace earlier ones, while lines within a section add up
+        let mut bindings = Bindings::default();
+        for scope in scopes {
+            for &(_, ref lines) in sections.iter().filter(|x| x.0 == scope) {
+                let mut replaced = Vec::new();
+                for line in lines {
+                    match line {
+                        &Line::Bind(device, key, ref binds) => {
+                            if !replaced.contains(&(device, key)) {
+                                bindings.set(device, key, Vec::new());
+                                replaced.push((device, key));
+                            }
+
+                            for bind in binds {
+                                bindings.add(device, key, *bind);
+                            }
+                        }
+                        &Line::TurboPeriod(period) => bindings.turbo.period = period,
+                        &Line::TurboDuty(duty) => bindings.turbo.duty = duty,
+                        &Line::Macro(ref mac) => {
+                            bindings.macros.retain(|x| x.name != mac.name);
+                            bindings.macros.push(mac.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(bindings)
+    }
+
+    fn add(&mut self, device: BindingDevice, key: InputKey, bind: Binding) {
+        match self.binds.iter_mut().find(|x| x.0 == device && x.1 == key) {
+            Some(v) => v.2.push(bind),
+            None => self.binds.push((device, key, vec![bind])),
+        }
+    }
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let mut binds = Vec::new();
+
+        for layout in &[
+            KeyboardLayout::Full,
+            KeyboardLayout::LeftHalf,
+            KeyboardLayout::RightHalf,
+        ] {
+            for key in ALL_KEYS.iter() {
+                binds.push((
+                    BindingDevice::Keyboard(*layout),
+                    *key,
+                    vec![Binding::Key(get_default_key(layout, key))],
+                ));
+            }
+        }
+
+        for key in ALL_KEYS.iter() {
+            binds.push((BindingDevice::Gamepad, *key, vec![Binding::Button(*key)]));
+        }
+
+        Bindings {
+            binds,
+            turbo: TurboConfig::default(),
+            macros: Vec::new(),
+        }
+    }
+}
+
+/// Returns the key which presses a RetroPad button in a layout, unless it has been bound.
+fn get_default_key(layout: &KeyboardLayout, key: &InputKey) -> RetroKey {
+    match layout {
+        &KeyboardLayout::Full => match key {
+            &InputKey::A => RetroKey::A,
+            &InputKey::B => RetroKey::S,
+            &InputKey::X => RetroKey::Z,
+            &InputKey::Y => RetroKey::X,
+            &InputKey::Select => RetroKey::V,
+            &InputKey::Start => RetroKey::B,
+            &InputKey::Up => RetroKey::Up,
+            &InputKey::Down => RetroKey::Down,
+            &InputKey::Left => RetroKey::Left,
+            &InputKey::Right => RetroKey::Right,
+            &InputKey::L => RetroKey::Q,
+            &InputKey::R => RetroKey::W,
+            &InputKey::L2 => RetroKey::Num1,
+            &InputKey::R2 => RetroKey::Num2,
+            &InputKey::L3 => RetroKey::Num3,
+            &InputKey::R3 => RetroKey::Num4,
+        },
+        &KeyboardLayout::LeftHalf => match key {
+            &InputKey::A => RetroKey::G,
+            &InputKey::B => RetroKey::F,
+            &InputKey::X => RetroKey::T,
+            &InputKey::Y => RetroKey::R,
+            &InputKey::Select => RetroKey::Z,
+            &InputKey::Start => RetroKey::X,
+            &InputKey::Up => RetroKey::W,
+            &InputKey::Down => RetroKey::S,
+            &InputKey::Left => RetroKey::A,
+            &InputKey::Right => RetroKey::D,
+            &InputKey::L => RetroKey::Q,
+            &InputKey::R => RetroKey::E,
+            &InputKey::L2 => RetroKey::Num1,
+            &InputKey::R2 => RetroKey::Num2,
+            &InputKey::L3 => RetroKey::Num3,
+            &InputKey::R3 => RetroKey::Num4,
+        },
+        &KeyboardLayout::RightHalf => match key {
+            &InputKey::A => RetroKey::L,
+            &InputKey::B => RetroKey::K,
+            &InputKey::X => RetroKey::O,
+            &InputKey::Y => RetroKey::I,
+            &InputKey::Select => RetroKey::N,
+            &InputKey::Start => RetroKey::M,
+            &InputKey::Up => RetroKey::Up,
+            &InputKey::Down => RetroKey::Down,
+            &InputKey::Left => RetroKey::Left,
+            &InputKey::Right => RetroKey::Right,
+            &InputKey::L => RetroKey::U,
+            &InputKey::R => RetroKey::P,
+            &InputKey::L2 => RetroKey::Num7,
+            &InputKey::R2 => RetroKey::Num8,
+            &InputKey::L3 => RetroKey::Num9,
+            &InputKey::R3 => RetroKey::Num0,
+        },
+    }
+}
+
+/// Returns the name of a button on a device, as used in config files.
+fn get_bind_name(device: &BindingDevice, key: &InputKey) -> String {
+    format!("{}.{}", device.get_name(), format!("{:?}", key).to_lowercase())
+}
+
+/// Replaces the global bindings of some of a device's buttons in a config file, leaving every
+/// other line alone. Buttons which weren't bound before are added to the end of the global
+/// section.
+fn replace_global(
+    config: &str,
+    device: &BindingDevice,
+    binds: &[(InputKey, Vec<Binding>)],
+) -> String {
+    let replaced: Vec<(String, String)> = binds
+        .iter()
+        .map(|&(key, ref binds)| {
+            let names: Vec<String> = binds.iter().map(|x| x.get_name()).collect();
+            let name = get_bind_name(device, &key);
+            let line = format!("{} = {}", name, names.join(", "));
+            (name, line)
+        })
+        .collect();
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut written = vec![false; replaced.len()];
+    // Where the global section ends, ignoring any blank lines after it
+    let mut end_of_global = None;
+    let mut scope = Some(Scope::Global);
+    for line in config.lines() {
+        let (name, value) = split_line(line);
+        if value.is_none() && name.starts_with('[') && name.ends_with(']') {
+            scope = Scope::parse(&name[1..name.len() - 1]);
+        } else if scope == Some(Scope::Global) {
+            if let Some(index) = replaced.iter().position(|x| x.0 == name) {
+                // Later lines for the same button are dropped, as they would replace this one
+                if written[index] {
+                    continue;
+                }
+
+                written[index] = true;
+                lines.push(replaced[index].1.clone());
+                end_of_global = Some(lines.len());
+                continue;
+            }
+        }
+
+        lines.push(line.to_owned());
+        if scope == Some(Scope::Global) && !line.trim().is_empty() {
+            end_of_global = Some(lines.len());
+        }
+    }
+
+    let missing = replaced
+        .iter()
+        .zip(written)
+        .filter(|&(_, written)| !written)
+        .map(|(x, _)| x.1.clone());
+
+    match end_of_global {
+        Some(index) => {
+            let tail = lines.split_off(index);
+            lines.extend(missing);
+            lines.extend(tail);
+        }
+        None => {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push("[global]".to_owned());
+            lines.extend(missing);
+        }
+    }
+
+    let mut output = lines.join("\n");
+    output += "\n";
+    output
+}
+
+/// Saves the bindings of some of a device's buttons to the global section of a config file,
+/// creating it if needed.
+pub fn save(
+    path: &str,
+    device: &BindingDevice,
+    binds: &[(InputKey, Vec<Binding>)],
+) -> io::Result<()> {
+    let mut config = String::new();
+    match File::open(path) {
+        Ok(mut v) => {
+            v.read_to_string(&mut config)?;
+        }
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    File::create(path)?.write_all(replace_global(&config, device, binds).as_bytes())
+}
+
+/// Asks the user to press a key for each RetroPad button in turn.
+pub struct BindWizard {
+    layout: KeyboardLayout,
+    /// The buttons bound so far. Skipped buttons keep their bindings.
+    binds: Vec<(InputKey, Vec<Binding>)>,
+    step: usize,
+}
+
+impl BindWizard {
+    fn prompt(&self) {
+        println!("Press a key for {:?}", ALL_KEYS[self.step]);
+    }
+
+    /// Returns the device being bound.
+    pub fn get_device(&self) -> BindingDevice {
+        BindingDevice::Keyboard(self.layout)
+    }
+
+    /// Handles a key being pressed, binding it to the current button. Escape skips a button.
+    /// Returns the new bindings once every button has had its turn.
+    pub fn key_pressed(&mut self, key: RetroKey) -> Option<Vec<(InputKey, Vec<Binding>)>> {
+        match key {
+            // Keys that can't be identified can't be bound
+            RetroKey::Unknown => return None,
+            RetroKey::Escape => {}
+            _ => self.binds
+                .push((ALL_KEYS[self.step], vec![Binding::Key(key)])),
+        }
+
+        self.step += 1;
+        if self.step == ALL_KEYS.len() {
+            return Some(mem::replace(&mut self.binds, Vec::new()));
+        }
+
+        self.prompt();
+        None
+    }
+
+    pub fn new(layout: KeyboardLayout) -> BindWizard {
+        println!(
+            "Binding {}, Escape skips a button",
+            BindingDevice::Keyboard(layout).get_name()
+        );
+
+        let wizard = BindWizard {
+            layout,
+            binds: Vec::new(),
+            step: 0,
+        };
+        wizard.prompt();
+        wizard
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BindWizard;
+    use super::Binding;
+    use super::BindingDevice;
+    use super::Bindings;
+    use super::MacroBinding;
+    use super::replace_global;
+
+    use input::InputKey;
+    use input::ports::KeyboardLayout;
+
+    use retro_types::RetroKey;
+
+    const CONFIG: &'static str = "
+# Everything
+keyboard.a = X, Space
+gamepad.a = b
+
+macro.hadouken = F1, hadouken.txt
+
+[core:Snes9x]
+keyboard.a = C
+keyboard.a = Return
+gamepad.y = turbo:y
+turbo.period = 4
+macro.hadouken = F2, snes.txt, 1
+
+[game:Super Mario World]
+keyboard.b = Z
+";
+
+    #[test]
+    fn scopes() {
+        let keyboard = BindingDevice::Keyboard(KeyboardLayout::Full);
+
+        let bindings = Bindings::parse(CONFIG.as_bytes(), "Genesis Plus GX", None).unwrap();
+        assert_eq!(
+            bindings.get(&keyboard, &InputKey::A),
+            &[Binding::Key(RetroKey::X), Binding::Key(RetroKey::Space)]
+        );
+        assert_eq!(
+            bindings.get(&BindingDevice::Gamepad, &InputKey::A),
+            &[Binding::Button(InputKey::B)]
+        );
+        assert_eq!(bindings.get(&keyboard, &InputKey::B), &[Binding::Key(RetroKey::S)]);
+        assert_eq!(bindings.macros[0].key, RetroKey::F1);
+
+        let bindings =
+            Bindings::parse(CONFIG.as_bytes(), "Snes9x", Some("Super Mario World")).unwrap();
+        assert_eq!(
+            bindings.get(&keyboard, &InputKey::A),
+            &[Binding::Key(RetroKey::C), Binding::Key(RetroKey::Return)]
+        );
+        assert_eq!(bindings.get(&keyboard, &InputKey::B), &[Binding::Key(RetroKey::Z)]);
+        assert_eq!(
+            bindings.get(&BindingDevice::Gamepad, &InputKey::Y),
+            &[Binding::TurboButton(InputKey::Y)]
+        );
+        assert_eq!(bindings.turbo.period, 4);
+        assert_eq!(
+            bindings.macros,
+            vec![
+                MacroBinding {
+                    name: "hadouken".to_owned(),
+                    key: RetroKey::F2,
+                    path: "snes.txt".to_owned(),
+                    port: 1,
+                },
+            ]
+        );
+
+        assert!(Bindings::parse("turbo.duty = 2".as_bytes(), "Snes9x", None).is_err());
+        assert!(Bindings::parse("macro.x = F1".as_bytes(), "Snes9x", None).is_err());
+        assert!(Bindings::parse("keyboard.a = Nope".as_bytes(), "Snes9x", None).is_err());
+        assert!(Bindings::parse("[cheats]".as_bytes(), "Snes9x", None).is_err());
+    }
+
+    #[test]
+    fn wizard() {
+        let mut wizard = BindWizard::new(KeyboardLayout::LeftHalf);
+        assert_eq!(wizard.key_pressed(RetroKey::I), None);
+        assert_eq!(wizard.key_pressed(RetroKey::Escape), None);
+
+        let mut binds = None;
+        for _ in 2..16 {
+            binds = wizard.key_pressed(RetroKey::Space);
+        }
+
+        let binds = binds.unwrap();
+        assert_eq!(binds.len(), 15);
+        assert_eq!(binds[0], (InputKey::Up, vec![Binding::Key(RetroKey::I)]));
+        assert_eq!(binds[1].0, InputKey::Left);
+    }
+
+    #[test]
+    fn save_global() {
+        let keyboard = BindingDevice::Keyboard(KeyboardLayout::Full);
+        let binds = vec![
+            (InputKey::Up, vec![Binding::Key(RetroKey::I)]),
+            (InputKey::A, vec![Binding::Key(RetroKey::Space)]),
+        ];
+        let config = replace_global(CONFIG, &keyboard, &binds);
+
+        let bindings = Bindings::parse(config.as_bytes(), "Genesis Plus GX", None).unwrap();
+        assert_eq!(bindings.get(&keyboard, &InputKey::Up), &[Binding::Key(RetroKey::I)]);
+        assert_eq!(bindings.get(&keyboard, &InputKey::A), &[Binding::Key(RetroKey::Space)]);
+        assert_eq!(
+            bindings.get(&BindingDevice::Gamepad, &InputKey::A),
+            &[Binding::Button(InputKey::B)]
+        );
+
+        // Other buttons and sections are kept
+        assert!(config.contains("\ngamepad.a = b\n"));
+        assert!(config.contains("[core:Snes9x]\nkeyboard.a = C\n"));
+        assert!(!config.contains("X, Space"));
+
+        // Bindings are edited in place, so saving again doesn't change anything
+        assert_eq!(replace_global(&config, &keyboard, &binds), config);
+        assert_eq!(config.matches("[global]").count(), 0);
+        assert!(config.starts_with(
+            "\n# Everything\nkeyboard.a = Space\ngamepad.a = b\n\nmacro.hadouken = F1, \
+             hadouken.txt\nkeyboard.up = I\n\n[core:Snes9x]\n"
+        ));
+
+        // Files without global bindings are given a section for them
+        let config = replace_global("[core:Snes9x]\nkeyboard.a = C\n", &keyboard, &binds);
+        assert_eq!(
+            config,
+            "[core:Snes9x]\nkeyboard.a = C\n\n[global]\nkeyboard.up = I\nkeyboard.a = Space\n"
+        );
+        assert_eq!(replace_global(&config, &keyboard, &binds), config);
+        assert_eq!(
+            replace_global("", &keyboard, &binds),
+            "[global]\nkeyboard.up = I\nkeyboard.a = Space\n"
+        );
+    }
+}
@@ -0,0 +1,170 @@
+/// Chooses which of the controllers that a core supports (e.g. a multitap or mouse) are
+/// plugged into its ports.
+
+use input::ports::MAX_PORTS;
+use retro_types::RetroControllerDescription;
+use retro_types::RETRO_DEVICE_JOYPAD;
+
+pub struct Controllers {
+    /// The controllers each port supports, as described by the core.
+    ports: Vec<Vec<RetroControllerDescription>>,
+    /// Which of each port's controllers is plugged in, if known.
+    selected: Vec<Option<usize>>,
+    /// Controllers that the user wants, by name or ID, for when the core describes its ports.
+    preferred: Vec<(usize, String)>,
+    /// The port which cycling changes the controller of.
+    cycled_port: usize,
+}
+
+/// Parses the choice of a controller for a port, as `port=controller` (e.g. `1=multitap`).
+/// Controllers are named as the core describes them, or by their device ID.
+pub fn parse_choice(value: &str) -> Result<(usize, String), String> {
+    let mut parts = value.splitn(2, '=');
+
+    let port = match parts.next().map(|x| x.trim().parse::<usize>()) {
+        Some(Ok(v)) if v < MAX_PORTS => v,
+        _ => return Err(format!("Ports must be a number below {}", MAX_PORTS)),
+    };
+
+    match parts.next().map(|x| x.trim()) {
+        Some(name) if !name.is_empty() => Ok((port, name.to_owned())),
+        _ => Err(format!("Expected port=controller, got {}", value)),
+    }
+}
+
+/// Returns if a controller is the one the user asked for, by name (ignoring case) or ID.
+fn is_match(controller: &RetroControllerDescription, name: &str) -> bool {
+    controller.name.eq_ignore_ascii_case(name) || name.parse() == Ok(controller.id)
+}
+
+impl Controllers {
+    /// Stores the controllers that the core supports. Returns the `(port, device)`s which
+    /// should be plugged in, as the user has asked for them.
+    pub fn set_info(&mut self, ports: Vec<Vec<RetroControllerDescription>>) -> Vec<(u32, u32)> {
+        // Cores start with a RetroPad plugged in
+        self.selected = ports
+            .iter()
+            .map(|x| x.iter().position(|x| x.id == RETRO_DEVICE_JOYPAD))
+            .collect();
+        self.ports = ports;
+
+        let mut changes = Vec::new();
+        for &(port, ref name) in &self.preferred {
+            let controllers = match self.ports.get(port) {
+                Some(v) => v,
+                None => {
+                    println!("Core doesn't have a port {} for {}", port, name);
+                    continue;
+                }
+            };
+
+            match controllers.iter().position(|x| is_match(x, name)) {
+                Some(index) => {
+                    self.selected[port] = Some(index);
+                    changes.push((port as u32, controllers[index].id));
+                }
+                None => println!(
+                    "Port {} doesn't support {}, only: {:?}",
+                    port,
+                    name,
+                    controllers.iter().map(|x| &x.name).collect::<Vec<_>>()
+                ),
+            }
+        }
+
+        changes
+    }
+
+    /// Moves on to changing the controller of the next port.
+    pub fn next_port(&mut self) {
+        if self.ports.is_empty() {
+            println!("Core doesn't support changing controllers");
+            return;
+        }
+
+        self.cycled_port = (self.cycled_port + 1) % self.ports.len();
+        println!("Changing the controller of port {}", self.cycled_port);
+    }
+
+    /// Plugs the next controller into the port being changed. Returns the `(port, device)`
+    /// which should be plugged in, if the port has any controllers.
+    pub fn cycle(&mut self) -> Option<(u32, u32)> {
+        let port = self.cycled_port;
+        let controllers = match self.ports.get(port) {
+            Some(v) if !v.is_empty() => v,
+            _ => {
+                println!("Core doesn't support changing controllers");
+                return None;
+            }
+        };
+
+        let index = match self.selected[port] {
+            Some(v) => (v + 1) % controllers.len(),
+            None => 0,
+        };
+        self.selected[port] = Some(index);
+
+        println!("Port {}: {}", port, controllers[index].name);
+        Some((port as u32, controllers[index].id))
+    }
+
+    pub fn new(preferred: Vec<(usize, String)>) -> Controllers {
+        Controllers {
+            ports: Vec::new(),
+            selected: Vec::new(),
+            preferred,
+            cycled_port: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Controllers;
+    use super::parse_choice;
+
+    use retro_types::RetroControllerDescription;
+
+    fn describe(name: &str, id: u32) -> RetroControllerDescription {
+        RetroControllerDescription {
+            name: name.to_owned(),
+            id,
+        }
+    }
+
+    #[test]
+    fn parse_choices() {
+        assert_eq!(parse_choice("1=Multitap"), Ok((1, "Multitap".to_owned())));
+        assert!(parse_choice("99=multitap").is_err());
+        assert!(parse_choice("1=").is_err());
+    }
+
+    #[test]
+    fn preferred_controllers() {
+        let mut controllers =
+            Controllers::new(vec![(0, "multitap".to_owned()), (1, "258".to_owned())]);
+
+        let changes = controllers.set_info(vec![
+            vec![describe("RetroPad", 1), describe("Multitap", 257)],
+            vec![describe("RetroPad", 1), describe("Mouse", 2), describe("Justifier", 258)],
+        ]);
+
+        assert_eq!(changes, vec![(0, 257), (1, 258)]);
+    }
+
+    #[test]
+    fn cycle_controllers() {
+        let mut controllers = Controllers::new(Vec::new());
+        controllers.set_info(vec![
+            vec![describe("RetroPad", 1), describe("Multitap", 257)],
+            vec![describe("RetroPad", 1), describe("Mouse", 2)],
+        ]);
+
+        // Cycling starts after the RetroPad, which is plugged in to begin with
+        assert_eq!(controllers.cycle(), Some((0, 257)));
+        assert_eq!(controllers.cycle(), Some((0, 1)));
+
+        controllers.next_port();
+        assert_eq!(controllers.cycle(), Some((1, 2)));
+    }
+}
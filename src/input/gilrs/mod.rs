@@ -1,26 +1,161 @@
-/*fn main() {
-    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();*/
+/// Reads gamepads through gilrs. Gamepads are mapped to the RetroPad using SDL's controller
+/// database, along with any mappings in `SDL_GAMECONTROLLERCONFIG` or `gamecontrollerdb.txt`.
+
 extern crate gilrs;
 
-use self::gilrs::{Button, Event, Gilrs};
+mod pads;
+
+use self::gilrs::{Gilrs, GilrsBuilder};
+use self::gilrs::ff::{BaseEffect, BaseEffectType, Effect, EffectBuilder, Repeat, Replay,
+                      Ticks};
+
+use self::pads::PadEvent;
+use self::pads::Pads;
 
 use input::InputBackendInfo;
 use input::InputBackend;
+use input::InputAxis;
+use input::InputKey;
+
+use retro_types::RetroRumbleEffect;
 
-struct GLFWBackend {}
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
 
-impl InputBackend for GLFWBackend {
+/// Extra SDL mappings, in the format of SDL_GameControllerDB.
+const MAPPINGS_PATH: &'static str = "gamecontrollerdb.txt";
+
+struct GilrsBackend {
+    gilrs: Gilrs,
+    pads: Pads,
+    /// Effects which are currently playing, and their strength. Dropping an effect stops it.
+    rumble: HashMap<(usize, RetroRumbleEffect), (u16, Effect)>,
+}
+
+impl InputBackend for GilrsBackend {
     fn poll_events(&mut self) {
-        unimplemented!()
+        while let Some(event) = self.gilrs.next_event() {
+            let pad_event = match PadEvent::from(&event.event) {
+                Some(v) => v,
+                None => continue,
+            };
+
+            if self.pads.handle_event(event.id, pad_event) {
+                match pad_event {
+                    PadEvent::Connected => println!(
+                        "Gamepad {} connected: {}",
+                        event.id,
+                        self.gilrs[event.id].name()
+                    ),
+                    _ => {
+                        println!("Gamepad {} disconnected", event.id);
+                        self.rumble.retain(|x, _| x.0 != event.id);
+                    }
+                }
+            }
+        }
+
+        self.gilrs.inc();
+    }
+
+    fn get_gamepads(&self) -> Vec<usize> {
+        self.pads.get_ids()
+    }
+
+    fn is_key_down(&self, gamepad: usize, key: &InputKey) -> bool {
+        self.pads.is_key_down(gamepad, key)
+    }
+
+    fn get_button_value(&self, gamepad: usize, key: &InputKey) -> f32 {
+        self.pads.get_button_value(gamepad, key)
     }
 
-    fn is_key_down(&self, key: &InputKey) -> bool {
-        unimplemented!()
+    fn get_axis(&self, gamepad: usize, axis: &InputAxis) -> f32 {
+        self.pads.get_axis(gamepad, axis)
+    }
+
+    fn set_rumble_state(&mut self, gamepad: usize, effect: RetroRumbleEffect, strength: u16) {
+        // Cores may set the same strength every frame, which shouldn't restart the effect
+        match self.rumble.get(&(gamepad, effect)) {
+            Some(&(current, _)) if current == strength => return,
+            _ => {}
+        }
+
+        // Stop whatever was playing on this motor
+        self.rumble.remove(&(gamepad, effect));
+
+        if strength == 0 {
+            return;
+        }
+
+        match self.gilrs.get(gamepad) {
+            Some(v) if v.is_ff_supported() => {}
+            _ => return,
+        }
+
+        let kind = match effect {
+            RetroRumbleEffect::Strong => BaseEffectType::Strong { magnitude: strength },
+            RetroRumbleEffect::Weak => BaseEffectType::Weak { magnitude: strength },
+        };
+
+        // Rumble until the core tells us otherwise. Without a delay between them, the repeats
+        // join up into one long effect.
+        let result = EffectBuilder::new()
+            .add_effect(BaseEffect {
+                kind,
+                scheduling: Replay {
+                    play_for: Ticks::from_ms(1000),
+                    with_delay: Ticks::from_ms(0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .repeat(Repeat::Infinitely)
+            .gamepads(&[gamepad])
+            .finish(&mut self.gilrs);
+
+        match result {
+            Ok(v) => {
+                v.play().unwrap();
+                self.rumble.insert((gamepad, effect), (strength, v));
+            }
+            Err(e) => println!("Failed to rumble gamepad {}: {}", gamepad, e),
+        }
     }
 }
 
-pub static INFO: InputBackendInfo = InputBackendInfo { name: "GLFW" };
+pub static INFO: InputBackendInfo = InputBackendInfo { name: "gilrs" };
+
+pub fn build() -> Option<Box<InputBackend>> {
+    let mut builder = GilrsBuilder::new();
+
+    let mut mappings = String::new();
+    if let Ok(mut file) = File::open(MAPPINGS_PATH) {
+        match file.read_to_string(&mut mappings) {
+            Ok(_) => builder = builder.add_mappings(&mappings),
+            Err(e) => println!("Failed to read {}: {}", MAPPINGS_PATH, e),
+        }
+    }
+
+    let gilrs = match builder.build() {
+        Ok(v) => v,
+        Err(e) => {
+            println!("Failed to start gilrs: {}", e);
+            return None;
+        }
+    };
+
+    // Gamepads which are already plugged in don't announce themselves
+    let mut pads = Pads::default();
+    for (id, gamepad) in gilrs.gamepads() {
+        println!("Gamepad {} connected: {}", id, gamepad.name());
+        pads.handle_event(id, PadEvent::Connected);
+    }
 
-pub fn build() -> Option<Box<GLFWBackend>> {
-    let mut gilrs = Gilrs::new().unwrap();
+    Some(Box::new(GilrsBackend {
+        gilrs,
+        pads,
+        rumble: HashMap::new(),
+    }))
 }
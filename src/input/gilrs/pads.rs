@@ -0,0 +1,235 @@
+/// Tracks the state of each gamepad from its events, mapping gilrs' buttons and axes (which
+/// follow SDL's layout) onto the RetroPad.
+
+use super::gilrs::{Axis, Button, EventType};
+
+use input::InputAxis;
+use input::InputKey;
+
+use std::collections::HashMap;
+
+/// The number of RetroPad buttons, by joypad ID.
+const BUTTONS: usize = 16;
+
+/// Something which happened to a gamepad, without the details that only gilrs can create.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PadEvent {
+    Connected,
+    Disconnected,
+    ButtonPressed(Button),
+    ButtonReleased(Button),
+    /// How far an analog button (e.g. a trigger) is pressed, from 0 to 1.
+    ButtonChanged(Button, f32),
+    /// The position of an axis, from -1 to 1. Positive Y values are up.
+    AxisChanged(Axis, f32),
+}
+
+impl PadEvent {
+    pub fn from(event: &EventType) -> Option<PadEvent> {
+        Some(match event {
+            &EventType::Connected => PadEvent::Connected,
+            &EventType::Disconnected => PadEvent::Disconnected,
+            &EventType::ButtonPressed(button, _) => PadEvent::ButtonPressed(button),
+            &EventType::ButtonReleased(button, _) => PadEvent::ButtonReleased(button),
+            &EventType::ButtonChanged(button, value, _) => PadEvent::ButtonChanged(button, value),
+            &EventType::AxisChanged(axis, value, _) => PadEvent::AxisChanged(axis, value),
+            _ => return None,
+        })
+    }
+}
+
+/// Returns the RetroPad button at the same place as a gamepad's button. The RetroPad follows
+/// the SNES, where B is at the bottom and A is on the right.
+pub fn to_input_key(button: Button) -> Option<InputKey> {
+    Some(match button {
+        Button::South => InputKey::B,
+        Button::East => InputKey::A,
+        Button::West => InputKey::Y,
+        Button::North => InputKey::X,
+        Button::LeftTrigger => InputKey::L,
+        Button::RightTrigger => InputKey::R,
+        Button::LeftTrigger2 => InputKey::L2,
+        Button::RightTrigger2 => InputKey::R2,
+        Button::LeftThumb => InputKey::L3,
+        Button::RightThumb => InputKey::R3,
+        Button::Select => InputKey::Select,
+        Button::Start => InputKey::Start,
+        Button::DPadUp => InputKey::Up,
+        Button::DPadDown => InputKey::Down,
+        Button::DPadLeft => InputKey::Left,
+        Button::DPadRight => InputKey::Right,
+        _ => return None,
+    })
+}
+
+/// Returns the stick axis for a gamepad's axis, and if it has to be flipped so that positive
+/// values are down.
+fn to_input_axis(axis: Axis) -> Option<(InputAxis, bool)> {
+    Some(match axis {
+        Axis::LeftStickX => (InputAxis::LeftX, false),
+        Axis::LeftStickY => (InputAxis::LeftY, true),
+        Axis::RightStickX => (InputAxis::RightX, false),
+        Axis::RightStickY => (InputAxis::RightY, true),
+        _ => return None,
+    })
+}
+
+fn get_axis_index(axis: &InputAxis) -> usize {
+    match axis {
+        &InputAxis::LeftX => 0,
+        &InputAxis::LeftY => 1,
+        &InputAxis::RightX => 2,
+        &InputAxis::RightY => 3,
+    }
+}
+
+/// Returns the joypad ID of a RetroPad button.
+fn get_key_index(key: &InputKey) -> usize {
+    (0..BUTTONS as u32)
+        .position(|x| InputKey::from(x) == Some(*key))
+        .unwrap()
+}
+
+#[derive(Debug, Default)]
+struct PadState {
+    pressed: [bool; BUTTONS],
+    /// How far buttons are pressed, for those which have told us.
+    values: [Option<f32>; BUTTONS],
+    axes: [f32; 4],
+}
+
+/// The state of every connected gamepad.
+#[derive(Debug, Default)]
+pub struct Pads {
+    pads: HashMap<usize, PadState>,
+}
+
+impl Pads {
+    /// Handles an event from a gamepad. Returns if it was connected or disconnected.
+    pub fn handle_event(&mut self, id: usize, event: PadEvent) -> bool {
+        match event {
+            PadEvent::Connected => {
+                self.pads.insert(id, PadState::default());
+                return true;
+            }
+            PadEvent::Disconnected => {
+                self.pads.remove(&id);
+                return true;
+            }
+            _ => {}
+        }
+
+        let pad = self.pads.entry(id).or_insert_with(PadState::default);
+        match event {
+            PadEvent::ButtonPressed(button) => if let Some(key) = to_input_key(button) {
+                pad.pressed[get_key_index(&key)] = true;
+            },
+            PadEvent::ButtonReleased(button) => if let Some(key) = to_input_key(button) {
+                pad.pressed[get_key_index(&key)] = false;
+            },
+            PadEvent::ButtonChanged(button, value) => if let Some(key) = to_input_key(button) {
+                pad.values[get_key_index(&key)] = Some(value);
+            },
+            PadEvent::AxisChanged(axis, value) => if let Some((axis, flip)) = to_input_axis(axis)
+            {
+                pad.axes[get_axis_index(&axis)] = if flip { -value } else { value };
+            },
+            _ => {}
+        }
+
+        false
+    }
+
+    /// Returns the IDs of the connected gamepads, in order.
+    pub fn get_ids(&self) -> Vec<usize> {
+        let mut ids: Vec<usize> = self.pads.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    pub fn is_key_down(&self, id: usize, key: &InputKey) -> bool {
+        match self.pads.get(&id) {
+            Some(v) => v.pressed[get_key_index(key)],
+            None => false,
+        }
+    }
+
+    /// Returns how far a button is pressed, from 0 to 1. Digital buttons are fully pressed.
+    pub fn get_button_value(&self, id: usize, key: &InputKey) -> f32 {
+        let pad = match self.pads.get(&id) {
+            Some(v) => v,
+            None => return 0.0,
+        };
+
+        let index = get_key_index(key);
+        match pad.values[index] {
+            Some(v) => v,
+            None if pad.pressed[index] => 1.0,
+            None => 0.0,
+        }
+    }
+
+    pub fn get_axis(&self, id: usize, axis: &InputAxis) -> f32 {
+        match self.pads.get(&id) {
+            Some(v) => v.axes[get_axis_index(axis)],
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PadEvent;
+    use super::Pads;
+
+    use super::super::gilrs::{Axis, Button};
+
+    use input::InputAxis;
+    use input::InputKey;
+
+    #[test]
+    fn buttons() {
+        let mut pads = Pads::default();
+        pads.handle_event(3, PadEvent::Connected);
+
+        pads.handle_event(3, PadEvent::ButtonPressed(Button::South));
+        pads.handle_event(3, PadEvent::ButtonPressed(Button::DPadLeft));
+        pads.handle_event(3, PadEvent::ButtonChanged(Button::RightTrigger2, 0.5));
+        assert!(pads.is_key_down(3, &InputKey::B));
+        assert!(pads.is_key_down(3, &InputKey::Left));
+        assert!(!pads.is_key_down(3, &InputKey::A));
+        assert_eq!(pads.get_button_value(3, &InputKey::B), 1.0);
+        assert_eq!(pads.get_button_value(3, &InputKey::R2), 0.5);
+
+        pads.handle_event(3, PadEvent::ButtonReleased(Button::South));
+        assert!(!pads.is_key_down(3, &InputKey::B));
+
+        // Buttons without a place on the RetroPad are ignored
+        pads.handle_event(3, PadEvent::ButtonPressed(Button::Mode));
+    }
+
+    #[test]
+    fn axes() {
+        let mut pads = Pads::default();
+        pads.handle_event(0, PadEvent::Connected);
+
+        pads.handle_event(0, PadEvent::AxisChanged(Axis::LeftStickX, 0.25));
+        pads.handle_event(0, PadEvent::AxisChanged(Axis::RightStickY, 1.0));
+        assert_eq!(pads.get_axis(0, &InputAxis::LeftX), 0.25);
+        assert_eq!(pads.get_axis(0, &InputAxis::RightY), -1.0);
+        assert_eq!(pads.get_axis(0, &InputAxis::LeftY), 0.0);
+    }
+
+    #[test]
+    fn hotplug() {
+        let mut pads = Pads::default();
+        assert!(pads.handle_event(2, PadEvent::Connected));
+        assert!(pads.handle_event(0, PadEvent::Connected));
+        assert_eq!(pads.get_ids(), vec![0, 2]);
+
+        pads.handle_event(2, PadEvent::ButtonPressed(Button::Start));
+        assert!(pads.handle_event(2, PadEvent::Disconnected));
+        assert_eq!(pads.get_ids(), vec![0]);
+        assert!(!pads.is_key_down(2, &InputKey::Start));
+    }
+}
@@ -0,0 +1,200 @@
+/// Tracks the keyboard from window events, and maps its keys onto analog sticks, sensors and
+/// hotkeys. RetroPad buttons are bound separately, see `input::bindings`.
+
+use input::analog::AnalogKey;
+
+use sensor::SensorKey;
+
+use frontend::hotkeys::Hotkey;
+
+use core_protocol::KeyboardEvent;
+
+use retro_types::RetroKey;
+
+pub struct Keyboard {
+    held: Vec<RetroKey>,
+    /// The modifiers that were held during the last key press.
+    modifiers: u16,
+    /// Key presses and typed characters which haven't been taken yet.
+    events: Vec<KeyboardEvent>,
+}
+
+impl Keyboard {
+    /// Handles a key being pressed or released, without telling the core about it.
+    pub fn set_held(&mut self, key: RetroKey, down: bool) {
+        // Keys that cores can't identify can't be told apart either
+        if key != RetroKey::Unknown {
+            if down && !self.held.contains(&key) {
+                self.held.push(key);
+            } else if !down {
+                self.held.remove_item(&key);
+            }
+        }
+    }
+
+    /// Handles a key being pressed or released.
+    pub fn key_event(&mut self, key: RetroKey, down: bool, modifiers: u16) {
+        self.set_held(key, down);
+
+        self.modifiers = modifiers;
+        self.events.push(KeyboardEvent {
+            down,
+            keycode: key,
+            character: 0,
+            modifiers,
+        });
+    }
+
+    /// Handles a character being typed.
+    pub fn character_event(&mut self, character: char) {
+        // Control characters are already described by the key that was pressed
+        if !character.is_control() {
+            self.events.push(KeyboardEvent {
+                down: true,
+                keycode: RetroKey::Unknown,
+                character: character as u32,
+                modifiers: self.modifiers,
+            });
+        }
+    }
+
+    pub fn is_key_down(&self, key: RetroKey) -> bool {
+        self.held.contains(&key)
+    }
+
+    /// Returns the keys which are held.
+    pub fn get_held(&self) -> &[RetroKey] {
+        &self.held
+    }
+
+    /// Returns the keyboard events which have occurred since this was last called.
+    pub fn take_events(&mut self) -> Vec<KeyboardEvent> {
+        self.events.drain(..).collect()
+    }
+
+    pub fn new() -> Keyboard {
+        Keyboard {
+            held: Vec::new(),
+            modifiers: 0,
+            events: Vec::new(),
+        }
+    }
+}
+
+/// Returns the key which pushes an analog stick.
+pub fn get_analog_key(key: &AnalogKey) -> RetroKey {
+    match key {
+        &AnalogKey::LeftStickUp => RetroKey::T,
+        &AnalogKey::LeftStickDown => RetroKey::G,
+        &AnalogKey::LeftStickLeft => RetroKey::F,
+        &AnalogKey::LeftStickRight => RetroKey::H,
+        &AnalogKey::RightStickUp => RetroKey::Keypad8,
+        &AnalogKey::RightStickDown => RetroKey::Keypad2,
+        &AnalogKey::RightStickLeft => RetroKey::Keypad4,
+        &AnalogKey::RightStickRight => RetroKey::Keypad6,
+        &AnalogKey::SlowWalk => RetroKey::LeftShift,
+    }
+}
+
+/// Returns the key which moves an emulated sensor. These are kept clear of the default
+/// bindings, so that pressing a RetroPad button doesn't tilt the device.
+pub fn get_sensor_key(key: &SensorKey) -> RetroKey {
+    match key {
+        &SensorKey::TiltUp => RetroKey::Home,
+        &SensorKey::TiltDown => RetroKey::End,
+        &SensorKey::TiltLeft => RetroKey::Delete,
+        &SensorKey::TiltRight => RetroKey::PageDown,
+        &SensorKey::Darker => RetroKey::Insert,
+        &SensorKey::Brighter => RetroKey::PageUp,
+    }
+}
+
+/// Returns the key which triggers a hotkey.
+pub fn get_hotkey(key: &Hotkey) -> RetroKey {
+    match key {
+        &Hotkey::ToggleEject => RetroKey::F5,
+        &Hotkey::PreviousDisk => RetroKey::F6,
+        &Hotkey::NextDisk => RetroKey::F7,
+        &Hotkey::ToggleGameFocus => RetroKey::ScrollLock,
+        &Hotkey::NextController => RetroKey::F8,
+        &Hotkey::NextControllerPort => RetroKey::F9,
+        &Hotkey::BindKeys => RetroKey::F10,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::get_analog_key;
+    use super::get_hotkey;
+    use super::get_sensor_key;
+
+    use input::InputKey;
+    use input::analog::AnalogKey;
+    use input::bindings::Binding;
+    use input::bindings::BindingDevice;
+    use input::bindings::Bindings;
+    use input::ports::KeyboardLayout;
+
+    use sensor::SensorKey;
+
+    use frontend::hotkeys::Hotkey;
+
+    #[test]
+    fn sensor_keys_are_free() {
+        let bindings = Bindings::default();
+
+        let mut used = Vec::new();
+        for layout in &[
+            KeyboardLayout::Full,
+            KeyboardLayout::LeftHalf,
+            KeyboardLayout::RightHalf,
+        ] {
+            for id in 0..16 {
+                let key = InputKey::from(id).unwrap();
+                used.extend_from_slice(bindings.get(&BindingDevice::Keyboard(*layout), &key));
+            }
+        }
+
+        for key in &[
+            AnalogKey::LeftStickUp,
+            AnalogKey::LeftStickDown,
+            AnalogKey::LeftStickLeft,
+            AnalogKey::LeftStickRight,
+            AnalogKey::RightStickUp,
+            AnalogKey::RightStickDown,
+            AnalogKey::RightStickLeft,
+            AnalogKey::RightStickRight,
+            AnalogKey::SlowWalk,
+        ] {
+            used.push(Binding::Key(get_analog_key(key)));
+        }
+
+        for key in &[
+            Hotkey::ToggleEject,
+            Hotkey::NextDisk,
+            Hotkey::PreviousDisk,
+            Hotkey::ToggleGameFocus,
+            Hotkey::NextController,
+            Hotkey::NextControllerPort,
+            Hotkey::BindKeys,
+        ] {
+            used.push(Binding::Key(get_hotkey(key)));
+        }
+
+        for key in &[
+            SensorKey::TiltUp,
+            SensorKey::TiltDown,
+            SensorKey::TiltLeft,
+            SensorKey::TiltRight,
+            SensorKey::Darker,
+            SensorKey::Brighter,
+        ] {
+            let key = get_sensor_key(key);
+            assert!(
+                !used.contains(&Binding::Key(key)),
+                "{:?} is already in use",
+                key
+            );
+        }
+    }
+}
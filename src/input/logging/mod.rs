@@ -0,0 +1,41 @@
+/// An input backend without any hardware behind it, which logs what cores ask of it.
+/// Useful for testing features such as rumble.
+
+use input::InputBackend;
+use input::InputBackendInfo;
+use input::InputKey;
+use input::InputAxis;
+
+use retro_types::RetroRumbleEffect;
+
+struct LoggingBackend {}
+
+impl InputBackend for LoggingBackend {
+    fn poll_events(&mut self) {}
+
+    /// A single gamepad is always connected, so that it is given to the first port.
+    fn get_gamepads(&self) -> Vec<usize> {
+        vec![0]
+    }
+
+    fn is_key_down(&self, _gamepad: usize, _key: &InputKey) -> bool {
+        false
+    }
+
+    fn get_axis(&self, _gamepad: usize, _axis: &InputAxis) -> f32 {
+        0.0
+    }
+
+    fn set_rumble_state(&mut self, gamepad: usize, effect: RetroRumbleEffect, strength: u16) {
+        println!(
+            "Rumble: gamepad {}, {:?} motor at strength {}",
+            gamepad, effect, strength
+        );
+    }
+}
+
+pub static INFO: InputBackendInfo = InputBackendInfo { name: "Logging" };
+
+pub fn build() -> Option<Box<InputBackend>> {
+    Some(Box::new(LoggingBackend {}))
+}
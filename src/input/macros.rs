@@ -0,0 +1,61 @@
+/// Macros, which play a sequence of RetroPad states on a port when their key is pressed.
+
+use input::InputKey;
+use input::ports;
+
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+
+/// A sequence of RetroPad states, in the format of input scripts (see `ports::parse_steps`).
+/// Polls count from when the macro is triggered, and it finishes after its last step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Macro {
+    steps: Vec<(u64, Vec<InputKey>)>,
+}
+
+impl Macro {
+    pub fn load(path: &str) -> io::Result<Macro> {
+        Macro::parse(BufReader::new(File::open(path)?))
+    }
+
+    pub fn parse<R: BufRead>(reader: R) -> io::Result<Macro> {
+        Ok(Macro {
+            steps: ports::parse_steps(reader)?,
+        })
+    }
+
+    /// Returns the keys held on a poll since the macro was triggered, or None once it has
+    /// finished.
+    pub fn get_keys(&self, poll: u64) -> Option<&[InputKey]> {
+        match self.steps.last() {
+            Some(&(last, _)) if poll <= last => {}
+            _ => return None,
+        }
+
+        Some(match self.steps.iter().rev().find(|x| x.0 <= poll) {
+            Some(&(_, ref keys)) => keys,
+            None => &[],
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Macro;
+
+    use input::InputKey;
+
+    #[test]
+    fn steps() {
+        let script = "1 Down\n3 Down Right\n4 Right Y\n6\n";
+        let mac = Macro::parse(script.as_bytes()).unwrap();
+
+        assert_eq!(mac.get_keys(0), Some(&[][..]));
+        assert_eq!(mac.get_keys(2), Some(&[InputKey::Down][..]));
+        assert_eq!(mac.get_keys(5), Some(&[InputKey::Right, InputKey::Y][..]));
+        assert_eq!(mac.get_keys(6), Some(&[][..]));
+        assert_eq!(mac.get_keys(7), None);
+    }
+}
@@ -3,7 +3,25 @@
 #[cfg(feature = "input_gilrs")]
 pub mod gilrs;
 
+pub mod aggregator;
+pub mod analog;
+pub mod bindings;
+pub mod controllers;
+pub mod keyboard;
+pub mod logging;
+pub mod macros;
+pub mod mouse;
+pub mod ports;
+pub mod snapshot;
+pub mod turbo;
+
+use input::mouse::MouseButton;
+
+use retro_types::RetroKey;
+use retro_types::RetroRumbleEffect;
+
 /// Keys that can be pressed on a controller/"RetroPad".
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum InputKey {
     A,
     B,
@@ -23,6 +41,87 @@ pub enum InputKey {
     R3,
 }
 
+impl InputKey {
+    /// Converts from a raw joypad ID, as used by the LibRetro API.
+    pub fn from(id: u32) -> Option<InputKey> {
+        Some(match id {
+            0 => InputKey::B,
+            1 => InputKey::Y,
+            2 => InputKey::Select,
+            3 => InputKey::Start,
+            4 => InputKey::Up,
+            5 => InputKey::Down,
+            6 => InputKey::Left,
+            7 => InputKey::Right,
+            8 => InputKey::A,
+            9 => InputKey::X,
+            10 => InputKey::L,
+            11 => InputKey::R,
+            12 => InputKey::L2,
+            13 => InputKey::R2,
+            14 => InputKey::L3,
+            15 => InputKey::R3,
+            _ => return None,
+        })
+    }
+
+    /// Parses the name of a key (e.g. `Start`), ignoring case.
+    pub fn from_name(name: &str) -> Option<InputKey> {
+        Some(match name.to_lowercase().as_str() {
+            "a" => InputKey::A,
+            "b" => InputKey::B,
+            "x" => InputKey::X,
+            "y" => InputKey::Y,
+            "select" => InputKey::Select,
+            "start" => InputKey::Start,
+            "up" => InputKey::Up,
+            "down" => InputKey::Down,
+            "left" => InputKey::Left,
+            "right" => InputKey::Right,
+            "l" => InputKey::L,
+            "r" => InputKey::R,
+            "l2" => InputKey::L2,
+            "r2" => InputKey::R2,
+            "l3" => InputKey::L3,
+            "r3" => InputKey::R3,
+            _ => return None,
+        })
+    }
+}
+
+/// Axes of the analog sticks on a controller. Positive values are right and down.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InputAxis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+}
+
+/// Input received by the window, which renderers report as it arrives.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputEvent {
+    /// A key was pressed or released, with the modifiers (`RETROKMOD_*`) held at the time.
+    Key {
+        key: RetroKey,
+        down: bool,
+        modifiers: u16,
+    },
+    /// A character was typed.
+    Character(char),
+    /// The cursor moved to a position over content (see `MouseState::position`).
+    CursorMoved(f64, f64),
+    /// The cursor left the window.
+    CursorLeft,
+    /// The mouse moved, regardless of the edges of the window.
+    MouseMotion(f64, f64),
+    MouseButton { button: MouseButton, down: bool },
+    /// The mouse wheel scrolled. Positive values are up and right.
+    MouseWheel(f64, f64),
+    /// The window gained or lost focus.
+    Focused(bool),
+}
+
 #[derive(Debug)]
 pub struct InputBackendInfo {
     name: &'static str,
@@ -31,12 +130,35 @@ pub struct InputBackendInfo {
 pub trait InputBackend {
     fn poll_events(&mut self);
 
-    fn is_key_down(&self, key: &InputKey) -> bool;
+    /// Returns the IDs of the gamepads which are connected. IDs stay the same while a
+    /// gamepad is connected.
+    fn get_gamepads(&self) -> Vec<usize>;
+
+    fn is_key_down(&self, gamepad: usize, key: &InputKey) -> bool;
+
+    /// Returns how far a button is pressed, from 0 to 1. Analog triggers are L2 and R2.
+    fn get_button_value(&self, gamepad: usize, key: &InputKey) -> f32 {
+        if self.is_key_down(gamepad, key) {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the raw position of a stick along an axis, from -1 to 1.
+    fn get_axis(&self, gamepad: usize, axis: &InputAxis) -> f32;
+
+    /// Sets the strength of a rumble motor on a gamepad.
+    fn set_rumble_state(&mut self, gamepad: usize, effect: RetroRumbleEffect, strength: u16);
 }
 
-static AVAILABLE_BACKENDS: &'static [(&'static InputBackendInfo, fn() -> Box<InputBackend>)] = &[
+static AVAILABLE_BACKENDS: &'static [(
+    &'static InputBackendInfo,
+    fn() -> Option<Box<InputBackend>>,
+)] = &[
     #[cfg(feature = "input_gilrs")]
     (&gilrs::INFO, gilrs::build),
+    (&logging::INFO, logging::build),
 ];
 
 /// Builds a new renderer with the specified properties.
@@ -44,7 +166,10 @@ pub fn build() -> Option<Box<InputBackend>> {
     for &(ref info, ref function) in AVAILABLE_BACKENDS {
         println!("Attempting to load input core: {:?}", info);
 
-        return Some(function());
+        // Backends can fail if their devices can't be opened
+        if let Some(backend) = function() {
+            return Some(backend);
+        }
     }
 
     return None;
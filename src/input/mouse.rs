@@ -0,0 +1,168 @@
+/// Answers mouse, pointer and lightgun queries from the state of the mouse over the window.
+
+use retro_types::*;
+
+/// Buttons on a mouse which can be read by cores.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Button4,
+    Button5,
+}
+
+/// The state of the mouse at the last poll.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct MouseState {
+    /// Where the cursor is over content, from (-1, -1) at the top-left to (1, 1) at the
+    /// bottom-right. Positions beyond this are outside of content (e.g. on the letterbox).
+    /// `None` if the cursor isn't over the window.
+    pub position: Option<(f64, f64)>,
+    /// Movement since the previous poll.
+    pub delta: (f64, f64),
+    /// Scrolling since the previous poll. Positive values are up and right.
+    pub wheel: (f64, f64),
+    pub buttons: Vec<MouseButton>,
+}
+
+/// The largest coordinate of a pointer, at the bottom-right of content.
+const POINTER_MAX: f64 = 0x7fff as f64;
+
+/// The coordinate of a pointer which is outside of content.
+const POINTER_OFFSCREEN: i16 = -0x8000;
+
+/// Clamps movement to fit into the values which can be returned to the core.
+fn to_delta(value: f64) -> i16 {
+    value.round().max(i16::min_value() as f64).min(i16::max_value() as f64) as i16
+}
+
+impl MouseState {
+    fn is_down(&self, button: MouseButton) -> bool {
+        self.buttons.contains(&button)
+    }
+
+    /// Returns where the cursor is in the coordinates of pointers, if it is over content.
+    fn get_pointer_position(&self) -> Option<(i16, i16)> {
+        match self.position {
+            Some((x, y)) if x.abs() <= 1.0 && y.abs() <= 1.0 => Some((
+                (x * POINTER_MAX).round() as i16,
+                (y * POINTER_MAX).round() as i16,
+            )),
+            _ => None,
+        }
+    }
+
+    /// Answers a query for `RETRO_DEVICE_MOUSE`.
+    pub fn get_mouse(&self, id: u32) -> i16 {
+        let value = match id {
+            RETRO_DEVICE_ID_MOUSE_X => return to_delta(self.delta.0),
+            RETRO_DEVICE_ID_MOUSE_Y => return to_delta(self.delta.1),
+            RETRO_DEVICE_ID_MOUSE_LEFT => self.is_down(MouseButton::Left),
+            RETRO_DEVICE_ID_MOUSE_RIGHT => self.is_down(MouseButton::Right),
+            RETRO_DEVICE_ID_MOUSE_MIDDLE => self.is_down(MouseButton::Middle),
+            RETRO_DEVICE_ID_MOUSE_BUTTON_4 => self.is_down(MouseButton::Button4),
+            RETRO_DEVICE_ID_MOUSE_BUTTON_5 => self.is_down(MouseButton::Button5),
+            RETRO_DEVICE_ID_MOUSE_WHEELUP => self.wheel.1 > 0.0,
+            RETRO_DEVICE_ID_MOUSE_WHEELDOWN => self.wheel.1 < 0.0,
+            RETRO_DEVICE_ID_MOUSE_HORIZ_WHEELUP => self.wheel.0 > 0.0,
+            RETRO_DEVICE_ID_MOUSE_HORIZ_WHEELDOWN => self.wheel.0 < 0.0,
+            _ => false,
+        };
+
+        value as i16
+    }
+
+    /// Answers a query for `RETRO_DEVICE_POINTER`. The cursor is the only pointer, and it is
+    /// pressed by the left mouse button.
+    pub fn get_pointer(&self, index: u32, id: u32) -> i16 {
+        let position = match index {
+            0 => self.get_pointer_position(),
+            _ => None,
+        };
+
+        match (id, position) {
+            (RETRO_DEVICE_ID_POINTER_X, Some((x, _))) => x,
+            (RETRO_DEVICE_ID_POINTER_Y, Some((_, y))) => y,
+            (RETRO_DEVICE_ID_POINTER_X, None) | (RETRO_DEVICE_ID_POINTER_Y, None) => {
+                POINTER_OFFSCREEN
+            }
+            (RETRO_DEVICE_ID_POINTER_PRESSED, Some(_)) => self.is_down(MouseButton::Left) as i16,
+            (RETRO_DEVICE_ID_POINTER_COUNT, _) => self.is_down(MouseButton::Left) as i16,
+            (RETRO_DEVICE_ID_POINTER_IS_OFFSCREEN, position) => position.is_none() as i16,
+            _ => 0,
+        }
+    }
+
+    /// Answers a query for `RETRO_DEVICE_LIGHTGUN`. The gun aims with the cursor, fires with
+    /// the left mouse button and reloads with the right.
+    pub fn get_lightgun(&self, id: u32) -> i16 {
+        let position = self.get_pointer_position();
+
+        let value = match id {
+            RETRO_DEVICE_ID_LIGHTGUN_SCREEN_X => {
+                return position.map(|x| x.0).unwrap_or(POINTER_OFFSCREEN)
+            }
+            RETRO_DEVICE_ID_LIGHTGUN_SCREEN_Y => {
+                return position.map(|x| x.1).unwrap_or(POINTER_OFFSCREEN)
+            }
+            RETRO_DEVICE_ID_LIGHTGUN_X => return to_delta(self.delta.0),
+            RETRO_DEVICE_ID_LIGHTGUN_Y => return to_delta(self.delta.1),
+            RETRO_DEVICE_ID_LIGHTGUN_IS_OFFSCREEN => position.is_none(),
+            RETRO_DEVICE_ID_LIGHTGUN_TRIGGER => self.is_down(MouseButton::Left),
+            RETRO_DEVICE_ID_LIGHTGUN_RELOAD => self.is_down(MouseButton::Right),
+            RETRO_DEVICE_ID_LIGHTGUN_AUX_A => self.is_down(MouseButton::Middle),
+            RETRO_DEVICE_ID_LIGHTGUN_AUX_B => self.is_down(MouseButton::Button4),
+            RETRO_DEVICE_ID_LIGHTGUN_AUX_C => self.is_down(MouseButton::Button5),
+            _ => false,
+        };
+
+        value as i16
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MouseButton;
+    use super::MouseState;
+
+    use retro_types::*;
+
+    #[test]
+    fn pointer_coordinates() {
+        let mut state = MouseState::default();
+        state.position = Some((-1.0, 0.5));
+        state.buttons.push(MouseButton::Left);
+
+        assert_eq!(state.get_pointer(0, RETRO_DEVICE_ID_POINTER_X), -0x7fff);
+        assert_eq!(state.get_pointer(0, RETRO_DEVICE_ID_POINTER_Y), 0x4000);
+        assert_eq!(state.get_pointer(0, RETRO_DEVICE_ID_POINTER_PRESSED), 1);
+        assert_eq!(state.get_pointer(1, RETRO_DEVICE_ID_POINTER_PRESSED), 0);
+    }
+
+    #[test]
+    fn offscreen_lightgun() {
+        let mut state = MouseState::default();
+
+        // On the letterbox, beside content
+        state.position = Some((1.2, 0.0));
+        assert_eq!(state.get_lightgun(RETRO_DEVICE_ID_LIGHTGUN_IS_OFFSCREEN), 1);
+        assert_eq!(state.get_lightgun(RETRO_DEVICE_ID_LIGHTGUN_SCREEN_X), -0x8000);
+
+        state.position = Some((0.0, 0.0));
+        assert_eq!(state.get_lightgun(RETRO_DEVICE_ID_LIGHTGUN_IS_OFFSCREEN), 0);
+        assert_eq!(state.get_lightgun(RETRO_DEVICE_ID_LIGHTGUN_SCREEN_X), 0);
+    }
+
+    #[test]
+    fn mouse_movement() {
+        let mut state = MouseState::default();
+        state.delta = (3.4, -100000.0);
+        state.wheel = (0.0, -1.0);
+
+        assert_eq!(state.get_mouse(RETRO_DEVICE_ID_MOUSE_X), 3);
+        assert_eq!(state.get_mouse(RETRO_DEVICE_ID_MOUSE_Y), -0x8000);
+        assert_eq!(state.get_mouse(RETRO_DEVICE_ID_MOUSE_WHEELDOWN), 1);
+        assert_eq!(state.get_mouse(RETRO_DEVICE_ID_MOUSE_WHEELUP), 0);
+    }
+}
@@ -0,0 +1,338 @@
+/// Assigns devices to the ports of the core, so that each player has their own controller.
+///
+/// Ports which haven't been assigned a device by the user are automatic: the keyboard belongs
+/// to the first, and gamepads are given to the first automatic port without one as they are
+/// connected.
+
+use input::InputKey;
+
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+
+/// The most ports which cores are told they can use.
+pub const MAX_PORTS: usize = 8;
+
+/// Which keys of the keyboard act as a RetroPad.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum KeyboardLayout {
+    Full,
+    /// WASD and the keys around them, for sharing the keyboard with another player.
+    LeftHalf,
+    /// The arrow keys and the keys above them, for sharing the keyboard with another player.
+    RightHalf,
+}
+
+/// A device which the user has chosen for a port.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PortDeviceType {
+    /// Nothing, so the port is never pressed.
+    None,
+    Keyboard(KeyboardLayout),
+    /// The gamepad with the specified ID from the input backend.
+    Gamepad(usize),
+    /// RetroPad states replayed from a file.
+    Script(String),
+}
+
+impl PortDeviceType {
+    /// Parses a device, as used on the command line (e.g. `gamepad:1`).
+    pub fn parse(value: &str) -> Result<PortDeviceType, String> {
+        Ok(match value {
+            "none" => PortDeviceType::None,
+            "keyboard" => PortDeviceType::Keyboard(KeyboardLayout::Full),
+            "keyboard-left" => PortDeviceType::Keyboard(KeyboardLayout::LeftHalf),
+            "keyboard-right" => PortDeviceType::Keyboard(KeyboardLayout::RightHalf),
+            _ if value.starts_with("gamepad:") => match value["gamepad:".len()..].parse() {
+                Ok(v) => PortDeviceType::Gamepad(v),
+                Err(_) => return Err(format!("Bad gamepad number in {}", value)),
+            },
+            _ if value.starts_with("script:") => {
+                PortDeviceType::Script(value["script:".len()..].to_owned())
+            }
+            _ => return Err(format!("Unknown port device: {}", value)),
+        })
+    }
+}
+
+/// Parses the assignment of a device to a port, as `port=device` (e.g. `1=keyboard-right`).
+pub fn parse_assignment(value: &str) -> Result<(usize, PortDeviceType), String> {
+    let mut parts = value.splitn(2, '=');
+
+    let port = match parts.next().map(|x| x.trim().parse::<usize>()) {
+        Some(Ok(v)) if v < MAX_PORTS => v,
+        _ => return Err(format!("Ports must be a number below {}", MAX_PORTS)),
+    };
+
+    match parts.next() {
+        Some(device) => Ok((port, PortDeviceType::parse(device.trim())?)),
+        None => Err(format!("Expected port=device, got {}", value)),
+    }
+}
+
+/// Parses a sequence of RetroPad states. Each line holds the poll to start on followed by the
+/// keys which are held from then on, e.g. `120 Start A`. `#` starts a comment. Steps are
+/// returned in order.
+pub fn parse_steps<R: BufRead>(reader: R) -> io::Result<Vec<(u64, Vec<InputKey>)>> {
+    let mut steps = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut words = line.split_whitespace();
+
+        let poll = words.next().unwrap().parse::<u64>().map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("Bad poll in: {}", line))
+        })?;
+
+        let mut keys = Vec::new();
+        for word in words {
+            match InputKey::from_name(word) {
+                Some(v) => keys.push(v),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Unknown key: {}", word),
+                    ))
+                }
+            }
+        }
+
+        steps.push((poll, keys));
+    }
+
+    steps.sort_by_key(|x| x.0);
+    Ok(steps)
+}
+
+/// Replays RetroPad states from a file, in the format read by `parse_steps`.
+pub struct ScriptedPad {
+    steps: Vec<(u64, Vec<InputKey>)>,
+    polls: u64,
+    held: Vec<InputKey>,
+}
+
+impl ScriptedPad {
+    pub fn load(path: &str) -> io::Result<ScriptedPad> {
+        ScriptedPad::parse(BufReader::new(File::open(path)?))
+    }
+
+    pub fn parse<R: BufRead>(reader: R) -> io::Result<ScriptedPad> {
+        Ok(ScriptedPad {
+            steps: parse_steps(reader)?,
+            polls: 0,
+            held: Vec::new(),
+        })
+    }
+
+    /// Moves on to the next poll.
+    pub fn poll(&mut self) {
+        let polls = self.polls;
+        if let Some(&(_, ref keys)) = self.steps.iter().rev().find(|x| x.0 <= polls) {
+            self.held = keys.clone();
+        }
+
+        self.polls += 1;
+    }
+
+    pub fn is_key_down(&self, key: &InputKey) -> bool {
+        self.held.contains(key)
+    }
+}
+
+/// A device plugged into a port.
+pub enum PortDevice {
+    Keyboard(KeyboardLayout),
+    Gamepad(usize),
+    Script(ScriptedPad),
+}
+
+struct Port {
+    /// Input from all devices is merged.
+    devices: Vec<PortDevice>,
+    /// If the user hasn't chosen a device, so gamepads can be assigned as they connect.
+    automatic: bool,
+}
+
+/// The devices plugged into each port.
+pub struct Ports {
+    ports: Vec<Port>,
+}
+
+impl Ports {
+    /// Returns the devices plugged into a port.
+    pub fn get_devices(&self, port: u32) -> &[PortDevice] {
+        match self.ports.get(port as usize) {
+            Some(v) => &v.devices,
+            None => &[],
+        }
+    }
+
+    /// Returns the gamepads plugged into a port.
+    pub fn get_gamepads(&self, port: u32) -> Vec<usize> {
+        self.get_devices(port)
+            .iter()
+            .filter_map(|x| match x {
+                &PortDevice::Gamepad(id) => Some(id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Assigns newly connected gamepads to automatic ports, and unplugs those which have
+    /// been disconnected from them. Gamepads the user assigned stay on their port.
+    pub fn update_gamepads(&mut self, connected: &[usize]) {
+        for (index, port) in self.ports.iter_mut().enumerate() {
+            if !port.automatic {
+                continue;
+            }
+
+            port.devices.retain(|x| match x {
+                &PortDevice::Gamepad(id) if !connected.contains(&id) => {
+                    println!("Gamepad {} disconnected from port {}", id, index);
+                    false
+                }
+                _ => true,
+            });
+        }
+
+        for &id in connected {
+            let assigned = self.ports.iter().any(|x| {
+                x.devices.iter().any(|x| match x {
+                    &PortDevice::Gamepad(v) => v == id,
+                    _ => false,
+                })
+            });
+            if assigned {
+                continue;
+            }
+
+            let free = self.ports.iter_mut().enumerate().find(|&(_, ref x)| {
+                x.automatic && !x.devices.iter().any(|x| match x {
+                    &PortDevice::Gamepad(_) => true,
+                    _ => false,
+                })
+            });
+
+            if let Some((index, port)) = free {
+                println!("Gamepad {} connected to port {}", id, index);
+                port.devices.push(PortDevice::Gamepad(id));
+            }
+        }
+    }
+
+    /// Advances scripted devices. Called each time input is polled.
+    pub fn poll(&mut self) {
+        for port in &mut self.ports {
+            for device in &mut port.devices {
+                if let &mut PortDevice::Script(ref mut script) = device {
+                    script.poll();
+                }
+            }
+        }
+    }
+
+    /// Builds ports from the devices the user has assigned to them.
+    pub fn new(assignments: &[(usize, PortDeviceType)]) -> Ports {
+        let mut ports: Vec<Port> = (0..MAX_PORTS)
+            .map(|_| Port {
+                devices: Vec::new(),
+                automatic: true,
+            })
+            .collect();
+
+        for &(index, ref device) in assignments {
+            let port = &mut ports[index];
+            port.automatic = false;
+
+            match device {
+                &PortDeviceType::None => {}
+                &PortDeviceType::Keyboard(layout) => {
+                    port.devices.push(PortDevice::Keyboard(layout))
+                }
+                &PortDeviceType::Gamepad(id) => port.devices.push(PortDevice::Gamepad(id)),
+                &PortDeviceType::Script(ref path) => match ScriptedPad::load(path) {
+                    Ok(v) => port.devices.push(PortDevice::Script(v)),
+                    Err(e) => println!("Failed to load input script {}: {}", path, e),
+                },
+            }
+        }
+
+        // The keyboard belongs to the first player, unless it has been given to someone
+        let keyboard_assigned = assignments.iter().any(|x| match x.1 {
+            PortDeviceType::Keyboard(_) => true,
+            _ => false,
+        });
+        if !keyboard_assigned && ports[0].automatic {
+            ports[0]
+                .devices
+                .push(PortDevice::Keyboard(KeyboardLayout::Full));
+        }
+
+        Ports { ports }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::KeyboardLayout;
+    use super::PortDeviceType;
+    use super::Ports;
+    use super::ScriptedPad;
+    use super::parse_assignment;
+
+    use input::InputKey;
+
+    #[test]
+    fn parse_assignments() {
+        assert_eq!(
+            parse_assignment("1=keyboard-right"),
+            Ok((1, PortDeviceType::Keyboard(KeyboardLayout::RightHalf)))
+        );
+        assert_eq!(
+            parse_assignment("0=gamepad:2"),
+            Ok((0, PortDeviceType::Gamepad(2)))
+        );
+        assert!(parse_assignment("99=keyboard").is_err());
+        assert!(parse_assignment("1=joystick").is_err());
+    }
+
+    #[test]
+    fn hotplug() {
+        let mut ports = Ports::new(&[(1, PortDeviceType::Keyboard(KeyboardLayout::LeftHalf))]);
+
+        ports.update_gamepads(&[4, 7]);
+        assert_eq!(ports.get_gamepads(0), vec![4]);
+        assert_eq!(ports.get_gamepads(1), Vec::<usize>::new());
+        assert_eq!(ports.get_gamepads(2), vec![7]);
+
+        // The first port is free again once its gamepad is unplugged
+        ports.update_gamepads(&[7]);
+        assert_eq!(ports.get_gamepads(0), Vec::<usize>::new());
+        ports.update_gamepads(&[7, 5]);
+        assert_eq!(ports.get_gamepads(0), vec![5]);
+        assert_eq!(ports.get_gamepads(2), vec![7]);
+    }
+
+    #[test]
+    fn scripted_pad() {
+        let script = "# Skip the intro\n0\n2 Start\n3 A b # Jump\n";
+        let mut pad = ScriptedPad::parse(script.as_bytes()).unwrap();
+
+        let mut held = Vec::new();
+        for _ in 0..4 {
+            pad.poll();
+            held.push((pad.is_key_down(&InputKey::Start), pad.is_key_down(&InputKey::B)));
+        }
+
+        assert_eq!(
+            held,
+            vec![(false, false), (false, false), (true, false), (false, true)]
+        );
+    }
+}
@@ -0,0 +1,129 @@
+/// The state of every device plugged into the core, taken when it polls input. The frontend
+/// sends it to the backend in response to the poll, which answers all of the core's queries
+/// until the next poll from it. This keeps them in agreement, and saves a round trip for each.
+
+use input::mouse::MouseState;
+
+use retro_types::*;
+
+/// The number of RetroPad buttons, by joypad ID.
+pub const JOYPAD_BUTTONS: usize = 16;
+
+/// What a port's devices were doing, in the ranges used by cores.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct PortSnapshot {
+    /// Held RetroPad buttons, as bits by joypad ID.
+    pub joypad: u16,
+    /// The left stick's X and Y, then the right stick's.
+    pub sticks: [i16; 4],
+    /// How far each RetroPad button is pressed, by joypad ID.
+    pub buttons: [i16; JOYPAD_BUTTONS],
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct InputSnapshot {
+    pub ports: Vec<PortSnapshot>,
+    /// Held keys on the keyboard, as `RetroKey` values.
+    pub keys: Vec<u32>,
+    /// The mouse, which belongs to the first port.
+    pub mouse: MouseState,
+}
+
+impl InputSnapshot {
+    /// Answers a query from the core, as given to `retro_input_state`.
+    pub fn get_state(&self, port: u32, device: u32, index: u32, id: u32) -> i16 {
+        let port_state = match self.ports.get(port as usize) {
+            Some(v) => v,
+            None => return 0,
+        };
+
+        match device & RETRO_DEVICE_MASK {
+            RETRO_DEVICE_JOYPAD if id == RETRO_DEVICE_ID_JOYPAD_MASK => port_state.joypad as i16,
+            RETRO_DEVICE_JOYPAD if (id as usize) < JOYPAD_BUTTONS => {
+                ((port_state.joypad >> id) & 1) as i16
+            }
+            RETRO_DEVICE_ANALOG => match (index, id) {
+                (RETRO_DEVICE_INDEX_ANALOG_BUTTON, id) => match port_state.buttons.get(id as usize) {
+                    Some(v) => *v,
+                    None => 0,
+                },
+                (RETRO_DEVICE_INDEX_ANALOG_LEFT, RETRO_DEVICE_ID_ANALOG_X) => port_state.sticks[0],
+                (RETRO_DEVICE_INDEX_ANALOG_LEFT, RETRO_DEVICE_ID_ANALOG_Y) => port_state.sticks[1],
+                (RETRO_DEVICE_INDEX_ANALOG_RIGHT, RETRO_DEVICE_ID_ANALOG_X) => port_state.sticks[2],
+                (RETRO_DEVICE_INDEX_ANALOG_RIGHT, RETRO_DEVICE_ID_ANALOG_Y) => port_state.sticks[3],
+                _ => 0,
+            },
+            RETRO_DEVICE_KEYBOARD => self.keys.contains(&id) as i16,
+            // There is only one mouse, which is shared by whichever ports the core reads it on
+            RETRO_DEVICE_MOUSE => self.mouse.get_mouse(id),
+            RETRO_DEVICE_POINTER => self.mouse.get_pointer(index, id),
+            RETRO_DEVICE_LIGHTGUN => self.mouse.get_lightgun(id),
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::InputSnapshot;
+    use super::PortSnapshot;
+
+    use input::mouse::MouseButton;
+
+    use retro_types::*;
+
+    #[test]
+    fn port_queries() {
+        let mut port = PortSnapshot::default();
+        port.joypad = 1 << 8 | 1 << 3;
+        port.sticks[3] = -0x7fff;
+        port.buttons[12] = 0x4000;
+
+        let mut snapshot = InputSnapshot {
+            ports: vec![PortSnapshot::default(), port],
+            keys: vec![RetroKey::Space as u32],
+            ..InputSnapshot::default()
+        };
+        snapshot.mouse.position = Some((0.0, 0.0));
+        snapshot.mouse.buttons.push(MouseButton::Left);
+
+        assert_eq!(snapshot.get_state(1, RETRO_DEVICE_JOYPAD, 0, 8), 1);
+        assert_eq!(snapshot.get_state(1, RETRO_DEVICE_JOYPAD, 0, 0), 0);
+        assert_eq!(snapshot.get_state(0, RETRO_DEVICE_JOYPAD, 0, 8), 0);
+        assert_eq!(
+            snapshot.get_state(1, RETRO_DEVICE_JOYPAD, 0, RETRO_DEVICE_ID_JOYPAD_MASK),
+            1 << 8 | 1 << 3
+        );
+        assert_eq!(
+            snapshot.get_state(
+                1,
+                RETRO_DEVICE_ANALOG,
+                RETRO_DEVICE_INDEX_ANALOG_RIGHT,
+                RETRO_DEVICE_ID_ANALOG_Y
+            ),
+            -0x7fff
+        );
+        assert_eq!(
+            snapshot.get_state(1, RETRO_DEVICE_ANALOG, RETRO_DEVICE_INDEX_ANALOG_BUTTON, 12),
+            0x4000
+        );
+        assert_eq!(
+            snapshot.get_state(0, RETRO_DEVICE_KEYBOARD, 0, RetroKey::Space as u32),
+            1
+        );
+
+        // Light guns can be plugged into any port, such as a Super Scope on the second
+        assert_eq!(
+            snapshot.get_state(1, RETRO_DEVICE_LIGHTGUN, 0, RETRO_DEVICE_ID_LIGHTGUN_TRIGGER),
+            1
+        );
+        assert_eq!(
+            snapshot.get_state(1, RETRO_DEVICE_LIGHTGUN, 0, RETRO_DEVICE_ID_LIGHTGUN_IS_OFFSCREEN),
+            0
+        );
+        assert_eq!(snapshot.get_state(1, RETRO_DEVICE_MOUSE, 0, RETRO_DEVICE_ID_MOUSE_LEFT), 1);
+
+        // Ports which the core was never told about are never pressed
+        assert_eq!(snapshot.get_state(9, RETRO_DEVICE_JOYPAD, 0, 8), 0);
+    }
+}
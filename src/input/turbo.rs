@@ -0,0 +1,116 @@
+/// Auto-fire, which repeatedly presses and releases a button while it is held.
+
+/// How quickly turbo buttons press and release.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TurboConfig {
+    /// How many polls each press and release takes.
+    pub period: u32,
+    /// How much of each period the button is pressed for, from 0 to 1.
+    pub duty: f32,
+}
+
+impl TurboConfig {
+    /// Returns if turbo buttons are pressed a number of polls after they were first held.
+    /// Every press lasts at least one poll, and every release does too, so that cores see
+    /// each press.
+    pub fn is_pressed(&self, poll: u64) -> bool {
+        let period = self.period.max(2) as u64;
+        let pressed = ((period as f32 * self.duty).round() as u64)
+            .max(1)
+            .min(period - 1);
+
+        poll % period < pressed
+    }
+}
+
+impl Default for TurboConfig {
+    fn default() -> Self {
+        TurboConfig {
+            period: 6,
+            duty: 0.5,
+        }
+    }
+}
+
+/// Remembers the poll that each held turbo key or button was pressed on, so that the button
+/// is always pressed on the first poll it is held for.
+pub struct TurboPresses<T> {
+    started: Vec<(T, u64)>,
+}
+
+impl<T: Copy + PartialEq> TurboPresses<T> {
+    /// Updates which keys or buttons are held on a poll.
+    pub fn update(&mut self, held: &[T], poll: u64) {
+        self.started.retain(|x| held.contains(&x.0));
+
+        for source in held {
+            if !self.started.iter().any(|x| x.0 == *source) {
+                self.started.push((*source, poll));
+            }
+        }
+    }
+
+    /// Returns if a held key or button presses its turbo button on a poll.
+    pub fn is_pressed(&self, config: &TurboConfig, source: &T, poll: u64) -> bool {
+        match self.started.iter().find(|x| x.0 == *source) {
+            Some(&(_, start)) => config.is_pressed(poll - start),
+            None => false,
+        }
+    }
+}
+
+impl<T> Default for TurboPresses<T> {
+    fn default() -> Self {
+        TurboPresses {
+            started: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TurboConfig;
+    use super::TurboPresses;
+
+    #[test]
+    fn duty_cycle() {
+        let turbo = TurboConfig {
+            period: 4,
+            duty: 0.25,
+        };
+        let pressed: Vec<bool> = (0..8).map(|x| turbo.is_pressed(x)).collect();
+        assert_eq!(
+            pressed,
+            vec![true, false, false, false, true, false, false, false]
+        );
+
+        // Buttons are always pressed and released at some point
+        let turbo = TurboConfig {
+            period: 1,
+            duty: 1.0,
+        };
+        let pressed: Vec<bool> = (0..4).map(|x| turbo.is_pressed(x)).collect();
+        assert_eq!(pressed, vec![true, false, true, false]);
+    }
+    #[test]
+    fn first_press() {
+        let turbo = TurboConfig::default();
+        let mut presses = TurboPresses::default();
+
+        // Presses register straight away, whichever poll they start on
+        for poll in 10..16 {
+            presses.update(&[1], poll);
+            assert!(presses.is_pressed(&turbo, &1, poll));
+            assert!(!presses.is_pressed(&turbo, &2, poll));
+
+            presses.update(&[], poll + 1);
+            assert!(!presses.is_pressed(&turbo, &1, poll + 1));
+        }
+
+        // Held keys keep their own phase
+        presses.update(&[1], 20);
+        presses.update(&[1, 2], 23);
+        assert!(!presses.is_pressed(&turbo, &1, 23));
+        assert!(presses.is_pressed(&turbo, &2, 23));
+    }
+}
@@ -0,0 +1,280 @@
+/// Replays tracks recorded in the GPX format.
+///
+/// Only the points of tracks (`<trkpt>`) and their timestamps are read. Positions between
+/// points are interpolated.
+
+use std::fs::File;
+use std::io;
+use std::io::Read;
+
+use location::LocationSource;
+use location::DEFAULT_ACCURACY;
+
+use retro_types::RetroPosition;
+
+/// A single point along a track.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct TrackPoint {
+    /// Seconds since the first point.
+    time: f64,
+    latitude: f64,
+    longitude: f64,
+}
+
+pub struct GpxTrack {
+    points: Vec<TrackPoint>,
+}
+
+/// Builds an error for a malformed track.
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Returns the value of an attribute within an XML start tag.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+
+    loop {
+        let start = rest.find(name)?;
+        let preceding = rest[..start].chars().last();
+        rest = &rest[start + name.len()..];
+
+        // Skip matches in the middle of other attributes' names
+        if !preceding.map(|x| x.is_whitespace()).unwrap_or(false) {
+            continue;
+        }
+
+        let value = rest.trim_left();
+        if !value.starts_with('=') {
+            continue;
+        }
+
+        let value = value[1..].trim_left();
+        let quote = value.chars().next()?;
+        if quote != '"' && quote != '\'' {
+            return None;
+        }
+
+        let value = &value[1..];
+        return Some(&value[..value.find(quote)?]);
+    }
+}
+
+/// Returns the text within the first element with the specified name.
+fn element<'a>(body: &'a str, name: &str) -> Option<&'a str> {
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+
+    let start = body.find(&open)? + open.len();
+    let end = body[start..].find(&close)? + start;
+    Some(body[start..end].trim())
+}
+
+/// Returns the number of days between 1970-01-01 and a date in the Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Parses an ISO 8601 timestamp (e.g. `2018-03-01T12:30:00.5Z`) into seconds since the Unix
+/// epoch.
+fn parse_timestamp(value: &str) -> Option<f64> {
+    if value.len() < 19 {
+        return None;
+    }
+
+    let number = |range: ::std::ops::Range<usize>| value.get(range)?.parse::<i64>().ok();
+
+    let days = days_from_civil(number(0..4)?, number(5..7)?, number(8..10)?);
+    let seconds = number(11..13)? * 3600 + number(14..16)? * 60 + number(17..19)?;
+
+    let rest = &value[19..];
+
+    // Fractional seconds
+    let fraction_length = if rest.starts_with('.') {
+        rest[1..]
+            .find(|x: char| !x.is_ascii_digit())
+            .unwrap_or(rest.len() - 1) + 1
+    } else {
+        0
+    };
+    let fraction = if fraction_length > 1 {
+        format!("0{}", &rest[..fraction_length]).parse::<f64>().ok()?
+    } else {
+        0.0
+    };
+
+    // Timezone, which is UTC if it isn't specified
+    let zone = &rest[fraction_length..];
+    let offset = if zone.is_empty() || zone == "Z" {
+        0
+    } else {
+        let sign = match zone.chars().next()? {
+            '+' => 1,
+            '-' => -1,
+            _ => return None,
+        };
+        let hours = zone.get(1..3)?.parse::<i64>().ok()?;
+        let minutes = zone.get(4..6)?.parse::<i64>().ok()?;
+        sign * (hours * 3600 + minutes * 60)
+    };
+
+    Some((days * 86400 + seconds - offset) as f64 + fraction)
+}
+
+/// Interpolates between two values.
+#[inline]
+fn lerp(a: f64, b: f64, amount: f64) -> f64 {
+    a + (b - a) * amount
+}
+
+impl LocationSource for GpxTrack {
+    fn get_position(&self, time: f64) -> RetroPosition {
+        // Find the first point after this time
+        let next = self.points
+            .iter()
+            .position(|x| x.time > time)
+            .unwrap_or(self.points.len());
+
+        let (latitude, longitude) = if next == 0 {
+            (self.points[0].latitude, self.points[0].longitude)
+        } else if next == self.points.len() {
+            let last = &self.points[next - 1];
+            (last.latitude, last.longitude)
+        } else {
+            let a = &self.points[next - 1];
+            let b = &self.points[next];
+            let amount = (time - a.time) / (b.time - a.time);
+            (
+                lerp(a.latitude, b.latitude, amount),
+                lerp(a.longitude, b.longitude, amount),
+            )
+        };
+
+        RetroPosition {
+            latitude,
+            longitude,
+            horizontal_accuracy: DEFAULT_ACCURACY,
+            vertical_accuracy: DEFAULT_ACCURACY,
+        }
+    }
+}
+
+impl GpxTrack {
+    /// Loads a track from disk.
+    pub fn load(path: &str) -> io::Result<GpxTrack> {
+        let mut data = String::new();
+        File::open(path)?.read_to_string(&mut data)?;
+        GpxTrack::parse(&data)
+    }
+
+    /// Parses the points of all tracks in a GPX document. If any point doesn't have a time,
+    /// points are instead assumed to be a second apart.
+    pub fn parse(data: &str) -> io::Result<GpxTrack> {
+        let mut points = Vec::new();
+        let mut timestamps = Vec::new();
+
+        let mut rest = data;
+        while let Some(start) = rest.find("<trkpt") {
+            rest = &rest[start..];
+
+            let tag_end = rest.find('>')
+                .ok_or_else(|| invalid("Unterminated track point".to_owned()))?;
+            let tag = &rest[..tag_end];
+
+            let coordinate = |name: &str| -> io::Result<f64> {
+                attribute(tag, name)
+                    .and_then(|x| x.parse::<f64>().ok())
+                    .ok_or_else(|| invalid(format!("Track point without a valid {}", name)))
+            };
+            let latitude = coordinate("lat")?;
+            let longitude = coordinate("lon")?;
+
+            // Self closing points have no children
+            let body = if tag.ends_with('/') {
+                ""
+            } else {
+                let end = rest.find("</trkpt>").unwrap_or(rest.len());
+                &rest[tag_end..end]
+            };
+
+            timestamps.push(element(body, "time").and_then(parse_timestamp));
+            points.push(TrackPoint {
+                time: 0.0,
+                latitude,
+                longitude,
+            });
+
+            rest = &rest[tag_end..];
+        }
+
+        if points.is_empty() {
+            return Err(invalid("No track points found".to_owned()));
+        }
+
+        if timestamps.iter().all(|x| x.is_some()) {
+            let start = timestamps[0].unwrap();
+            for (point, timestamp) in points.iter_mut().zip(timestamps) {
+                point.time = timestamp.unwrap() - start;
+            }
+        } else {
+            println!("Track is missing timestamps, assuming one point per second");
+            for (i, point) in points.iter_mut().enumerate() {
+                point.time = i as f64;
+            }
+        }
+
+        points.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+
+        Ok(GpxTrack { points })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::GpxTrack;
+    use super::parse_timestamp;
+
+    use location::LocationSource;
+
+    static TRACK: &str = r#"<?xml version="1.0"?>
+<gpx version="1.1" creator="test">
+  <trk><trkseg>
+    <trkpt lat="10.0" lon="20.0"><ele>5</ele><time>2018-03-01T12:00:00Z</time></trkpt>
+    <trkpt lon='22.0' lat='12.0'><time>2018-03-01T12:00:10Z</time></trkpt>
+  </trkseg></trk>
+</gpx>"#;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Some(0.0));
+        assert_eq!(parse_timestamp("2018-03-01T12:00:00Z"), Some(1519905600.0));
+        assert_eq!(parse_timestamp("2018-03-01T13:00:00.5+01:00"), Some(1519905600.5));
+        assert_eq!(parse_timestamp("yesterday"), None);
+    }
+
+    #[test]
+    fn interpolate_track() {
+        let track = GpxTrack::parse(TRACK).unwrap();
+
+        let start = track.get_position(0.0);
+        assert_eq!((start.latitude, start.longitude), (10.0, 20.0));
+
+        let middle = track.get_position(5.0);
+        assert_eq!((middle.latitude, middle.longitude), (11.0, 21.0));
+
+        // Positions hold once the track has finished
+        let end = track.get_position(60.0);
+        assert_eq!((end.latitude, end.longitude), (12.0, 22.0));
+    }
+
+    #[test]
+    fn reject_empty_track() {
+        assert!(GpxTrack::parse("<gpx></gpx>").is_err());
+    }
+}
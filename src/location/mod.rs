@@ -0,0 +1,201 @@
+/// Location services which cores can ask for the user's position.
+///
+/// Positions are always fake, so that location-aware cores behave the same way on each run.
+/// Time only passes for them while the core is running.
+
+pub mod gpx;
+
+use retro_types::RetroPosition;
+
+/// The mean radius of the Earth, in meters.
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+/// The accuracy reported when none has been specified, in meters.
+pub const DEFAULT_ACCURACY: f64 = 10.0;
+
+/// Where positions should come from.
+pub enum LocationSourceType {
+    /// No location services at all.
+    None,
+    /// A single position which never changes.
+    Fixed(RetroPosition),
+    /// A GPX track, replayed from when the core starts location services.
+    Track(String),
+}
+
+/// Parses a position in the form `latitude,longitude[,accuracy]`.
+pub fn parse_position(value: &str) -> Result<RetroPosition, String> {
+    let values = value
+        .split(',')
+        .map(|x| x.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Bad location {:?}: {}", value, e))?;
+
+    let accuracy = match values.len() {
+        2 => DEFAULT_ACCURACY,
+        3 => values[2],
+        _ => return Err("Location should be latitude,longitude[,accuracy]".to_owned()),
+    };
+
+    if values[0].abs() > 90.0 || values[1].abs() > 180.0 {
+        return Err(format!("Location {:?} is not on Earth", value));
+    }
+
+    Ok(RetroPosition {
+        latitude: values[0],
+        longitude: values[1],
+        horizontal_accuracy: accuracy,
+        vertical_accuracy: accuracy,
+    })
+}
+
+/// Returns the distance between two positions along the surface of the Earth, in meters.
+pub fn distance(a: &RetroPosition, b: &RetroPosition) -> f64 {
+    let latitude_a = a.latitude.to_radians();
+    let latitude_b = b.latitude.to_radians();
+    let delta_latitude = (b.latitude - a.latitude).to_radians();
+    let delta_longitude = (b.longitude - a.longitude).to_radians();
+
+    let h = (delta_latitude / 2.0).sin().powi(2)
+        + latitude_a.cos() * latitude_b.cos() * (delta_longitude / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS * h.sqrt().asin()
+}
+
+pub trait LocationSource {
+    /// Returns the position at the specified number of seconds after location services were
+    /// started.
+    fn get_position(&self, time: f64) -> RetroPosition;
+}
+
+/// A position which never changes.
+struct FixedLocation {
+    position: RetroPosition,
+}
+
+impl LocationSource for FixedLocation {
+    fn get_position(&self, _time: f64) -> RetroPosition {
+        self.position
+    }
+}
+
+/// Tracks if a core has started location services, and what it has been told so far.
+pub struct Location {
+    source: Option<Box<LocationSource>>,
+    running: bool,
+
+    /// Seconds since location services were started.
+    time: f64,
+
+    /// How often the core wants its position updated.
+    interval_ms: u32,
+    interval_distance: u32,
+
+    /// The position the core is currently given, and when it was updated.
+    reported: Option<RetroPosition>,
+    reported_time: f64,
+}
+
+impl Location {
+    /// Starts location services on behalf of a core.
+    pub fn start(&mut self) -> bool {
+        if self.source.is_none() {
+            return false;
+        }
+
+        if !self.running {
+            self.running = true;
+            self.time = 0.0;
+            self.reported = None;
+            self.update();
+        }
+
+        true
+    }
+
+    /// Stops location services on behalf of a core.
+    pub fn stop(&mut self) {
+        self.running = false;
+    }
+
+    /// Sets how much time must pass, and how far the user must move, before the position
+    /// that the core is given changes.
+    pub fn set_interval(&mut self, interval_ms: u32, interval_distance: u32) {
+        self.interval_ms = interval_ms;
+        self.interval_distance = interval_distance;
+    }
+
+    /// Moves time forward by the specified number of seconds.
+    pub fn advance(&mut self, seconds: f64) {
+        if !self.running {
+            return;
+        }
+
+        self.time += seconds;
+        self.update();
+    }
+
+    /// Returns the position that the core should be given, if location services are running.
+    pub fn get_position(&self) -> Option<RetroPosition> {
+        if !self.running {
+            return None;
+        }
+
+        self.reported
+    }
+
+    /// Updates the reported position, if the core's interval has been met.
+    fn update(&mut self) {
+        let position = match &self.source {
+            &Some(ref v) => v.get_position(self.time),
+            &None => return,
+        };
+
+        let update = match &self.reported {
+            &Some(ref reported) => {
+                (self.time - self.reported_time) * 1000.0 >= self.interval_ms as f64
+                    && distance(reported, &position) >= self.interval_distance as f64
+            }
+            &None => true,
+        };
+
+        if update {
+            self.reported = Some(position);
+            self.reported_time = self.time;
+        }
+    }
+
+    /// Wraps a source of positions, if any are available.
+    pub fn new(source: Option<Box<LocationSource>>) -> Location {
+        Location {
+            source,
+            running: false,
+
+            time: 0.0,
+
+            interval_ms: 0,
+            interval_distance: 0,
+
+            reported: None,
+            reported_time: 0.0,
+        }
+    }
+}
+
+/// Builds a new source of positions, if one has been configured.
+pub fn build(source_type: &LocationSourceType) -> Option<Box<LocationSource>> {
+    match source_type {
+        &LocationSourceType::None => None,
+        &LocationSourceType::Fixed(position) => {
+            println!(
+                "Using fixed location: {}, {}",
+                position.latitude, position.longitude
+            );
+            Some(Box::new(FixedLocation { position }))
+        }
+        &LocationSourceType::Track(ref path) => {
+            println!("Replaying location track from {}", path);
+            Some(Box::new(gpx::GpxTrack::load(path).unwrap()))
+        }
+    }
+}
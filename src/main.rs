@@ -1,6 +1,7 @@
 //! A multi-process implementation of oxretro.
 #![feature(vec_remove_item)]
 #![feature(duration_from_micros)]
+#![cfg_attr(test, feature(test))]
 
 extern crate bincode;
 extern crate serde;
@@ -9,12 +10,18 @@ extern crate serde_derive;
 
 extern crate byteorder;
 
+#[cfg(target_os = "linux")]
+extern crate libc;
+
 #[macro_use]
 extern crate clap;
 
 pub mod audio;
 pub mod input;
 pub mod graphics;
+pub mod sensor;
+pub mod camera;
+pub mod location;
 pub mod backend;
 pub mod frontend;
 
@@ -24,6 +31,20 @@ pub mod core_protocol;
 
 use clap::{App, Arg};
 
+use frontend::config::FrontendConfig;
+use sensor::SensorSourceType;
+use camera::CameraSourceType;
+use location::LocationSourceType;
+use input::analog::AnalogConfig;
+
+/// Parses a number from 0 up to (but not including) 1.
+fn parse_fraction(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(v) if v >= 0.0 && v < 1.0 => Ok(v),
+        _ => Err(format!("{} must be a number from 0 to 1", value)),
+    }
+}
+
 fn main() {
     let matches = App::new("OxRetro")
         .version(crate_version!())
@@ -38,7 +59,6 @@ fn main() {
                 .help("The kind of process that should be started")
                 .requires_if("backend", "core")
                 .requires_if("backend", "address")
-                .requires_if("frontend", "rom")
                 .takes_value(true),
         )
         .arg(
@@ -59,9 +79,97 @@ fn main() {
             Arg::with_name("rom")
                 .short("r")
                 .long("rom")
-                .help("[Frontend only] The rom to load")
+                .help("[Frontend only] The rom to load, if the core needs one")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("sensor")
+                .long("sensor")
+                .default_value("auto")
+                .possible_values(&["auto", "keyboard", "device"])
+                .help("[Frontend only] Where sensor readings (e.g. tilt) should come from")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("sensor-script")
+                .long("sensor-script")
+                .help("[Frontend only] A CSV file of sensor readings to replay instead")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("camera")
+                .long("camera")
+                .help("[Frontend only] An image, directory of images or device to use as a camera")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("location")
+                .long("location")
+                .help("[Frontend only] A fixed position to report, as lat,lon[,accuracy in m]")
+                .validator(|v| location::parse_position(&v).map(|_| ()))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("location-track")
+                .long("location-track")
+                .help("[Frontend only] A GPX track to replay as the position instead")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("analog-deadzone")
+                .long("analog-deadzone")
+                .help("[Frontend only] How far sticks must be pushed before moving, from 0 to 1")
+                .validator(|v| parse_fraction(&v).map(|_| ()))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("analog-sensitivity")
+                .long("analog-sensitivity")
+                .help("[Frontend only] Multiplies how far sticks are pushed")
+                .validator(|v| {
+                    v.parse::<f32>()
+                        .map(|_| ())
+                        .map_err(|_| "Sensitivity must be a number".to_owned())
+                })
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("port")
+                .long("port")
+                .help(
+                    "[Frontend only] Plugs a device into a port, as port=device. Devices are \
+                     keyboard, keyboard-left, keyboard-right, gamepad:N, script:path or none",
+                )
+                .validator(|v| input::ports::parse_assignment(&v).map(|_| ()))
+                .multiple(true)
+                .number_of_values(1)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("controller")
+                .long("controller")
+                .help(
+                    "[Frontend only] Tells the core which of its controllers is plugged into a \
+                     port, as port=controller (by name, e.g. multitap, or device ID)",
+                )
+                .validator(|v| input::controllers::parse_choice(&v).map(|_| ()))
+                .multiple(true)
+                .number_of_values(1)
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("input-config")
+                .long("input-config")
+                .help("[Frontend only] The file that key and button bindings are kept in")
+                .default_value("input.cfg")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("send-content")
+                .long("send-content")
+                .help("[Frontend only] Sends the rom to the backend, for when it can't read it")
+                .requires("rom"),
+        )
         .arg(
             Arg::with_name("no-backend")
                 .long("no-backend")
@@ -74,12 +182,62 @@ fn main() {
     let process_type = matches.value_of("type").unwrap();
     match &process_type {
         &"frontend" => {
-            let core = matches.value_of("core").map(|v| v.to_owned());
-            let address = matches.value_of("address").map(|v| v.to_owned());
-            let rom = matches.value_of("rom").unwrap().to_owned();
-            let spawn_core = matches.is_present("no-backend");
+            let sensor = match matches.value_of("sensor-script") {
+                Some(v) => SensorSourceType::Script(v.to_owned()),
+                None => match matches.value_of("sensor").unwrap() {
+                    "keyboard" => SensorSourceType::Keyboard,
+                    "device" => SensorSourceType::Device,
+                    _ => SensorSourceType::Auto,
+                },
+            };
+
+            let camera = match matches.value_of("camera") {
+                Some(v) => CameraSourceType::from_path(v),
+                None => CameraSourceType::Auto,
+            };
+
+            let location = match matches.value_of("location-track") {
+                Some(v) => LocationSourceType::Track(v.to_owned()),
+                None => match matches.value_of("location") {
+                    Some(v) => LocationSourceType::Fixed(location::parse_position(v).unwrap()),
+                    None => LocationSourceType::None,
+                },
+            };
+
+            let mut analog = AnalogConfig::default();
+            if let Some(v) = matches.value_of("analog-deadzone") {
+                analog.deadzone = parse_fraction(v).unwrap();
+            }
+            if let Some(v) = matches.value_of("analog-sensitivity") {
+                analog.sensitivity = v.parse().unwrap();
+            }
+
+            let ports = match matches.values_of("port") {
+                Some(v) => v.map(|x| input::ports::parse_assignment(x).unwrap())
+                    .collect(),
+                None => Vec::new(),
+            };
+
+            let controllers = match matches.values_of("controller") {
+                Some(v) => v.map(|x| input::controllers::parse_choice(x).unwrap())
+                    .collect(),
+                None => Vec::new(),
+            };
 
-            frontend::run(core, rom, address, spawn_core);
+            frontend::run(FrontendConfig {
+                core: matches.value_of("core").map(|v| v.to_owned()),
+                rom: matches.value_of("rom").map(|v| v.to_owned()),
+                address: matches.value_of("address").map(|v| v.to_owned()),
+                send_content: matches.is_present("send-content"),
+                dont_spawn_core: matches.is_present("no-backend"),
+                sensor,
+                camera,
+                location,
+                analog,
+                ports,
+                controllers,
+                bindings: matches.value_of("input-config").unwrap().to_owned(),
+            });
         }
         &"backend" => {
             let address = matches.value_of("address").unwrap().to_owned();
@@ -4,10 +4,18 @@ use std::ffi::CString;
 
 use std::str::Utf8Error;
 
+use std::fmt;
+
+use std::slice;
+
 use std::os::raw::*;
 
 use ffi::char_pointer_to_owned;
 
+/// Passed to the video refresh callback instead of a framebuffer when a hardware rendered frame
+/// is ready.
+pub const RETRO_HW_FRAME_BUFFER_VALID: *const c_void = -1isize as *const c_void;
+
 // Core functions
 // retro_init()
 pub type RetroInitFn = unsafe extern "C" fn() -> ();
@@ -72,6 +80,767 @@ pub type RetroUnloadGameFn = unsafe extern "C" fn() -> ();
 // void retro_get_system_av_info(struct retro_system_av_info*)
 pub type RetroGetSystemAvInfoFn = unsafe extern "C" fn(*const RetroAvInfo) -> ();
 
+// void *retro_get_memory_data(unsigned id)
+pub type RetroGetMemoryDataFn = unsafe extern "C" fn(c_uint) -> *mut c_void;
+
+// size_t retro_get_memory_size(unsigned id)
+pub type RetroGetMemorySizeFn = unsafe extern "C" fn(c_uint) -> usize;
+
+// void retro_set_controller_port_device(unsigned port, unsigned device)
+pub type RetroSetControllerPortDeviceFn = unsafe extern "C" fn(c_uint, c_uint) -> ();
+
+// void retro_frame_time_callback(retro_usec_t usec)
+pub type RetroFrameTimeCallbackFn = unsafe extern "C" fn(i64) -> ();
+
+/// Registered by cores which want to know how much time has passed between frames.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct RetroFrameTimeCallback {
+    pub callback: Option<RetroFrameTimeCallbackFn>,
+    /// The ideal time between frames in microseconds, used when time isn't flowing normally.
+    pub reference: i64,
+}
+
+// void retro_audio_callback(void)
+pub type RetroAudioCallbackFn = unsafe extern "C" fn() -> ();
+
+// void retro_audio_set_state_callback(bool enabled)
+pub type RetroAudioSetStateCallbackFn = unsafe extern "C" fn(bool) -> ();
+
+/// Registered by cores which want to be asked for audio, rather than submitting it while running.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct RetroAudioCallback {
+    pub callback: Option<RetroAudioCallbackFn>,
+    pub set_state: Option<RetroAudioSetStateCallbackFn>,
+}
+
+// bool set_rumble_state(unsigned port, enum retro_rumble_effect effect, uint16_t strength)
+pub type RetroSetRumbleStateFn = unsafe extern "C" fn(c_uint, c_uint, u16) -> bool;
+
+/// Provided to cores so that they can rumble controllers.
+#[repr(C)]
+pub struct RetroRumbleInterface {
+    pub set_rumble_state: RetroSetRumbleStateFn,
+}
+
+/// Describes which motor of a controller should rumble.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum RetroRumbleEffect {
+    Strong = 0,
+    Weak = 1,
+}
+
+impl RetroRumbleEffect {
+    /// Converts from a raw integer effect, as used by the LibRetro API.
+    pub fn from(effect: u32) -> Option<RetroRumbleEffect> {
+        Some(match effect {
+            0 => RetroRumbleEffect::Strong,
+            1 => RetroRumbleEffect::Weak,
+            _ => return None,
+        })
+    }
+}
+
+// bool set_sensor_state(unsigned port, enum retro_sensor_action action, unsigned rate)
+pub type RetroSetSensorStateFn = unsafe extern "C" fn(c_uint, c_uint, c_uint) -> bool;
+
+// float get_sensor_input(unsigned port, unsigned id)
+pub type RetroGetSensorInputFn = unsafe extern "C" fn(c_uint, c_uint) -> f32;
+
+/// Provided to cores so that they can read sensors (e.g. tilt).
+#[repr(C)]
+pub struct RetroSensorInterface {
+    pub set_sensor_state: RetroSetSensorStateFn,
+    pub get_sensor_input: RetroGetSensorInputFn,
+}
+
+/// Describes a core enabling or disabling a sensor.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
+pub enum RetroSensorAction {
+    AccelerometerEnable = 0,
+    AccelerometerDisable = 1,
+    GyroscopeEnable = 2,
+    GyroscopeDisable = 3,
+    IlluminanceEnable = 4,
+    IlluminanceDisable = 5,
+}
+
+impl RetroSensorAction {
+    /// Converts from a raw integer action, as used by the LibRetro API.
+    pub fn from(action: u32) -> Option<RetroSensorAction> {
+        Some(match action {
+            0 => RetroSensorAction::AccelerometerEnable,
+            1 => RetroSensorAction::AccelerometerDisable,
+            2 => RetroSensorAction::GyroscopeEnable,
+            3 => RetroSensorAction::GyroscopeDisable,
+            4 => RetroSensorAction::IlluminanceEnable,
+            5 => RetroSensorAction::IlluminanceDisable,
+            _ => return None,
+        })
+    }
+}
+
+/// Describes a particular value which can be read from a sensor.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
+pub enum RetroSensor {
+    AccelerometerX = 0,
+    AccelerometerY = 1,
+    AccelerometerZ = 2,
+    GyroscopeX = 3,
+    GyroscopeY = 4,
+    GyroscopeZ = 5,
+    Illuminance = 6,
+}
+
+impl RetroSensor {
+    /// Converts from a raw integer ID, as used by the LibRetro API.
+    pub fn from(id: u32) -> Option<RetroSensor> {
+        Some(match id {
+            0 => RetroSensor::AccelerometerX,
+            1 => RetroSensor::AccelerometerY,
+            2 => RetroSensor::AccelerometerZ,
+            3 => RetroSensor::GyroscopeX,
+            4 => RetroSensor::GyroscopeY,
+            5 => RetroSensor::GyroscopeZ,
+            6 => RetroSensor::Illuminance,
+            _ => return None,
+        })
+    }
+}
+
+/// Identifies a device which the core reads input from. The low byte is the base device,
+/// which cores may subclass.
+pub const RETRO_DEVICE_MASK: c_uint = 0xff;
+pub const RETRO_DEVICE_JOYPAD: c_uint = 1;
+pub const RETRO_DEVICE_MOUSE: c_uint = 2;
+pub const RETRO_DEVICE_KEYBOARD: c_uint = 3;
+pub const RETRO_DEVICE_LIGHTGUN: c_uint = 4;
+pub const RETRO_DEVICE_ANALOG: c_uint = 5;
+pub const RETRO_DEVICE_POINTER: c_uint = 6;
+
+/// Asks for every RetroPad button at once, as a bit for each joypad ID.
+pub const RETRO_DEVICE_ID_JOYPAD_MASK: c_uint = 256;
+
+/// Which part of an analog controller is being read. Analog buttons use the joypad IDs.
+pub const RETRO_DEVICE_INDEX_ANALOG_LEFT: c_uint = 0;
+pub const RETRO_DEVICE_INDEX_ANALOG_RIGHT: c_uint = 1;
+pub const RETRO_DEVICE_INDEX_ANALOG_BUTTON: c_uint = 2;
+pub const RETRO_DEVICE_ID_ANALOG_X: c_uint = 0;
+pub const RETRO_DEVICE_ID_ANALOG_Y: c_uint = 1;
+
+/// Values which can be read from a mouse. Movement is relative to the last poll.
+pub const RETRO_DEVICE_ID_MOUSE_X: c_uint = 0;
+pub const RETRO_DEVICE_ID_MOUSE_Y: c_uint = 1;
+pub const RETRO_DEVICE_ID_MOUSE_LEFT: c_uint = 2;
+pub const RETRO_DEVICE_ID_MOUSE_RIGHT: c_uint = 3;
+pub const RETRO_DEVICE_ID_MOUSE_WHEELUP: c_uint = 4;
+pub const RETRO_DEVICE_ID_MOUSE_WHEELDOWN: c_uint = 5;
+pub const RETRO_DEVICE_ID_MOUSE_MIDDLE: c_uint = 6;
+pub const RETRO_DEVICE_ID_MOUSE_HORIZ_WHEELUP: c_uint = 7;
+pub const RETRO_DEVICE_ID_MOUSE_HORIZ_WHEELDOWN: c_uint = 8;
+pub const RETRO_DEVICE_ID_MOUSE_BUTTON_4: c_uint = 9;
+pub const RETRO_DEVICE_ID_MOUSE_BUTTON_5: c_uint = 10;
+
+/// Values which can be read from a lightgun. Positions are in the same space as pointers.
+pub const RETRO_DEVICE_ID_LIGHTGUN_SCREEN_X: c_uint = 13;
+pub const RETRO_DEVICE_ID_LIGHTGUN_SCREEN_Y: c_uint = 14;
+pub const RETRO_DEVICE_ID_LIGHTGUN_IS_OFFSCREEN: c_uint = 15;
+pub const RETRO_DEVICE_ID_LIGHTGUN_TRIGGER: c_uint = 2;
+pub const RETRO_DEVICE_ID_LIGHTGUN_RELOAD: c_uint = 16;
+pub const RETRO_DEVICE_ID_LIGHTGUN_AUX_A: c_uint = 3;
+pub const RETRO_DEVICE_ID_LIGHTGUN_AUX_B: c_uint = 4;
+pub const RETRO_DEVICE_ID_LIGHTGUN_AUX_C: c_uint = 8;
+/// Deprecated relative movement, as with a mouse.
+pub const RETRO_DEVICE_ID_LIGHTGUN_X: c_uint = 0;
+pub const RETRO_DEVICE_ID_LIGHTGUN_Y: c_uint = 1;
+
+/// Values which can be read from a pointer (e.g. a touchscreen). Positions range from -0x7fff
+/// at the top-left of content to 0x7fff at the bottom-right, with -0x8000 being off screen.
+pub const RETRO_DEVICE_ID_POINTER_X: c_uint = 0;
+pub const RETRO_DEVICE_ID_POINTER_Y: c_uint = 1;
+pub const RETRO_DEVICE_ID_POINTER_PRESSED: c_uint = 2;
+pub const RETRO_DEVICE_ID_POINTER_COUNT: c_uint = 3;
+pub const RETRO_DEVICE_ID_POINTER_IS_OFFSCREEN: c_uint = 15;
+
+/// Describes a controller which can be plugged into a port (e.g. a multitap).
+#[repr(C)]
+pub struct RawRetroControllerDescription {
+    pub desc: *const c_char,
+    /// A `RETRO_DEVICE_*` value, which may be a subclass of one.
+    pub id: c_uint,
+}
+
+/// The controllers which a port supports. Cores pass an array of these, one per port, which
+/// is terminated by an entry without any types.
+#[repr(C)]
+pub struct RawRetroControllerInfo {
+    pub types: *const RawRetroControllerDescription,
+    pub num_types: c_uint,
+}
+
+impl RawRetroControllerInfo {
+    pub fn is_eof(&self) -> bool {
+        self.types as usize == 0
+    }
+
+    pub fn to_owned(&self) -> Result<Vec<RetroControllerDescription>, Utf8Error> {
+        let types = unsafe { slice::from_raw_parts(self.types, self.num_types as usize) };
+
+        types
+            .iter()
+            .map(|x| {
+                Ok(RetroControllerDescription {
+                    name: char_pointer_to_owned(x.desc)?,
+                    id: x.id,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Owned version of a RawRetroControllerDescription.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RetroControllerDescription {
+    pub name: String,
+    pub id: u32,
+}
+
+/// Keys on a keyboard, as identified by the LibRetro API. These mostly follow SDL 1.2.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
+pub enum RetroKey {
+    Unknown = 0,
+    Backspace = 8,
+    Tab = 9,
+    Clear = 12,
+    Return = 13,
+    Pause = 19,
+    Escape = 27,
+    Space = 32,
+    Exclaim = 33,
+    QuoteDbl = 34,
+    Hash = 35,
+    Dollar = 36,
+    Ampersand = 38,
+    Quote = 39,
+    LeftParen = 40,
+    RightParen = 41,
+    Asterisk = 42,
+    Plus = 43,
+    Comma = 44,
+    Minus = 45,
+    Period = 46,
+    Slash = 47,
+    Num0 = 48,
+    Num1 = 49,
+    Num2 = 50,
+    Num3 = 51,
+    Num4 = 52,
+    Num5 = 53,
+    Num6 = 54,
+    Num7 = 55,
+    Num8 = 56,
+    Num9 = 57,
+    Colon = 58,
+    Semicolon = 59,
+    Less = 60,
+    Equals = 61,
+    Greater = 62,
+    Question = 63,
+    At = 64,
+    LeftBracket = 91,
+    Backslash = 92,
+    RightBracket = 93,
+    Caret = 94,
+    Underscore = 95,
+    Backquote = 96,
+    A = 97,
+    B = 98,
+    C = 99,
+    D = 100,
+    E = 101,
+    F = 102,
+    G = 103,
+    H = 104,
+    I = 105,
+    J = 106,
+    K = 107,
+    L = 108,
+    M = 109,
+    N = 110,
+    O = 111,
+    P = 112,
+    Q = 113,
+    R = 114,
+    S = 115,
+    T = 116,
+    U = 117,
+    V = 118,
+    W = 119,
+    X = 120,
+    Y = 121,
+    Z = 122,
+    LeftBrace = 123,
+    Bar = 124,
+    RightBrace = 125,
+    Tilde = 126,
+    Delete = 127,
+
+    Keypad0 = 256,
+    Keypad1 = 257,
+    Keypad2 = 258,
+    Keypad3 = 259,
+    Keypad4 = 260,
+    Keypad5 = 261,
+    Keypad6 = 262,
+    Keypad7 = 263,
+    Keypad8 = 264,
+    Keypad9 = 265,
+    KeypadPeriod = 266,
+    KeypadDivide = 267,
+    KeypadMultiply = 268,
+    KeypadMinus = 269,
+    KeypadPlus = 270,
+    KeypadEnter = 271,
+    KeypadEquals = 272,
+
+    Up = 273,
+    Down = 274,
+    Right = 275,
+    Left = 276,
+    Insert = 277,
+    Home = 278,
+    End = 279,
+    PageUp = 280,
+    PageDown = 281,
+
+    F1 = 282,
+    F2 = 283,
+    F3 = 284,
+    F4 = 285,
+    F5 = 286,
+    F6 = 287,
+    F7 = 288,
+    F8 = 289,
+    F9 = 290,
+    F10 = 291,
+    F11 = 292,
+    F12 = 293,
+    F13 = 294,
+    F14 = 295,
+    F15 = 296,
+
+    NumLock = 300,
+    CapsLock = 301,
+    ScrollLock = 302,
+    RightShift = 303,
+    LeftShift = 304,
+    RightCtrl = 305,
+    LeftCtrl = 306,
+    RightAlt = 307,
+    LeftAlt = 308,
+    RightMeta = 309,
+    LeftMeta = 310,
+    LeftSuper = 311,
+    RightSuper = 312,
+    Mode = 313,
+    Compose = 314,
+
+    Help = 315,
+    Print = 316,
+    SysReq = 317,
+    Break = 318,
+    Menu = 319,
+    Power = 320,
+    Euro = 321,
+    Undo = 322,
+    Oem102 = 323,
+}
+
+/// Modifier bits passed alongside keyboard events.
+pub const RETROKMOD_SHIFT: u16 = 1 << 0;
+pub const RETROKMOD_CTRL: u16 = 1 << 1;
+pub const RETROKMOD_ALT: u16 = 1 << 2;
+pub const RETROKMOD_META: u16 = 1 << 3;
+
+// void retro_keyboard_event(bool down, unsigned keycode, uint32_t character,
+//                           uint16_t key_modifiers)
+pub type RetroKeyboardEventFn = unsafe extern "C" fn(bool, c_uint, u32, u16) -> ();
+
+/// Registered by cores which want to be told about key presses as they happen (e.g. for
+/// typing), rather than polling the keyboard device.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct RetroKeyboardCallback {
+    pub callback: Option<RetroKeyboardEventFn>,
+}
+
+// bool retro_camera_start(void)
+pub type RetroCameraStartFn = unsafe extern "C" fn() -> bool;
+
+// void retro_camera_stop(void)
+pub type RetroCameraStopFn = unsafe extern "C" fn() -> ();
+
+// void retro_camera_frame_raw_framebuffer(const uint32_t *buffer, unsigned width,
+//                                         unsigned height, size_t pitch)
+pub type RetroCameraFrameRawFramebufferFn =
+    unsafe extern "C" fn(*const u32, c_uint, c_uint, usize) -> ();
+
+// void retro_camera_frame_opengl_texture(unsigned texture_id, unsigned texture_target,
+//                                        const float *affine)
+pub type RetroCameraFrameOpenGLTextureFn = unsafe extern "C" fn(c_uint, c_uint, *const f32) -> ();
+
+// void retro_camera_lifetime_status(void)
+pub type RetroCameraLifetimeStatusFn = unsafe extern "C" fn() -> ();
+
+/// Capability bit for cores which accept raw XRGB8888 framebuffers from the camera.
+pub const RETRO_CAMERA_BUFFER_RAW_FRAMEBUFFER: u64 = 1 << 1;
+
+/// Registered by cores which want frames from a camera. `start` and `stop` are filled in by
+/// the frontend, everything else by the core.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct RetroCameraCallback {
+    pub caps: u64,
+    /// The desired dimensions of frames, or 0 if the core doesn't mind.
+    pub width: c_uint,
+    pub height: c_uint,
+    pub start: Option<RetroCameraStartFn>,
+    pub stop: Option<RetroCameraStopFn>,
+    pub frame_raw_framebuffer: Option<RetroCameraFrameRawFramebufferFn>,
+    pub frame_opengl_texture: Option<RetroCameraFrameOpenGLTextureFn>,
+    pub initialized: Option<RetroCameraLifetimeStatusFn>,
+    pub deinitialized: Option<RetroCameraLifetimeStatusFn>,
+}
+
+// bool retro_location_start(void)
+pub type RetroLocationStartFn = unsafe extern "C" fn() -> bool;
+
+// void retro_location_stop(void)
+pub type RetroLocationStopFn = unsafe extern "C" fn() -> ();
+
+// bool retro_location_get_position(double *lat, double *lon, double *horiz_accuracy,
+//                                  double *vert_accuracy)
+pub type RetroLocationGetPositionFn =
+    unsafe extern "C" fn(*mut f64, *mut f64, *mut f64, *mut f64) -> bool;
+
+// void retro_location_set_interval(unsigned interval_ms, unsigned interval_distance)
+pub type RetroLocationSetIntervalFn = unsafe extern "C" fn(c_uint, c_uint) -> ();
+
+// void retro_location_lifetime_status(void)
+pub type RetroLocationLifetimeStatusFn = unsafe extern "C" fn() -> ();
+
+/// Registered by cores which want to know where the user is. `start`, `stop`, `get_position`
+/// and `set_interval` are filled in by the frontend, everything else by the core.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct RetroLocationCallback {
+    pub start: Option<RetroLocationStartFn>,
+    pub stop: Option<RetroLocationStopFn>,
+    pub get_position: Option<RetroLocationGetPositionFn>,
+    pub set_interval: Option<RetroLocationSetIntervalFn>,
+    pub initialized: Option<RetroLocationLifetimeStatusFn>,
+    pub deinitialized: Option<RetroLocationLifetimeStatusFn>,
+}
+
+/// A position on Earth, with accuracies in meters.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RetroPosition {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub horizontal_accuracy: f64,
+    pub vertical_accuracy: f64,
+}
+
+/// A counter that cores use to measure how long parts of themselves take.
+#[repr(C)]
+pub struct RetroPerfCounter {
+    pub ident: *const c_char,
+    pub start: u64,
+    pub total: u64,
+    pub call_count: u64,
+    pub registered: bool,
+}
+
+// retro_time_t retro_perf_get_time_usec(void)
+pub type RetroPerfGetTimeUsecFn = unsafe extern "C" fn() -> i64;
+
+// uint64_t retro_get_cpu_features(void)
+pub type RetroGetCpuFeaturesFn = unsafe extern "C" fn() -> u64;
+
+// retro_perf_tick_t retro_perf_get_counter(void)
+pub type RetroPerfGetCounterFn = unsafe extern "C" fn() -> u64;
+
+// void retro_perf_register(struct retro_perf_counter *counter), also used for start/stop
+pub type RetroPerfCounterFn = unsafe extern "C" fn(*mut RetroPerfCounter) -> ();
+
+// void retro_perf_log(void)
+pub type RetroPerfLogFn = unsafe extern "C" fn() -> ();
+
+/// Provided to cores so that they can measure their own performance.
+#[repr(C)]
+pub struct RetroPerfCallback {
+    pub get_time_usec: RetroPerfGetTimeUsecFn,
+    pub get_cpu_features: RetroGetCpuFeaturesFn,
+    pub get_perf_counter: RetroPerfGetCounterFn,
+    pub perf_register: RetroPerfCounterFn,
+    pub perf_start: RetroPerfCounterFn,
+    pub perf_stop: RetroPerfCounterFn,
+    pub perf_log: RetroPerfLogFn,
+}
+
+/// CPU feature bits, as reported to cores.
+pub const RETRO_SIMD_SSE: u64 = 1 << 0;
+pub const RETRO_SIMD_SSE2: u64 = 1 << 1;
+pub const RETRO_SIMD_AVX: u64 = 1 << 4;
+pub const RETRO_SIMD_NEON: u64 = 1 << 5;
+pub const RETRO_SIMD_SSE3: u64 = 1 << 6;
+pub const RETRO_SIMD_SSSE3: u64 = 1 << 7;
+pub const RETRO_SIMD_MMX: u64 = 1 << 8;
+pub const RETRO_SIMD_SSE4: u64 = 1 << 10;
+pub const RETRO_SIMD_SSE42: u64 = 1 << 11;
+pub const RETRO_SIMD_AVX2: u64 = 1 << 12;
+pub const RETRO_SIMD_AES: u64 = 1 << 15;
+pub const RETRO_SIMD_POPCNT: u64 = 1 << 18;
+pub const RETRO_SIMD_CMOV: u64 = 1 << 20;
+
+/// A snapshot of a single performance counter, which can be sent between processes.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RetroPerfCounterReport {
+    pub ident: String,
+    pub call_count: u64,
+    /// Ticks (nanoseconds) spent between all starts and stops.
+    pub total_ticks: u64,
+}
+
+/// All of the performance counters that a core has registered.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RetroPerfReport {
+    pub counters: Vec<RetroPerfCounterReport>,
+}
+
+impl fmt::Display for RetroPerfReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.counters
+            .iter()
+            .map(|x| x.ident.len())
+            .max()
+            .unwrap_or(0)
+            .max("Counter".len());
+
+        writeln!(
+            f,
+            "{:<width$} {:>12} {:>16} {:>12}",
+            "Counter",
+            "Calls",
+            "Total ticks",
+            "Ticks/call",
+            width = width
+        )?;
+
+        for counter in &self.counters {
+            let average = if counter.call_count > 0 {
+                counter.total_ticks / counter.call_count
+            } else {
+                0
+            };
+
+            writeln!(
+                f,
+                "{:<width$} {:>12} {:>16} {:>12}",
+                counter.ident,
+                counter.call_count,
+                counter.total_ticks,
+                average,
+                width = width
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Opaque handles to files and directories opened through the VFS.
+pub enum RetroVfsFileHandle {}
+pub enum RetroVfsDirHandle {}
+
+pub const RETRO_VFS_FILE_ACCESS_READ: c_uint = 1 << 0;
+pub const RETRO_VFS_FILE_ACCESS_WRITE: c_uint = 1 << 1;
+pub const RETRO_VFS_FILE_ACCESS_UPDATE_EXISTING: c_uint = 1 << 2;
+
+pub const RETRO_VFS_SEEK_POSITION_START: c_int = 0;
+pub const RETRO_VFS_SEEK_POSITION_CURRENT: c_int = 1;
+pub const RETRO_VFS_SEEK_POSITION_END: c_int = 2;
+
+pub const RETRO_VFS_STAT_IS_VALID: c_int = 1 << 0;
+pub const RETRO_VFS_STAT_IS_DIRECTORY: c_int = 1 << 1;
+
+/// The newest version of the VFS interface that is implemented.
+pub const RETRO_VFS_INTERFACE_VERSION: u32 = 3;
+
+// const char *retro_vfs_get_path(struct retro_vfs_file_handle *stream)
+pub type RetroVfsGetPathFn = unsafe extern "C" fn(*mut RetroVfsFileHandle) -> *const c_char;
+
+// struct retro_vfs_file_handle *retro_vfs_open(const char *path, unsigned mode, unsigned hints)
+pub type RetroVfsOpenFn =
+    unsafe extern "C" fn(*const c_char, c_uint, c_uint) -> *mut RetroVfsFileHandle;
+
+// int retro_vfs_close(struct retro_vfs_file_handle *stream), also used for flush
+pub type RetroVfsCloseFn = unsafe extern "C" fn(*mut RetroVfsFileHandle) -> c_int;
+
+// int64_t retro_vfs_size(struct retro_vfs_file_handle *stream), also used for tell
+pub type RetroVfsSizeFn = unsafe extern "C" fn(*mut RetroVfsFileHandle) -> i64;
+
+// int64_t retro_vfs_seek(struct retro_vfs_file_handle *stream, int64_t offset,
+//                        int seek_position)
+pub type RetroVfsSeekFn = unsafe extern "C" fn(*mut RetroVfsFileHandle, i64, c_int) -> i64;
+
+// int64_t retro_vfs_read(struct retro_vfs_file_handle *stream, void *s, uint64_t len)
+pub type RetroVfsReadFn = unsafe extern "C" fn(*mut RetroVfsFileHandle, *mut c_void, u64) -> i64;
+
+// int64_t retro_vfs_write(struct retro_vfs_file_handle *stream, const void *s, uint64_t len)
+pub type RetroVfsWriteFn =
+    unsafe extern "C" fn(*mut RetroVfsFileHandle, *const c_void, u64) -> i64;
+
+// int retro_vfs_remove(const char *path)
+pub type RetroVfsRemoveFn = unsafe extern "C" fn(*const c_char) -> c_int;
+
+// int retro_vfs_rename(const char *old_path, const char *new_path)
+pub type RetroVfsRenameFn = unsafe extern "C" fn(*const c_char, *const c_char) -> c_int;
+
+// int64_t retro_vfs_truncate(struct retro_vfs_file_handle *stream, int64_t length)
+pub type RetroVfsTruncateFn = unsafe extern "C" fn(*mut RetroVfsFileHandle, i64) -> i64;
+
+// int retro_vfs_stat(const char *path, int32_t *size)
+pub type RetroVfsStatFn = unsafe extern "C" fn(*const c_char, *mut i32) -> c_int;
+
+// int retro_vfs_mkdir(const char *dir)
+pub type RetroVfsMkdirFn = unsafe extern "C" fn(*const c_char) -> c_int;
+
+// struct retro_vfs_dir_handle *retro_vfs_opendir(const char *dir, bool include_hidden)
+pub type RetroVfsOpendirFn = unsafe extern "C" fn(*const c_char, bool) -> *mut RetroVfsDirHandle;
+
+// bool retro_vfs_readdir(struct retro_vfs_dir_handle *dirstream), also used for dirent_is_dir
+pub type RetroVfsReaddirFn = unsafe extern "C" fn(*mut RetroVfsDirHandle) -> bool;
+
+// const char *retro_vfs_dirent_get_name(struct retro_vfs_dir_handle *dirstream)
+pub type RetroVfsDirentGetNameFn = unsafe extern "C" fn(*mut RetroVfsDirHandle) -> *const c_char;
+
+// int retro_vfs_closedir(struct retro_vfs_dir_handle *dirstream)
+pub type RetroVfsClosedirFn = unsafe extern "C" fn(*mut RetroVfsDirHandle) -> c_int;
+
+/// Provided to cores so that all of their file I/O goes through the frontend.
+#[repr(C)]
+pub struct RetroVfsInterface {
+    // v1
+    pub get_path: RetroVfsGetPathFn,
+    pub open: RetroVfsOpenFn,
+    pub close: RetroVfsCloseFn,
+    pub size: RetroVfsSizeFn,
+    pub tell: RetroVfsSizeFn,
+    pub seek: RetroVfsSeekFn,
+    pub read: RetroVfsReadFn,
+    pub write: RetroVfsWriteFn,
+    pub flush: RetroVfsCloseFn,
+    pub remove: RetroVfsRemoveFn,
+    pub rename: RetroVfsRenameFn,
+    // v2
+    pub truncate: RetroVfsTruncateFn,
+    // v3
+    pub stat: RetroVfsStatFn,
+    pub mkdir: RetroVfsMkdirFn,
+    pub opendir: RetroVfsOpendirFn,
+    pub readdir: RetroVfsReaddirFn,
+    pub dirent_get_name: RetroVfsDirentGetNameFn,
+    pub dirent_is_dir: RetroVfsReaddirFn,
+    pub closedir: RetroVfsClosedirFn,
+}
+
+/// Passed by cores to ask for a particular version of the VFS interface.
+#[repr(C)]
+pub struct RetroVfsInterfaceInfo {
+    pub required_interface_version: u32,
+    pub iface: *const RetroVfsInterface,
+}
+
+// bool retro_set_eject_state(bool ejected)
+pub type RetroSetEjectStateFn = unsafe extern "C" fn(bool) -> bool;
+
+// bool retro_get_eject_state(void)
+pub type RetroGetEjectStateFn = unsafe extern "C" fn() -> bool;
+
+// unsigned retro_get_image_index(void), also used for get_num_images
+pub type RetroGetImageIndexFn = unsafe extern "C" fn() -> c_uint;
+
+// bool retro_set_image_index(unsigned index)
+pub type RetroSetImageIndexFn = unsafe extern "C" fn(c_uint) -> bool;
+
+// bool retro_replace_image_index(unsigned index, const struct retro_game_info *info)
+pub type RetroReplaceImageIndexFn = unsafe extern "C" fn(c_uint, *const RawRetroGameInfo) -> bool;
+
+// bool retro_add_image_index(void)
+pub type RetroAddImageIndexFn = unsafe extern "C" fn() -> bool;
+
+// bool retro_set_initial_image(unsigned index, const char *path)
+pub type RetroSetInitialImageFn = unsafe extern "C" fn(c_uint, *const c_char) -> bool;
+
+// bool retro_get_image_path(unsigned index, char *path, size_t len), also used for labels
+pub type RetroGetImagePathFn = unsafe extern "C" fn(c_uint, *mut c_char, usize) -> bool;
+
+/// Registered by cores with multiple disks (e.g. multi-disc CD games), so that they can be
+/// swapped.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct RetroDiskControlCallback {
+    pub set_eject_state: Option<RetroSetEjectStateFn>,
+    pub get_eject_state: Option<RetroGetEjectStateFn>,
+    pub get_image_index: Option<RetroGetImageIndexFn>,
+    pub set_image_index: Option<RetroSetImageIndexFn>,
+    pub get_num_images: Option<RetroGetImageIndexFn>,
+    pub replace_image_index: Option<RetroReplaceImageIndexFn>,
+    pub add_image_index: Option<RetroAddImageIndexFn>,
+}
+
+/// A newer version of RetroDiskControlCallback, which can also describe disks.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct RetroDiskControlExtCallback {
+    pub set_eject_state: Option<RetroSetEjectStateFn>,
+    pub get_eject_state: Option<RetroGetEjectStateFn>,
+    pub get_image_index: Option<RetroGetImageIndexFn>,
+    pub set_image_index: Option<RetroSetImageIndexFn>,
+    pub get_num_images: Option<RetroGetImageIndexFn>,
+    pub replace_image_index: Option<RetroReplaceImageIndexFn>,
+    pub add_image_index: Option<RetroAddImageIndexFn>,
+    pub set_initial_image: Option<RetroSetInitialImageFn>,
+    pub get_image_path: Option<RetroGetImagePathFn>,
+    pub get_image_label: Option<RetroGetImagePathFn>,
+}
+
+impl RetroDiskControlExtCallback {
+    /// Upgrades the original interface, which can't describe disks.
+    pub fn from_basic(callback: &RetroDiskControlCallback) -> RetroDiskControlExtCallback {
+        RetroDiskControlExtCallback {
+            set_eject_state: callback.set_eject_state,
+            get_eject_state: callback.get_eject_state,
+            get_image_index: callback.get_image_index,
+            set_image_index: callback.set_image_index,
+            get_num_images: callback.get_num_images,
+            replace_image_index: callback.replace_image_index,
+            add_image_index: callback.add_image_index,
+            set_initial_image: None,
+            get_image_path: None,
+            get_image_label: None,
+        }
+    }
+}
+
+/// The newest version of the disk control interface that is implemented.
+pub const RETRO_DISK_CONTROL_INTERFACE_VERSION: c_uint = 1;
+
+/// Memory ID for battery backed save RAM.
+pub const RETRO_MEMORY_SAVE_RAM: c_uint = 0;
+
 /// Raw, C-compatible version of RetroSystemInfo for FFI.
 #[repr(C)]
 pub struct RawRetroSystemInfo {
@@ -183,7 +952,7 @@ impl RetroGameInfo {
 
 /// Describes the dimensions of a core's requested framebuffer.
 #[repr(C)]
-#[derive(Deserialize, Serialize)]
+#[derive(Debug, Copy, Clone, Deserialize, Serialize)]
 pub struct RetroGameGeometry {
     pub base_width: u32,
     pub base_height: u32,
@@ -194,7 +963,7 @@ pub struct RetroGameGeometry {
 
 /// Describes the timings of a core.
 #[repr(C)]
-#[derive(Deserialize, Serialize)]
+#[derive(Debug, Copy, Clone, Deserialize, Serialize)]
 pub struct RetroSystemTiming {
     pub fps: f64,
     pub sample_rate: f64,
@@ -202,7 +971,7 @@ pub struct RetroSystemTiming {
 
 /// Describes the A/V requirements for a Core.
 #[repr(C)]
-#[derive(Deserialize, Serialize)]
+#[derive(Debug, Copy, Clone, Deserialize, Serialize)]
 pub struct RetroAvInfo {
     pub geometry: RetroGameGeometry,
     pub timing: RetroSystemTiming,
@@ -228,7 +997,7 @@ impl RetroAvInfo {
 }
 
 /// Describes various formats for framebuffers that can be sent across the API.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
 pub enum RetroPixelFormat {
     /* 0RGB1555, native endian.
      * 0 bit must be set to 0.
@@ -259,66 +1028,6 @@ impl RetroPixelFormat {
         }
     }
 
-    /// Converts from this pixel format to ARGB8888.
-    pub fn convert(&self, original_data: &[u8], width: usize, height: usize) -> Vec<u8> {
-        let mut result: Vec<u8> = Vec::with_capacity(width * height * 4);
-
-        let pixel_size = self.get_pixel_size();
-
-        match self {
-            &RetroPixelFormat::Format0RGB1555 => for y in 0..height {
-                for x in 0..width {
-                    let pos = (y * width + x) * pixel_size;
-                    let data = ((original_data[pos + 1] as u16) << 8) | (original_data[pos] as u16);
-
-                    let r = (data >> 10) & 0b11111;
-                    let g = (data >> 5) & 0b11111;
-                    let b = (data >> 0) & 0b11111;
-
-                    let scaled_r = ((r as f64) / 32.0 * 256.0) as u8;
-                    let scaled_g = ((g as f64) / 32.0 * 256.0) as u8;
-                    let scaled_b = ((b as f64) / 32.0 * 256.0) as u8;
-
-                    result.push(scaled_r);
-                    result.push(scaled_g);
-                    result.push(scaled_b);
-                    result.push(255);
-                }
-            },
-            &RetroPixelFormat::FormatXRGB8888 => for y in 0..height {
-                for x in 0..width {
-                    let pos = y * width * pixel_size + x * pixel_size;
-
-                    result.push(original_data[pos + 2]);
-                    result.push(original_data[pos + 1]);
-                    result.push(original_data[pos]);
-                    result.push(255);
-                }
-            },
-            &RetroPixelFormat::FormatRGB565 => for y in 0..height {
-                for x in 0..width {
-                    let pos = (y * width + x) * pixel_size;
-                    let data = ((original_data[pos + 1] as u16) << 8) | (original_data[pos] as u16);
-
-                    let r = (data >> 11) & 0b11111;
-                    let g = (data >> 5) & 0b111111;
-                    let b = (data >> 0) & 0b11111;
-
-                    let scaled_r = ((r * 527 + 23) >> 6) as u8;
-                    let scaled_g = ((g * 259 + 33) >> 6) as u8;
-                    let scaled_b = ((b * 527 + 23) >> 6) as u8;
-
-                    result.push(scaled_r);
-                    result.push(scaled_g);
-                    result.push(scaled_b);
-                    result.push(255);
-                }
-            },
-        }
-
-        result
-    }
-
     /// Converts from a raw integer format, as used by the LibRetro API.
     pub fn from(format: u32) -> Option<RetroPixelFormat> {
         Some(match format {
@@ -377,6 +1086,11 @@ pub enum RetroEnvironment {
     GetCurrentSoftwareFramebuffer,
     SetHWSharedContext,
     GetVFSInterface,
+    GetDiskControlInterfaceVersion,
+    SetDiskControlExtInterface,
+    GetInputMaxUsers,
+    /// Asks if `RETRO_DEVICE_ID_JOYPAD_MASK` is supported.
+    GetInputBitmasks,
 }
 
 impl RetroEnvironment {
@@ -422,6 +1136,10 @@ impl RetroEnvironment {
             40 => RetroEnvironment::GetCurrentSoftwareFramebuffer,
             44 => RetroEnvironment::SetHWSharedContext,
             45 => RetroEnvironment::GetVFSInterface,
+            51 => RetroEnvironment::GetInputBitmasks,
+            57 => RetroEnvironment::GetDiskControlInterfaceVersion,
+            58 => RetroEnvironment::SetDiskControlExtInterface,
+            61 => RetroEnvironment::GetInputMaxUsers,
             _ => return None,
         })
     }
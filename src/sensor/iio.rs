@@ -0,0 +1,168 @@
+/// Reads sensors exposed through the Linux Industrial I/O subsystem, as found on many laptops,
+/// tablets and phones.
+
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+
+use sensor::SensorKey;
+use sensor::SensorReadings;
+use sensor::SensorSource;
+
+static DEVICES_PATH: &str = "/sys/bus/iio/devices";
+
+/// A single channel of a device, such as the X axis of an accelerometer.
+struct Channel {
+    raw: PathBuf,
+    scale: f32,
+}
+
+impl Channel {
+    fn read(&self) -> Option<f32> {
+        read_value(&self.raw).map(|x| x * self.scale)
+    }
+}
+
+pub struct IIOSensor {
+    name: String,
+
+    accelerometer: Option<[Channel; 3]>,
+    gyroscope: Option<[Channel; 3]>,
+    illuminance: Option<Channel>,
+}
+
+/// Reads a single number from a sysfs attribute.
+fn read_value(path: &Path) -> Option<f32> {
+    let mut contents = String::new();
+    File::open(path).ok()?.read_to_string(&mut contents).ok()?;
+    contents.trim().parse::<f32>().ok()
+}
+
+/// Finds a channel of a device. `kind` is the IIO channel type, such as `accel`, and `axis` is
+/// an optional modifier, such as `x`.
+fn find_channel(device: &Path, kind: &str, axis: Option<&str>) -> Option<Channel> {
+    let prefix = match axis {
+        Some(axis) => format!("in_{}_{}", kind, axis),
+        None => format!("in_{}", kind),
+    };
+
+    // Some drivers provide processed values, others provide raw values with a scale which is
+    // either shared between all axes or specific to a single one.
+    let input = device.join(format!("{}_input", prefix));
+    if input.exists() {
+        return Some(Channel {
+            raw: input,
+            scale: 1.0,
+        });
+    }
+
+    let raw = device.join(format!("{}_raw", prefix));
+    if !raw.exists() {
+        return None;
+    }
+
+    let scale = read_value(&device.join(format!("{}_scale", prefix)))
+        .or_else(|| read_value(&device.join(format!("in_{}_scale", kind))))
+        .unwrap_or(1.0);
+
+    Some(Channel { raw, scale })
+}
+
+/// Finds all three axes of a channel type.
+fn find_axes(device: &Path, kind: &str) -> Option<[Channel; 3]> {
+    Some([
+        find_channel(device, kind, Some("x"))?,
+        find_channel(device, kind, Some("y"))?,
+        find_channel(device, kind, Some("z"))?,
+    ])
+}
+
+/// Reads all three axes of a channel type.
+fn read_axes(channels: &Option<[Channel; 3]>) -> [f32; 3] {
+    match channels {
+        &Some(ref channels) => [
+            channels[0].read().unwrap_or(0.0),
+            channels[1].read().unwrap_or(0.0),
+            channels[2].read().unwrap_or(0.0),
+        ],
+        &None => [0.0; 3],
+    }
+}
+
+impl SensorSource for IIOSensor {
+    fn poll(&mut self, _is_key_down: &Fn(&SensorKey) -> bool) -> SensorReadings {
+        SensorReadings {
+            accelerometer: read_axes(&self.accelerometer),
+            gyroscope: read_axes(&self.gyroscope),
+            illuminance: match self.illuminance {
+                Some(ref channel) => channel.read().unwrap_or(0.0),
+                None => 0.0,
+            },
+        }
+    }
+}
+
+impl IIOSensor {
+    /// Returns a description of the devices in use.
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Combines the sensors of every IIO device on this machine. Returns `None` if none have
+    /// sensors that cores can use.
+    pub fn find() -> Option<IIOSensor> {
+        let mut sensor = IIOSensor {
+            name: String::new(),
+
+            accelerometer: None,
+            gyroscope: None,
+            illuminance: None,
+        };
+
+        let mut names = Vec::new();
+
+        for entry in fs::read_dir(DEVICES_PATH).ok()? {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(_) => continue,
+            };
+
+            let mut used = false;
+
+            if sensor.accelerometer.is_none() {
+                sensor.accelerometer = find_axes(&path, "accel");
+                used |= sensor.accelerometer.is_some();
+            }
+
+            if sensor.gyroscope.is_none() {
+                sensor.gyroscope = find_axes(&path, "anglvel");
+                used |= sensor.gyroscope.is_some();
+            }
+
+            if sensor.illuminance.is_none() {
+                sensor.illuminance = find_channel(&path, "illuminance", None);
+                used |= sensor.illuminance.is_some();
+            }
+
+            if used {
+                let mut name = String::new();
+                match File::open(path.join("name")) {
+                    Ok(mut file) => {
+                        file.read_to_string(&mut name).ok()?;
+                    }
+                    Err(_) => name = path.to_string_lossy().into_owned(),
+                }
+                names.push(name.trim().to_owned());
+            }
+        }
+
+        if names.is_empty() {
+            return None;
+        }
+
+        sensor.name = names.join(", ");
+        Some(sensor)
+    }
+}
@@ -0,0 +1,118 @@
+/// Emulates tilting a device and changing the ambient light with the keyboard.
+
+use std::f32::consts::PI;
+use std::time::Instant;
+
+use sensor::SensorKey;
+use sensor::SensorReadings;
+use sensor::SensorSource;
+
+/// Standard gravity, in m/s^2.
+const GRAVITY: f32 = 9.80665;
+
+/// How far the device can be tilted in any direction, in radians.
+const MAX_TILT: f32 = PI / 4.0;
+
+/// How far the device tilts each time input is polled, in radians.
+const TILT_STEP: f32 = MAX_TILT / 15.0;
+
+/// How bright it is by default, in lux.
+const DEFAULT_ILLUMINANCE: f32 = 400.0;
+
+/// How much brighter or darker it gets each time input is polled.
+const ILLUMINANCE_STEP: f32 = 1.05;
+
+pub struct KeyboardSensor {
+    /// Rotation around the X axis (tilting forwards and backwards), in radians.
+    pitch: f32,
+    /// Rotation around the Y axis (tilting left and right), in radians.
+    roll: f32,
+    illuminance: f32,
+
+    last_poll: Option<Instant>,
+}
+
+/// Moves an angle towards a target by a single step.
+fn approach(value: f32, target: f32) -> f32 {
+    if value < target {
+        (value + TILT_STEP).min(target)
+    } else {
+        (value - TILT_STEP).max(target)
+    }
+}
+
+impl SensorSource for KeyboardSensor {
+    fn poll(&mut self, is_key_down: &Fn(&SensorKey) -> bool) -> SensorReadings {
+        let mut target_pitch = 0.0;
+        let mut target_roll = 0.0;
+
+        if is_key_down(&SensorKey::TiltUp) {
+            target_pitch += MAX_TILT;
+        }
+        if is_key_down(&SensorKey::TiltDown) {
+            target_pitch -= MAX_TILT;
+        }
+        if is_key_down(&SensorKey::TiltLeft) {
+            target_roll -= MAX_TILT;
+        }
+        if is_key_down(&SensorKey::TiltRight) {
+            target_roll += MAX_TILT;
+        }
+
+        if is_key_down(&SensorKey::Darker) {
+            self.illuminance = (self.illuminance / ILLUMINANCE_STEP).max(1.0);
+        }
+        if is_key_down(&SensorKey::Brighter) {
+            self.illuminance = (self.illuminance * ILLUMINANCE_STEP).min(100000.0);
+        }
+
+        let pitch = approach(self.pitch, target_pitch);
+        let roll = approach(self.roll, target_roll);
+
+        let now = Instant::now();
+        let elapsed = match self.last_poll {
+            Some(last_poll) => {
+                let elapsed = now.duration_since(last_poll);
+                elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 / 1_000_000_000.0
+            }
+            None => 0.0,
+        };
+
+        let gyroscope = if elapsed > 0.0 {
+            [
+                (pitch - self.pitch) / elapsed,
+                (roll - self.roll) / elapsed,
+                0.0,
+            ]
+        } else {
+            [0.0; 3]
+        };
+
+        self.pitch = pitch;
+        self.roll = roll;
+        self.last_poll = Some(now);
+
+        SensorReadings {
+            accelerometer: [
+                GRAVITY * roll.sin(),
+                GRAVITY * pitch.sin(),
+                GRAVITY * roll.cos() * pitch.cos(),
+            ],
+            gyroscope,
+            illuminance: self.illuminance,
+        }
+    }
+}
+
+impl KeyboardSensor {
+    /// Creates a new sensor, lying flat in a well-lit room.
+    pub fn new() -> KeyboardSensor {
+        KeyboardSensor {
+            pitch: 0.0,
+            roll: 0.0,
+            illuminance: DEFAULT_ILLUMINANCE,
+
+            last_poll: None,
+        }
+    }
+}
@@ -0,0 +1,193 @@
+/// Sensors (accelerometers, gyroscopes and light sensors) which cores can read from, such
+/// as for tilt-controlled games.
+///
+/// Most machines running oxretro don't have these, so readings can come from stand-ins.
+
+pub mod keyboard;
+pub mod script;
+
+#[cfg(target_os = "linux")]
+pub mod iio;
+
+use retro_types::RetroSensor;
+use retro_types::RetroSensorAction;
+
+/// Where sensor readings should come from.
+pub enum SensorSourceType {
+    /// A real device if one is present, otherwise the keyboard.
+    Auto,
+    /// Tilt emulated with the keyboard.
+    Keyboard,
+    /// A real device.
+    Device,
+    /// Readings replayed from a CSV file.
+    Script(String),
+}
+
+/// Keys that can be used to emulate sensors.
+pub enum SensorKey {
+    TiltUp,
+    TiltDown,
+    TiltLeft,
+    TiltRight,
+    Darker,
+    Brighter,
+}
+
+/// A snapshot of all the values of a sensor source.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SensorReadings {
+    /// In m/s^2.
+    pub accelerometer: [f32; 3],
+    /// In rad/s.
+    pub gyroscope: [f32; 3],
+    /// In lux.
+    pub illuminance: f32,
+}
+
+pub trait SensorSource {
+    /// Fetches new readings. Called each time input is polled.
+    fn poll(&mut self, is_key_down: &Fn(&SensorKey) -> bool) -> SensorReadings;
+}
+
+/// Tracks which sensors a core has enabled, and answers its queries from a source.
+pub struct Sensors {
+    source: Box<SensorSource>,
+    readings: SensorReadings,
+
+    accelerometer_enabled: bool,
+    gyroscope_enabled: bool,
+    illuminance_enabled: bool,
+}
+
+impl Sensors {
+    /// Enables or disables a sensor. All sources provide all sensors, so this always succeeds.
+    pub fn set_sensor_state(&mut self, _port: u32, action: RetroSensorAction, _rate: u32) -> bool {
+        match action {
+            RetroSensorAction::AccelerometerEnable => self.accelerometer_enabled = true,
+            RetroSensorAction::AccelerometerDisable => self.accelerometer_enabled = false,
+            RetroSensorAction::GyroscopeEnable => self.gyroscope_enabled = true,
+            RetroSensorAction::GyroscopeDisable => self.gyroscope_enabled = false,
+            RetroSensorAction::IlluminanceEnable => self.illuminance_enabled = true,
+            RetroSensorAction::IlluminanceDisable => self.illuminance_enabled = false,
+        }
+
+        true
+    }
+
+    /// Fetches new readings from the source.
+    pub fn poll(&mut self, is_key_down: &Fn(&SensorKey) -> bool) {
+        self.readings = self.source.poll(is_key_down);
+    }
+
+    /// Returns the last reading of a sensor, or 0 if it hasn't been enabled.
+    pub fn get_sensor_input(&self, _port: u32, sensor: RetroSensor) -> f32 {
+        match sensor {
+            RetroSensor::AccelerometerX if self.accelerometer_enabled => {
+                self.readings.accelerometer[0]
+            }
+            RetroSensor::AccelerometerY if self.accelerometer_enabled => {
+                self.readings.accelerometer[1]
+            }
+            RetroSensor::AccelerometerZ if self.accelerometer_enabled => {
+                self.readings.accelerometer[2]
+            }
+            RetroSensor::GyroscopeX if self.gyroscope_enabled => self.readings.gyroscope[0],
+            RetroSensor::GyroscopeY if self.gyroscope_enabled => self.readings.gyroscope[1],
+            RetroSensor::GyroscopeZ if self.gyroscope_enabled => self.readings.gyroscope[2],
+            RetroSensor::Illuminance if self.illuminance_enabled => self.readings.illuminance,
+            _ => 0.0,
+        }
+    }
+
+    /// Wraps a source of sensor readings.
+    pub fn new(source: Box<SensorSource>) -> Sensors {
+        Sensors {
+            source,
+            readings: SensorReadings::default(),
+
+            accelerometer_enabled: false,
+            gyroscope_enabled: false,
+            illuminance_enabled: false,
+        }
+    }
+}
+
+/// Builds a new source of sensor readings.
+pub fn build(source_type: &SensorSourceType) -> Box<SensorSource> {
+    match source_type {
+        &SensorSourceType::Script(ref path) => {
+            println!("Replaying sensor readings from {}", path);
+            Box::new(script::ScriptSensor::load(path).unwrap())
+        }
+        &SensorSourceType::Device => match build_device() {
+            Some(v) => v,
+            None => panic!("No sensor device available!"),
+        },
+        &SensorSourceType::Auto => match build_device() {
+            Some(v) => v,
+            None => {
+                println!("No sensor device available, emulating sensors with the keyboard");
+                Box::new(keyboard::KeyboardSensor::new())
+            }
+        },
+        &SensorSourceType::Keyboard => Box::new(keyboard::KeyboardSensor::new()),
+    }
+}
+
+/// Attempts to find a real sensor device on this machine.
+#[cfg(target_os = "linux")]
+fn build_device() -> Option<Box<SensorSource>> {
+    let device = iio::IIOSensor::find()?;
+    println!("Using sensor device: {}", device.get_name());
+    Some(Box::new(device))
+}
+
+/// Attempts to find a real sensor device on this machine.
+#[cfg(not(target_os = "linux"))]
+fn build_device() -> Option<Box<SensorSource>> {
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::SensorKey;
+    use super::SensorReadings;
+    use super::SensorSource;
+    use super::Sensors;
+
+    use retro_types::RetroSensor;
+    use retro_types::RetroSensorAction;
+
+    struct FixedSensor {}
+
+    impl SensorSource for FixedSensor {
+        fn poll(&mut self, _is_key_down: &Fn(&SensorKey) -> bool) -> SensorReadings {
+            SensorReadings {
+                accelerometer: [1.0, 2.0, 3.0],
+                gyroscope: [4.0, 5.0, 6.0],
+                illuminance: 7.0,
+            }
+        }
+    }
+
+    #[test]
+    fn enabling() {
+        let mut sensors = Sensors::new(Box::new(FixedSensor {}));
+        sensors.poll(&|_| false);
+
+        // Nothing is read until the core enables it
+        assert_eq!(sensors.get_sensor_input(0, RetroSensor::AccelerometerY), 0.0);
+
+        assert!(sensors.set_sensor_state(0, RetroSensorAction::AccelerometerEnable, 60));
+        assert_eq!(sensors.get_sensor_input(0, RetroSensor::AccelerometerY), 2.0);
+        assert_eq!(sensors.get_sensor_input(0, RetroSensor::GyroscopeZ), 0.0);
+        assert_eq!(sensors.get_sensor_input(0, RetroSensor::Illuminance), 0.0);
+
+        sensors.set_sensor_state(0, RetroSensorAction::IlluminanceEnable, 60);
+        assert_eq!(sensors.get_sensor_input(0, RetroSensor::Illuminance), 7.0);
+
+        sensors.set_sensor_state(0, RetroSensorAction::AccelerometerDisable, 60);
+        assert_eq!(sensors.get_sensor_input(0, RetroSensor::AccelerometerY), 0.0);
+    }
+}
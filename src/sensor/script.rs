@@ -0,0 +1,148 @@
+/// Replays sensor readings from a CSV file, for testing cores with reproducible input.
+///
+/// Each row is `frame,accel_x,accel_y,accel_z,gyro_x,gyro_y,gyro_z,illuminance`. A reading
+/// holds from its frame until the frame of the next row. Lines starting with `#`, and a header
+/// on the first row that isn't a comment, are ignored.
+
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+
+use sensor::SensorKey;
+use sensor::SensorReadings;
+use sensor::SensorSource;
+
+pub struct ScriptSensor {
+    /// Readings, sorted by the frame they start on.
+    readings: Vec<(u64, SensorReadings)>,
+    next_reading: usize,
+
+    current: SensorReadings,
+    frame: u64,
+}
+
+/// Parses a single row of a script. Returns `None` if it isn't a row of numbers.
+fn parse_row(line: &str) -> Option<(u64, SensorReadings)> {
+    let mut fields = line.split(',').map(|x| x.trim());
+
+    let frame = fields.next()?.parse::<u64>().ok()?;
+
+    let mut values = [0.0 as f32; 7];
+    for value in values.iter_mut() {
+        *value = fields.next()?.parse::<f32>().ok()?;
+    }
+
+    Some((
+        frame,
+        SensorReadings {
+            accelerometer: [values[0], values[1], values[2]],
+            gyroscope: [values[3], values[4], values[5]],
+            illuminance: values[6],
+        },
+    ))
+}
+
+impl SensorSource for ScriptSensor {
+    fn poll(&mut self, _is_key_down: &Fn(&SensorKey) -> bool) -> SensorReadings {
+        while self.next_reading < self.readings.len()
+            && self.readings[self.next_reading].0 <= self.frame
+        {
+            self.current = self.readings[self.next_reading].1;
+            self.next_reading += 1;
+        }
+
+        self.frame += 1;
+
+        self.current
+    }
+}
+
+impl ScriptSensor {
+    /// Loads a script from disk.
+    pub fn load(path: &str) -> io::Result<ScriptSensor> {
+        ScriptSensor::parse(BufReader::new(File::open(path)?))
+    }
+
+    pub fn parse<R: BufRead>(reader: R) -> io::Result<ScriptSensor> {
+        let mut readings = Vec::new();
+        let mut first_row = true;
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let is_first_row = first_row;
+            first_row = false;
+
+            match parse_row(line) {
+                Some(reading) => readings.push(reading),
+                // A header may follow any comments at the top of the file
+                None if is_first_row => continue,
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Malformed sensor reading on line {}", i + 1),
+                    ))
+                }
+            }
+        }
+
+        readings.sort_by_key(|x| x.0);
+
+        Ok(ScriptSensor {
+            readings,
+            next_reading: 0,
+
+            current: SensorReadings::default(),
+            frame: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ScriptSensor;
+    use super::parse_row;
+
+    use sensor::SensorSource;
+
+    #[test]
+    fn rows() {
+        let (frame, readings) = parse_row("12, 0.5, -9.8, 0, 1, 2, 3, 400").unwrap();
+        assert_eq!(frame, 12);
+        assert_eq!(readings.accelerometer, [0.5, -9.8, 0.0]);
+        assert_eq!(readings.gyroscope, [1.0, 2.0, 3.0]);
+        assert_eq!(readings.illuminance, 400.0);
+
+        assert!(parse_row("frame,accel_x,accel_y,accel_z,gyro_x,gyro_y,gyro_z,lux").is_none());
+        assert!(parse_row("12,0.5,-9.8").is_none());
+        assert!(parse_row("-1,0,0,0,0,0,0,0").is_none());
+    }
+
+    #[test]
+    fn headers() {
+        let script = "# Recorded on a phone\n\nframe,ax,ay,az,gx,gy,gz,lux\n0,1,0,0,0,0,0,0\n";
+        assert!(ScriptSensor::parse(script.as_bytes()).is_ok());
+
+        // Only the first row can be a header
+        let script = "0,1,0,0,0,0,0,0\nframe,ax,ay,az,gx,gy,gz,lux\n";
+        assert!(ScriptSensor::parse(script.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn replay() {
+        let script = "4,0,0,0,0,0,0,40\n1,0,0,0,0,0,0,10\n";
+        let mut sensor = ScriptSensor::parse(script.as_bytes()).unwrap();
+
+        // Readings hold until the next row, and are sorted by frame
+        let lux: Vec<f32> = (0..6)
+            .map(|_| sensor.poll(&|_| false).illuminance)
+            .collect();
+        assert_eq!(lux, vec![0.0, 10.0, 10.0, 10.0, 40.0, 40.0]);
+    }
+}